@@ -1,6 +1,10 @@
-use clap::{ArgAction, Args, Parser, Subcommand, builder::styling};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum, builder::styling};
 use std::path::PathBuf;
+use wiki_stats::calc::link_prediction::LinkScore;
+use wiki_stats::calc::pagerank::PageRanking;
+use wiki_stats::calc::traversal::TraversalMode;
 use wiki_stats::download::ALL_DB_TABLES;
+use wiki_stats::stats::queries::DEFAULT_EXCLUDED_TITLES;
 
 const STYLES: styling::Styles = styling::Styles::styled()
     .header(styling::AnsiColor::Green.on_default().bold())
@@ -64,6 +68,12 @@ pub enum Commands {
         subcommands: StatsCommands,
     },
 
+    /// Graph algorithms on a single wiki
+    Graph {
+        #[command(subcommand)]
+        subcommands: GraphCommands,
+    },
+
     /// Various task related commands
     Tasks {
         #[command(subcommand)]
@@ -96,6 +106,334 @@ pub enum TasksCommands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum GraphCommands {
+    /// Precompute bfs distances from and to landmark pages and store them in the wiki db
+    ComputeLandmarks {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Number of landmarks
+        #[arg(short, long, default_value_t = 16)]
+        num_landmarks: usize,
+
+        /// How the landmarks are chosen
+        #[arg(long, value_enum, default_value_t = PageRankingArg::Degree)]
+        selection: PageRankingArg,
+
+        /// Number of threads per bfs
+        #[arg(short, long, default_value_t = 8)]
+        threads: usize,
+    },
+
+    /// Estimate the distance between two pages using the stored landmarks
+    Distance {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Title of the start page
+        #[arg(long)]
+        from: String,
+
+        /// Title of the end page
+        #[arg(long)]
+        to: String,
+
+        /// Also search the shortest path, pruned by the landmark bounds
+        #[arg(long, default_value_t = false)]
+        exact: bool,
+    },
+
+    /// Score the difficulty of a wiki game between two pages
+    GameDifficulty {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Title of the start page
+        #[arg(short, long)]
+        from: String,
+
+        /// Title of the target page
+        #[arg(short, long)]
+        to: String,
+
+        /// Seed of the random walks
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+
+    /// Sample random wiki game pairs with a difficulty score in [min-score, max-score]
+    GenerateGames {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Lowest difficulty score (0 is trivial)
+        #[arg(long, default_value_t = 0.3)]
+        min_score: f64,
+
+        /// Highest difficulty score (1 is hardest)
+        #[arg(long, default_value_t = 0.7)]
+        max_score: f64,
+
+        /// Number of pairs
+        #[arg(short, long, default_value_t = 10)]
+        num_pairs: usize,
+
+        /// Give up after scoring this many pairs
+        #[arg(long, default_value_t = 1000)]
+        max_attempts: usize,
+
+        /// Seed of the random walks
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Do not use disambiguation pages as start or target
+        #[arg(long, default_value_t = false)]
+        exclude_disambiguation: bool,
+    },
+
+    /// Export the shortest path distances between the top pages as json or csv (by file extension)
+    DistanceMatrix {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Number of pages
+        #[arg(short, long, default_value_t = 50)]
+        num_pages: usize,
+
+        /// How the top pages are chosen
+        #[arg(long, value_enum, default_value_t = PageRankingArg::InDegree)]
+        ranking: PageRankingArg,
+
+        /// Number of threads per bfs
+        #[arg(short, long, default_value_t = 8)]
+        threads: usize,
+
+        /// Output file, .csv or .json
+        #[arg(short, long, value_name = "PATH")]
+        output_path: PathBuf,
+    },
+
+    /// Depth histograms, deepest page and number of reachable pages from and to a page.
+    /// Stored in the wiki db, so repeated lookups are instant
+    DistanceProfile {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Title of the page
+        #[arg(short, long)]
+        title: String,
+    },
+
+    /// Count the pages that reach a page within max-depth links (bfs over incoming links)
+    Reachability {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Title of the target page
+        #[arg(short, long)]
+        title: String,
+
+        /// Maximum number of links
+        #[arg(short, long, default_value_t = 3)]
+        max_depth: u32,
+
+        /// Write the reaching pages to this file, .csv or .json
+        #[arg(short, long, value_name = "PATH")]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Find the pages and links whose removal disconnects the wiki (ignoring link directions)
+    CutStructure {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Write the articulation points and bridges as json to this file
+        #[arg(short, long, value_name = "PATH")]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Shortest path between two pages, following outgoing, incoming or both links
+    Path {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Title of the start page
+        #[arg(short, long)]
+        from: String,
+
+        /// Title of the end page
+        #[arg(short, long)]
+        to: String,
+
+        /// Which links the path may follow
+        #[arg(short, long, value_enum, default_value_t = TraversalModeArg::Directed)]
+        mode: TraversalModeArg,
+    },
+
+    /// Count the edge and vertex disjoint paths between two pages
+    DisjointPaths {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Title of the start page
+        #[arg(short, long)]
+        from: String,
+
+        /// Title of the end page
+        #[arg(short, long)]
+        to: String,
+    },
+
+    /// Precompute the MinHash signatures of all pages for the similar pages search
+    ComputeSignatures {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Number of threads
+        #[arg(short, long, default_value_t = 8)]
+        threads: usize,
+    },
+
+    /// Find the pages with the most similar links to a page. Requires compute-signatures
+    SimilarPages {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Title of the page
+        #[arg(short, long)]
+        title: String,
+
+        /// Number of results
+        #[arg(short, long, default_value_t = 20)]
+        num_results: usize,
+    },
+
+    /// Suggest pages that probably should link to a page, based on shared neighbours
+    SuggestLinks {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Title of the page
+        #[arg(short, long)]
+        title: String,
+
+        /// Suggest pages the page should link to instead
+        #[arg(long, default_value_t = false)]
+        outgoing: bool,
+
+        /// How the candidates are ranked
+        #[arg(long, value_enum, default_value_t = LinkScoreArg::AdamicAdar)]
+        score: LinkScoreArg,
+
+        /// Number of suggestions
+        #[arg(short, long, default_value_t = 20)]
+        num_suggestions: usize,
+    },
+
+    /// Precompute the shortest paths from and to the pages with the most incoming links.
+    /// Stored in {wiki}_sp_database.sqlite next to the wiki db; already stored pages are skipped
+    PrecalcShortestPaths {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Number of pages (ordered by incoming links)
+        #[arg(short, long, default_value_t = 100)]
+        num_sources: usize,
+
+        /// Number of threads per bfs
+        #[arg(short, long, default_value_t = 8)]
+        threads: usize,
+    },
+
+    /// Compare the wiki db to the one of an older dump date: added, removed and renamed pages,
+    /// added and removed links
+    Diff {
+        #[command(flatten)]
+        args: WikiDbArgs,
+
+        /// Path containing the older sqlite db files
+        #[arg(long, value_name = "PATH")]
+        old_db_path: PathBuf,
+
+        /// Write every change as a json line to this file
+        #[arg(short, long, value_name = "PATH")]
+        output_path: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PageRankingArg {
+    /// Pages with the most incoming + outgoing links
+    Degree,
+    /// Pages with the most incoming links
+    InDegree,
+    /// Pages with the highest PageRank
+    Pagerank,
+}
+
+impl From<PageRankingArg> for PageRanking {
+    fn from(ranking: PageRankingArg) -> Self {
+        match ranking {
+            PageRankingArg::Degree => PageRanking::Degree,
+            PageRankingArg::InDegree => PageRanking::InDegree,
+            PageRankingArg::Pagerank => PageRanking::PageRank,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum LinkScoreArg {
+    /// Number of shared neighbours
+    CommonNeighbours,
+    /// Shared neighbours weighted by 1 / ln(degree)
+    AdamicAdar,
+    /// Shared neighbours divided by the size of the union of both neighbourhoods
+    Jaccard,
+}
+
+impl From<LinkScoreArg> for LinkScore {
+    fn from(score: LinkScoreArg) -> Self {
+        match score {
+            LinkScoreArg::CommonNeighbours => LinkScore::CommonNeighbours,
+            LinkScoreArg::AdamicAdar => LinkScore::AdamicAdar,
+            LinkScoreArg::Jaccard => LinkScore::Jaccard,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TraversalModeArg {
+    /// Outgoing links
+    Directed,
+    /// Incoming links
+    Reverse,
+    /// Outgoing and incoming links
+    Undirected,
+}
+
+impl From<TraversalModeArg> for TraversalMode {
+    fn from(mode: TraversalModeArg) -> Self {
+        match mode {
+            TraversalModeArg::Directed => TraversalMode::Directed,
+            TraversalModeArg::Reverse => TraversalMode::Reverse,
+            TraversalModeArg::Undirected => TraversalMode::Undirected,
+        }
+    }
+}
+
+/// Arguments selecting a single wiki db
+#[derive(Args, Debug)]
+pub struct WikiDbArgs {
+    /// Path containing the sqlite db files
+    #[arg(long, value_name = "PATH")]
+    pub db_path: PathBuf,
+
+    /// Name of the wiki, e.g. dewiki
+    #[arg(short, long)]
+    pub wiki: String,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum StatsCommands {
     /// Generate default stats
@@ -110,9 +448,25 @@ pub enum StatsCommands {
         #[command(flatten)]
         sample_args: SampleOptions,
 
+        #[command(flatten)]
+        ranking_args: RankingArgs,
+
         /// Add sizes of the online tables
         #[arg(long, default_value_t = false, help_heading = "WikiSizes Options")]
         add_web_wiki_sizes: bool,
+
+        /// Only generate these stats (comma separated names, e.g. num_pages,most_linked)
+        #[arg(long, value_delimiter = ',', num_args = 1..)]
+        only: Vec<String>,
+
+        /// Do not generate these stats (comma separated names). Existing records are kept
+        #[arg(long, value_delimiter = ',', num_args = 1..)]
+        skip: Vec<String>,
+
+        /// SQLite stats store to write the stats to. The output json is exported from it, so it
+        /// includes the wikis of other runs of the same dump date
+        #[arg(long, value_name = "PATH")]
+        store_path: Option<PathBuf>,
     },
 
     /// Generate BFS sample stats (quite expensive). Make sure the output json file was already used for the normal stats
@@ -124,6 +478,43 @@ pub enum StatsCommands {
         sample_args: SampleOptions,
     },
 
+    /// Add the shortest path distances between the top pages of each wiki (expensive, one bfs per page)
+    AddDistanceMatrix {
+        #[command(flatten)]
+        args: StatsArgs,
+
+        /// Number of pages
+        #[arg(short, long, default_value_t = 50)]
+        num_pages: usize,
+
+        /// How the top pages are chosen
+        #[arg(long, value_enum, default_value_t = PageRankingArg::InDegree)]
+        ranking: PageRankingArg,
+
+        /// Number of threads per bfs
+        #[arg(short, long, default_value_t = 8)]
+        threads: usize,
+
+        /// Overwrite existing distance matrices in the output json file
+        #[arg(long, default_value_t = false)]
+        overwrite: bool,
+    },
+
+    /// Add the changes since an older dump date (pages and links added, removed, renamed) of each
+    /// wiki. The dump date is taken from the parent directory of --old-db-path
+    AddLinkChurn {
+        #[command(flatten)]
+        args: StatsArgs,
+
+        /// Path containing the older sqlite db files
+        #[arg(long, value_name = "PATH")]
+        old_db_path: PathBuf,
+
+        /// Overwrite existing summaries in the output json file
+        #[arg(long, default_value_t = false)]
+        overwrite: bool,
+    },
+
     /// Add sizes of the online table sizes and the downloaded sqlite files to the stats json file
     AddWebWikiSizes {
         #[command(flatten)]
@@ -133,6 +524,60 @@ pub enum StatsCommands {
         #[arg(short, long, value_name = "PATH")]
         output_path: PathBuf,
     },
+
+    /// Compare two stats json files: numeric deltas, rank changes of the top pages and shifts of the
+    /// sampled path length histograms
+    Diff {
+        /// The older stats json file
+        #[arg(long, value_name = "PATH")]
+        old: PathBuf,
+
+        /// The newer stats json file
+        #[arg(long, value_name = "PATH")]
+        new: PathBuf,
+
+        /// Write the diff as json to this file
+        #[arg(short, long, value_name = "PATH")]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Combine the numeric stats of all stats json files (all dump dates) into one time series json
+    /// file with the changes between the dump dates
+    TimeSeries {
+        /// Path containing the json statistics files
+        #[arg(long, value_name = "PATH")]
+        stats_path: PathBuf,
+
+        /// Output of the time series json file
+        #[arg(short, long, value_name = "PATH")]
+        output_path: PathBuf,
+    },
+
+    /// Import stats json files into a SQLite stats store, replacing the stored values
+    Import {
+        /// The stats json files
+        #[arg(num_args = 1.., required = true)]
+        stats_paths: Vec<PathBuf>,
+
+        /// The SQLite stats store, created if it does not exist
+        #[arg(long, value_name = "PATH")]
+        store_path: PathBuf,
+    },
+
+    /// Export the stats of a dump date from a SQLite stats store to a json file
+    Export {
+        /// The SQLite stats store
+        #[arg(long, value_name = "PATH")]
+        store_path: PathBuf,
+
+        /// Dump date of the stats (e.g. 20240501)
+        #[arg(long)]
+        dump_date: String,
+
+        /// Output of the statistic json file
+        #[arg(short, long, value_name = "PATH")]
+        output_path: PathBuf,
+    },
 }
 
 #[derive(Args, Debug)]
@@ -187,6 +632,31 @@ pub struct SampleOptions {
     /// Overwrite existing bfs stats in the output json file
     #[arg(long, default_value_t = false, help_heading = "Sample Options")]
     pub overwrite: bool,
+
+    /// Do not start from disambiguation pages
+    #[arg(long, default_value_t = false, help_heading = "Sample Options")]
+    pub exclude_disambiguation: bool,
+}
+
+/// Arguments of the most_linked and most_links rankings
+#[derive(Args, Debug)]
+pub struct RankingArgs {
+    /// Number of pages per ranking
+    #[arg(long, default_value_t = 10, help_heading = "Ranking Options")]
+    pub top: usize,
+
+    /// Regex of page titles left out of the filtered rankings, repeat it for several (regexes
+    /// contain commas). Replaces the default years, dates and lists, an empty one excludes none
+    #[arg(long, action = ArgAction::Append, default_values = &DEFAULT_EXCLUDED_TITLES, help_heading = "Ranking Options")]
+    pub exclude_titles: Vec<String>,
+
+    /// Keep redirects in the filtered rankings
+    #[arg(long, default_value_t = false, help_heading = "Ranking Options")]
+    pub include_redirects: bool,
+
+    /// Keep disambiguation pages in the filtered rankings
+    #[arg(long, default_value_t = false, help_heading = "Ranking Options")]
+    pub include_disambiguation: bool,
 }
 
 /// Arguments wikis
@@ -15,7 +15,6 @@ use parse_mediawiki_sql::{
     schemas::{LinkTarget, PageLink},
     utils::{Mmap, memory_map},
 };
-use schemars::schema_for;
 use wiki_stats::{
     calc::MAX_SIZE,
     parse_dump_date,
@@ -239,7 +238,7 @@ pub async fn handle_debug_commands(subcommands: DebugCommands) {
         }
         DebugCommands::GenStatsJSONSchema => {
             let file = File::create("stats-schema.json").expect("Failed creating file");
-            let schema = schema_for!(stats::Stats);
+            let schema = stats::registry::stats_schema();
             serde_json::to_writer_pretty(file, &schema).expect("Failed writing schema to file");
             println!("Wrote json schema to stats-schema.json");
         }
@@ -0,0 +1,520 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use colored::Colorize;
+use parse_mediawiki_sql::field_types::{PageId, PageTitle};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rusqlite::Connection;
+use wiki_stats::{
+    WikiIdent,
+    calc::{
+        difficulty::{DifficultyOptions, game_difficulty, generate_game_pairs},
+        distance_matrix::compute_distance_matrix,
+        distance_profile::distance_profile,
+        landmarks::{LandmarkOracle, bfs_bidirectional_alt},
+        link_prediction::suggest_links,
+        pagerank::top_pages,
+        precalc_shortest_path_trees,
+        reachability::reverse_reachability,
+        robustness::{articulation_points_and_bridges, count_disjoint_paths},
+        similarity::{compute_signatures, similar_pages},
+        traversal::{HopDirection, shortest_path_mode},
+    },
+    sqlite::{
+        diff::diff_db_files,
+        join_db_sp_wiki_path, join_db_wiki_path,
+        landmarks::{load_landmarks, save_landmarks},
+        page_links::{LinkCaches, get_cache},
+        paths::SpDirection,
+        similarity::save_signatures,
+        table_exists,
+        title_id_conv::{page_id_to_title, page_title_to_id},
+    },
+};
+
+use crate::{
+    args::{GraphCommands, WikiDbArgs},
+    print_error_and_exit,
+};
+
+pub async fn handle_graph_commands(subcommands: GraphCommands) {
+    match subcommands {
+        GraphCommands::ComputeLandmarks {
+            args,
+            num_landmarks,
+            selection,
+            threads,
+        } => {
+            let path = wiki_db_path(args);
+
+            println!("> Loading links..");
+            let caches = LinkCaches::load(&path, None);
+
+            let t1 = Instant::now();
+            let landmarks = top_pages(
+                &caches.outgoing,
+                &caches.incoming,
+                num_landmarks,
+                selection.into(),
+            );
+            println!("> Computing distances of {} landmarks..", landmarks.len());
+            let oracle = LandmarkOracle::compute(landmarks, &caches, threads);
+            save_landmarks(&path, &oracle);
+
+            println!(
+                "{}",
+                format!("Saved landmarks in {:?}", t1.elapsed()).green()
+            );
+        }
+
+        GraphCommands::Distance {
+            args,
+            from,
+            to,
+            exact,
+        } => {
+            let path = wiki_db_path(args);
+            let conn = Connection::open(&path)
+                .unwrap_or_else(|e| print_error_and_exit!("Failed opening {path:?}: {e}"));
+
+            let oracle = load_landmarks(&path).unwrap_or_else(|| {
+                print_error_and_exit!("No landmarks in {path:?}. Run graph compute-landmarks first")
+            });
+
+            let from_id = title_to_id(&from, &conn);
+            let to_id = title_to_id(&to, &conn);
+
+            let t1 = Instant::now();
+            let bounds = oracle.distance_bounds(from_id, to_id);
+            println!("Bounds: {bounds:?} ({:?})", t1.elapsed());
+
+            if exact {
+                println!("> Loading links..");
+                let caches = LinkCaches::load(&path, None);
+                let result = bfs_bidirectional_alt(from_id, to_id, &conn, &caches, &oracle);
+                match result.path {
+                    Some(path) => {
+                        let titles: Vec<String> = path
+                            .iter()
+                            .map(|pid| page_id_to_title(pid, &conn).unwrap().0)
+                            .collect();
+                        println!("Distance: {} | {}", path.len() - 1, titles.join(" -> "));
+                    }
+                    None => println!("No path from {from} to {to}"),
+                }
+                println!(
+                    "Visited: {} | Pruned links: {} | {}ms",
+                    result.visited, result.pruned_links, result.elapsed_ms
+                );
+            }
+        }
+
+        GraphCommands::GameDifficulty {
+            args,
+            from,
+            to,
+            seed,
+        } => {
+            let path = wiki_db_path(args);
+            let conn = Connection::open(&path)
+                .unwrap_or_else(|e| print_error_and_exit!("Failed opening {path:?}: {e}"));
+            let from_id = title_to_id(&from, &conn);
+            let to_id = title_to_id(&to, &conn);
+
+            println!("> Loading links..");
+            let caches = LinkCaches::load(&path, None);
+
+            let t1 = Instant::now();
+            let difficulty = game_difficulty(
+                from_id,
+                to_id,
+                &conn,
+                &caches,
+                DifficultyOptions::default(),
+                &mut seeded_rng(seed),
+            );
+            match difficulty {
+                Some(difficulty) => println!("{difficulty:#?}"),
+                None => println!("No path from {from} to {to}"),
+            }
+            println!("Took {:?}", t1.elapsed());
+        }
+
+        GraphCommands::GenerateGames {
+            args,
+            min_score,
+            max_score,
+            num_pairs,
+            max_attempts,
+            seed,
+            exclude_disambiguation,
+        } => {
+            let path = wiki_db_path(args);
+            let conn = Connection::open(&path)
+                .unwrap_or_else(|e| print_error_and_exit!("Failed opening {path:?}: {e}"));
+
+            println!("> Loading links..");
+            let caches = LinkCaches::load(&path, None);
+
+            let pairs = generate_game_pairs(
+                &path,
+                &caches,
+                min_score..=max_score,
+                num_pairs,
+                max_attempts,
+                DifficultyOptions {
+                    exclude_disambiguation,
+                    ..DifficultyOptions::default()
+                },
+                &mut seeded_rng(seed),
+            );
+            if pairs.len() < num_pairs {
+                println!(
+                    "{}",
+                    format!(
+                        "Only found {} pairs in {max_attempts} attempts",
+                        pairs.len()
+                    )
+                    .yellow()
+                );
+            }
+
+            for pair in pairs {
+                println!(
+                    "{} -> {} | score: {:.3} | distance: {} | shortest paths: {}",
+                    page_id_to_title(&pair.start, &conn).unwrap().0,
+                    page_id_to_title(&pair.target, &conn).unwrap().0,
+                    pair.difficulty.score,
+                    pair.difficulty.distance,
+                    pair.difficulty.num_shortest_paths
+                );
+            }
+        }
+
+        GraphCommands::DistanceMatrix {
+            args,
+            num_pages,
+            ranking,
+            threads,
+            output_path,
+        } => {
+            let wiki_name = args.wiki.clone();
+            let db_path = wiki_db_path(args);
+
+            let matrix = compute_distance_matrix(
+                WikiIdent { wiki_name, db_path },
+                num_pages,
+                ranking.into(),
+                threads,
+            );
+
+            let content = if output_path.extension().is_some_and(|ext| ext == "csv") {
+                matrix.to_csv()
+            } else {
+                serde_json::to_string_pretty(&matrix).unwrap()
+            };
+            fs::write(&output_path, content).unwrap_or_else(|e| {
+                print_error_and_exit!("Failed writing to {output_path:?}: {e}")
+            });
+            println!("{}", format!("Written to {output_path:?}").green());
+        }
+
+        GraphCommands::DistanceProfile { args, title } => {
+            let path = wiki_db_path(args);
+            let conn = Connection::open(&path)
+                .unwrap_or_else(|e| print_error_and_exit!("Failed opening {path:?}: {e}"));
+            let page_id = title_to_id(&title, &conn);
+
+            println!("> Loading links..");
+            let caches = LinkCaches::load(&path, None);
+
+            for direction in [SpDirection::FromSource, SpDirection::ToSource] {
+                let t1 = Instant::now();
+                let profile = distance_profile(page_id, direction, &conn, &caches);
+
+                let mut histogram: Vec<(u32, u64)> = profile.depth_histogram.into_iter().collect();
+                histogram.sort();
+                println!(
+                    "{direction:?} | reachable: {} | deepest: {} ({}) | histogram: {histogram:?} ({:?})",
+                    profile.num_reachable,
+                    page_id_to_title(&profile.deepest_id, &conn).unwrap().0,
+                    profile.max_depth,
+                    t1.elapsed()
+                );
+            }
+        }
+
+        GraphCommands::Reachability {
+            args,
+            title,
+            max_depth,
+            output_path,
+        } => {
+            let path = wiki_db_path(args);
+            let conn = Connection::open(&path)
+                .unwrap_or_else(|e| print_error_and_exit!("Failed opening {path:?}: {e}"));
+            let page_id = title_to_id(&title, &conn);
+
+            println!("> Loading links..");
+            let caches = LinkCaches::load(&path, None);
+
+            let t1 = Instant::now();
+            let reachability = reverse_reachability(page_id, max_depth, &conn, &caches);
+            println!(
+                "{} pages reach {title} within {max_depth} links ({:?})",
+                reachability.num_reaching(),
+                t1.elapsed()
+            );
+            for (depth, num_pages) in reachability.num_per_depth().iter().enumerate().skip(1) {
+                println!("Depth {depth}: {num_pages}");
+            }
+
+            if let Some(output_path) = output_path {
+                let content = if output_path.extension().is_some_and(|ext| ext == "csv") {
+                    reachability.to_csv()
+                } else {
+                    serde_json::to_string_pretty(&reachability).unwrap()
+                };
+                fs::write(&output_path, content).unwrap_or_else(|e| {
+                    print_error_and_exit!("Failed writing to {output_path:?}: {e}")
+                });
+                println!("{}", format!("Written to {output_path:?}").green());
+            }
+        }
+
+        GraphCommands::CutStructure { args, output_path } => {
+            let path = wiki_db_path(args);
+
+            println!("> Loading links..");
+            let outgoing = get_cache(&path, None, false);
+
+            let t1 = Instant::now();
+            let cut = articulation_points_and_bridges(&outgoing);
+            println!(
+                "Articulation points: {} | Bridges: {} ({:?})",
+                cut.articulation_points.len(),
+                cut.bridges.len(),
+                t1.elapsed()
+            );
+
+            if let Some(output_path) = output_path {
+                fs::write(&output_path, serde_json::to_string_pretty(&cut).unwrap())
+                    .unwrap_or_else(|e| {
+                        print_error_and_exit!("Failed writing to {output_path:?}: {e}")
+                    });
+                println!("{}", format!("Written to {output_path:?}").green());
+            }
+        }
+
+        GraphCommands::Path {
+            args,
+            from,
+            to,
+            mode,
+        } => {
+            let path = wiki_db_path(args);
+            let conn = Connection::open(&path)
+                .unwrap_or_else(|e| print_error_and_exit!("Failed opening {path:?}: {e}"));
+            let from_id = title_to_id(&from, &conn);
+            let to_id = title_to_id(&to, &conn);
+
+            println!("> Loading links..");
+            let caches = LinkCaches::load(&path, None);
+
+            let t1 = Instant::now();
+            match shortest_path_mode(from_id, to_id, mode.into(), &conn, &caches) {
+                Some(result) => {
+                    let mut line = page_id_to_title(&result.pages[0], &conn).unwrap().0;
+                    for (page_id, hop) in result.pages.iter().skip(1).zip(&result.hops) {
+                        let arrow = match hop {
+                            HopDirection::Forward => "->",
+                            HopDirection::Backward => "<-",
+                        };
+                        line.push_str(&format!(
+                            " {arrow} {}",
+                            page_id_to_title(page_id, &conn).unwrap().0
+                        ));
+                    }
+                    println!("Distance: {} | {line}", result.hops.len());
+                }
+                None => println!("No path from {from} to {to}"),
+            }
+            println!("({:?})", t1.elapsed());
+        }
+
+        GraphCommands::DisjointPaths { args, from, to } => {
+            let path = wiki_db_path(args);
+            let conn = Connection::open(&path)
+                .unwrap_or_else(|e| print_error_and_exit!("Failed opening {path:?}: {e}"));
+            let from_id = title_to_id(&from, &conn);
+            let to_id = title_to_id(&to, &conn);
+
+            println!("> Loading links..");
+            let outgoing = get_cache(&path, None, false);
+            let incoming = get_cache(&path, None, true);
+
+            let t1 = Instant::now();
+            let edge_disjoint = count_disjoint_paths(from_id, to_id, &outgoing, &incoming, false);
+            let vertex_disjoint = count_disjoint_paths(from_id, to_id, &outgoing, &incoming, true);
+            println!(
+                "Edge disjoint paths: {edge_disjoint} | Vertex disjoint paths: {vertex_disjoint} ({:?})",
+                t1.elapsed()
+            );
+        }
+
+        GraphCommands::ComputeSignatures { args, threads } => {
+            let path = wiki_db_path(args);
+
+            println!("> Loading links..");
+            let outgoing = get_cache(&path, None, false);
+            let incoming = get_cache(&path, None, true);
+
+            let t1 = Instant::now();
+            let signatures = compute_signatures(&outgoing, &incoming, threads);
+            save_signatures(&path, &signatures);
+            println!(
+                "{}",
+                format!(
+                    "Saved the signatures of {} pages in {:?}",
+                    signatures.len(),
+                    t1.elapsed()
+                )
+                .green()
+            );
+        }
+
+        GraphCommands::SimilarPages {
+            args,
+            title,
+            num_results,
+        } => {
+            let path = wiki_db_path(args);
+            let conn = Connection::open(&path)
+                .unwrap_or_else(|e| print_error_and_exit!("Failed opening {path:?}: {e}"));
+            if !table_exists(&conn, "PageSignature") {
+                print_error_and_exit!(
+                    "No signatures in {path:?}. Run graph compute-signatures first"
+                );
+            }
+            let page_id = title_to_id(&title, &conn);
+
+            println!("> Loading links..");
+            let caches = LinkCaches::load(&path, None);
+
+            let t1 = Instant::now();
+            let similar = similar_pages(page_id, &conn, &caches, num_results);
+            println!("Took {:?}", t1.elapsed());
+
+            for page in similar {
+                println!(
+                    "{} | weighted cosine: {:.3} | estimated jaccard: {:.3}",
+                    page_id_to_title(&page.page_id, &conn).unwrap().0,
+                    page.weighted_cosine,
+                    page.estimated_jaccard
+                );
+            }
+        }
+
+        GraphCommands::SuggestLinks {
+            args,
+            title,
+            outgoing,
+            score,
+            num_suggestions,
+        } => {
+            let path = wiki_db_path(args);
+            let conn = Connection::open(&path)
+                .unwrap_or_else(|e| print_error_and_exit!("Failed opening {path:?}: {e}"));
+            let page_id = title_to_id(&title, &conn);
+
+            println!("> Loading links..");
+            let caches = LinkCaches::load(&path, None);
+
+            let t1 = Instant::now();
+            let suggestions = suggest_links(
+                page_id,
+                &conn,
+                &caches,
+                !outgoing,
+                score.into(),
+                num_suggestions,
+            );
+            println!("Took {:?}", t1.elapsed());
+
+            for suggestion in suggestions {
+                println!(
+                    "{} | common neighbours: {} | adamic adar: {:.3} | jaccard: {:.3}",
+                    page_id_to_title(&suggestion.page_id, &conn).unwrap().0,
+                    suggestion.common_neighbours,
+                    suggestion.adamic_adar,
+                    suggestion.jaccard
+                );
+            }
+        }
+
+        GraphCommands::PrecalcShortestPaths {
+            args,
+            num_sources,
+            threads,
+        } => {
+            let sp_path = join_db_sp_wiki_path(&args.db_path, &args.wiki);
+            let path = wiki_db_path(args);
+
+            let t1 = Instant::now();
+            precalc_shortest_path_trees(&path, &sp_path, num_sources, threads);
+            println!(
+                "{}",
+                format!("Saved shortest paths to {sp_path:?} in {:?}", t1.elapsed()).green()
+            );
+        }
+
+        GraphCommands::Diff {
+            args,
+            old_db_path,
+            output_path,
+        } => {
+            let old_path = join_db_wiki_path(old_db_path, &args.wiki);
+            if !old_path.exists() {
+                print_error_and_exit!("{} Database at {old_path:?} does not exist", args.wiki);
+            }
+            let path = wiki_db_path(args);
+
+            let t1 = Instant::now();
+            let summary = diff_db_files(&old_path, &path, output_path.as_deref());
+            println!("Took {:?}", t1.elapsed());
+            println!(
+                "pages: +{} -{} renamed {} | links: +{} -{}",
+                summary.added_pages,
+                summary.removed_pages,
+                summary.renamed_pages,
+                summary.added_links,
+                summary.removed_links
+            );
+            if let Some(output_path) = output_path {
+                println!("{}", format!("Saved changes to {output_path:?}").green());
+            }
+        }
+    }
+}
+
+fn wiki_db_path(args: WikiDbArgs) -> PathBuf {
+    let path = join_db_wiki_path(args.db_path, &args.wiki);
+    if !path.exists() {
+        print_error_and_exit!("{} Database at {path:?} does not exist", args.wiki);
+    }
+    path
+}
+
+fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+fn title_to_id(title: &str, conn: &Connection) -> PageId {
+    page_title_to_id(&PageTitle(title.to_string()), conn)
+        .unwrap_or_else(|| print_error_and_exit!("{title} is not a valid page"))
+}
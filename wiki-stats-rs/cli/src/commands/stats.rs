@@ -1,42 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
 use wiki_stats::sqlite::get_all_database_files;
+use wiki_stats::stats::diff::StatsDiff;
+use wiki_stats::stats::queries::RankingFilter;
+use wiki_stats::stats::registry::{RankingOptions, StatSelection};
+use wiki_stats::stats::store::StatsStore;
+use wiki_stats::stats::time_series::TimeSeries;
+use wiki_stats::stats::{load_stats, save_stats};
 
 use crate::{
-    args::{SampleOptions, StatsArgs, StatsCommands, WikiSizesArgs},
+    args::{PageRankingArg, SampleOptions, StatsArgs, StatsCommands, WikiSizesArgs},
     print_error_and_exit,
     validation::{validate_sqlite_files, validate_wiki_names},
 };
 
-async fn handle_add_sample_stats(args: StatsArgs, sample_args: SampleOptions) {
-    let StatsArgs {
-        output_path,
-        db_path,
-        wikis,
-        all_wikis,
-        skip_validation,
-    } = args;
-
+/// All wikis in db_path if all_wikis, else the given ones. Exits if they fail validation
+async fn resolve_wikis(
+    db_path: &Path,
+    wikis: Vec<String>,
+    all_wikis: bool,
+    skip_validation: bool,
+) -> Vec<String> {
     let wikis = if all_wikis {
-        &get_all_database_files(&db_path).unwrap_or_else(|e| {
+        get_all_database_files(db_path).unwrap_or_else(|e| {
             print_error_and_exit!("Failed fetching all wikis from db path: {e}")
         })
     } else {
-        &wikis
+        wikis
     };
 
     println!("Wikis: {wikis:?}");
 
-    validate_wiki_names(wikis)
+    validate_wiki_names(&wikis)
         .await
         .unwrap_or_else(|e| print_error_and_exit!("Failed validating wiki names: {e}"));
-    validate_sqlite_files(&db_path, wikis, !skip_validation)
+    validate_sqlite_files(db_path, &wikis, !skip_validation)
         .await
         .unwrap_or_else(|e| print_error_and_exit!("Failed validating wiki sqlite files: {e}"));
 
+    wikis
+}
+
+async fn handle_add_distance_matrix(
+    args: StatsArgs,
+    num_pages: usize,
+    ranking: PageRankingArg,
+    threads: usize,
+    overwrite: bool,
+) {
+    let StatsArgs {
+        output_path,
+        db_path,
+        wikis,
+        all_wikis,
+        skip_validation,
+    } = args;
+
+    let wikis = resolve_wikis(&db_path, wikis, all_wikis, skip_validation).await;
+
+    println!("> Creating distance matrix of the top {num_pages} pages..");
+    wiki_stats::stats::add_distance_matrix_stats(
+        &output_path,
+        db_path,
+        wikis,
+        num_pages,
+        ranking.into(),
+        threads,
+        overwrite,
+    )
+    .await;
+}
+
+async fn handle_add_link_churn(args: StatsArgs, old_db_path: PathBuf, overwrite: bool) {
+    let StatsArgs {
+        output_path,
+        db_path,
+        wikis,
+        all_wikis,
+        skip_validation,
+    } = args;
+
+    let old_dump_date = old_db_path
+        .parent()
+        .and_then(|p| p.file_name())
+        .and_then(|s| s.to_str())
+        .unwrap_or_else(|| print_error_and_exit!("Failed extracting dumpdate from old db path"))
+        .to_string();
+    println!("Assuming old dump_date (from path): {old_dump_date}");
+
+    let wikis = resolve_wikis(&db_path, wikis, all_wikis, skip_validation).await;
+
+    println!("> Comparing to the dbs at {old_db_path:?}..");
+    wiki_stats::stats::add_link_churn_stats(
+        &output_path,
+        db_path,
+        old_db_path,
+        old_dump_date,
+        wikis,
+        overwrite,
+    )
+    .await;
+}
+
+async fn handle_add_sample_stats(args: StatsArgs, sample_args: SampleOptions) {
+    let StatsArgs {
+        output_path,
+        db_path,
+        wikis,
+        all_wikis,
+        skip_validation,
+    } = args;
+
+    let wikis = &resolve_wikis(&db_path, wikis, all_wikis, skip_validation).await;
+
     let SampleOptions {
         sample_size,
         threads,
         cache_size,
         overwrite,
+        exclude_disambiguation,
     } = sample_args;
     println!("> Creating sample bfs stats..");
 
@@ -47,6 +130,7 @@ async fn handle_add_sample_stats(args: StatsArgs, sample_args: SampleOptions) {
         sample_size,
         threads,
         cache_size,
+        exclude_disambiguation,
         overwrite,
     )
     .await;
@@ -57,6 +141,8 @@ async fn handle_generate_stats(
     add_sample: bool,
     add_web_wiki_sizes: bool,
     sample_args: SampleOptions,
+    selection: StatSelection,
+    store_path: Option<PathBuf>,
 ) {
     let StatsArgs {
         output_path,
@@ -66,21 +152,10 @@ async fn handle_generate_stats(
         skip_validation,
     } = args;
 
-    let wikis = if all_wikis {
-        &get_all_database_files(&db_path).unwrap_or_else(|e| {
-            print_error_and_exit!("Failed fetching all wikis from db path: {e}");
-        })
-    } else {
-        &wikis
-    };
-    println!("Wikis: {wikis:?}");
-
-    validate_wiki_names(wikis)
-        .await
-        .unwrap_or_else(|e| print_error_and_exit!("Failed validating wiki names: {e}"));
-    validate_sqlite_files(&db_path, wikis, !skip_validation)
-        .await
-        .unwrap_or_else(|e| print_error_and_exit!("Failed validating wiki sqlite files: {e}"));
+    let selected_stats = selection
+        .stats()
+        .unwrap_or_else(|e| print_error_and_exit!("{e}"));
+    let wikis = &resolve_wikis(&db_path, wikis, all_wikis, skip_validation).await;
 
     let base_path = db_path
         .clone()
@@ -104,7 +179,15 @@ async fn handle_generate_stats(
         "Creating stats at {:?} using db files from: {:?}",
         &output_path, &db_path
     );
-    wiki_stats::stats::create_stats(&output_path, wikis.clone(), &db_path, dump_date).await;
+    wiki_stats::stats::create_stats(
+        &output_path,
+        wikis.clone(),
+        &db_path,
+        dump_date,
+        selected_stats,
+        store_path.as_deref(),
+    )
+    .await;
 
     if add_sample {
         let SampleOptions {
@@ -112,6 +195,7 @@ async fn handle_generate_stats(
             threads,
             cache_size,
             overwrite,
+            exclude_disambiguation,
         } = sample_args;
         println!("Creating sample bfs stats..");
         wiki_stats::stats::add_sample_bfs_stats(
@@ -121,6 +205,7 @@ async fn handle_generate_stats(
             sample_size,
             threads,
             cache_size,
+            exclude_disambiguation,
             overwrite,
         )
         .await;
@@ -135,6 +220,131 @@ async fn handle_generate_stats(
     }
 }
 
+fn format_percent(relative: Option<f64>) -> String {
+    relative.map_or("-".to_string(), |r| format!("{:+.2}%", r * 100.0))
+}
+
+fn handle_stats_diff(old_path: &Path, new_path: &Path, output_path: Option<PathBuf>) {
+    let diff = StatsDiff::new(&load_stats(old_path), &load_stats(new_path));
+    println!(
+        "Changes from {} to {}",
+        diff.old_dump_date.bold(),
+        diff.new_dump_date.bold()
+    );
+
+    println!(
+        "\n{:<28} {:<14} {:>14} {:>14} {:>14} {:>10}",
+        "metric", "wiki", "old", "new", "delta", "change"
+    );
+    for (metric, wikis) in &diff.numeric {
+        for (wiki_name, delta) in wikis {
+            println!(
+                "{metric:<28} {wiki_name:<14} {:>14} {:>14} {:>+14} {:>10}",
+                delta.old,
+                delta.new,
+                delta.delta,
+                format_percent(delta.relative)
+            );
+        }
+    }
+
+    for (metric, wikis) in &diff.rankings {
+        for (wiki_name, changes) in wikis {
+            println!("\n{} {wiki_name}", metric.bold());
+            for change in changes {
+                let rank = |rank: Option<usize>| rank.map_or("-".to_string(), |r| format!("#{r}"));
+                let count = |count: Option<u64>| count.map_or("-".to_string(), |c| c.to_string());
+                let line = format!(
+                    "{:>4} (was {:>4}) {} {} -> {}",
+                    rank(change.new_rank),
+                    rank(change.old_rank),
+                    change.page_title,
+                    count(change.old_count),
+                    count(change.new_count)
+                );
+                match (change.old_rank, change.new_rank) {
+                    (None, _) => println!("{}", line.green()),
+                    (_, None) => println!("{}", line.red()),
+                    _ => println!("{line}"),
+                }
+            }
+        }
+    }
+
+    for (histogram, wikis) in &diff.histograms {
+        println!("\n{}", histogram.bold());
+        for (wiki_name, shift) in wikis {
+            println!(
+                "{wiki_name:<14} mean {:.3} -> {:.3} ({:+.3})",
+                shift.old_mean, shift.new_mean, shift.mean_shift
+            );
+        }
+    }
+
+    if let Some(output_path) = output_path {
+        let json = serde_json::to_string_pretty(&diff).unwrap();
+        std::fs::write(&output_path, json)
+            .unwrap_or_else(|e| print_error_and_exit!("Failed writing {output_path:?}: {e}"));
+        println!("{}", format!("Saved diff to {output_path:?}").green());
+    }
+}
+
+fn handle_time_series(stats_path: &Path, output_path: &Path) {
+    let series = TimeSeries::from_dir(stats_path);
+    println!(
+        "Time series of {} metrics over the dump dates {:?}",
+        series.metrics.len(),
+        series.dump_dates
+    );
+
+    let json = serde_json::to_string_pretty(&series).unwrap();
+    std::fs::write(output_path, json)
+        .unwrap_or_else(|e| print_error_and_exit!("Failed writing {output_path:?}: {e}"));
+    println!(
+        "{}",
+        format!("Saved time series to {output_path:?}").green()
+    );
+}
+
+fn handle_store_import(stats_paths: &[PathBuf], store_path: &Path) {
+    let store = StatsStore::open(store_path);
+    for stats_path in stats_paths {
+        let stats = load_stats(stats_path);
+        store.import_stats(&stats);
+        println!(
+            "Imported {stats_path:?} ({}, {} wikis)",
+            stats.dump_date,
+            stats.wikis.len()
+        );
+    }
+    println!(
+        "{}",
+        format!(
+            "Stats store {store_path:?} has the dump dates {:?}",
+            store.dump_dates()
+        )
+        .green()
+    );
+}
+
+fn handle_store_export(store_path: &Path, dump_date: &str, output_path: &Path) {
+    if !store_path.exists() {
+        print_error_and_exit!("No stats store at {store_path:?}");
+    }
+    let store = StatsStore::open(store_path);
+    let stats = store.load_stats(dump_date).unwrap_or_else(|| {
+        print_error_and_exit!(
+            "No stats of {dump_date} in {store_path:?}. Stored dump dates: {:?}",
+            store.dump_dates()
+        )
+    });
+    save_stats(&stats, output_path);
+    println!(
+        "{}",
+        format!("Exported the stats of {dump_date} to {output_path:?}").green()
+    );
+}
+
 pub async fn handle_stats(subcommands: StatsCommands) {
     match subcommands {
         StatsCommands::AddSampleStats { args, sample_args } => {
@@ -152,13 +362,92 @@ pub async fn handle_stats(subcommands: StatsCommands) {
             wiki_stats::stats::add_web_wiki_sizes(&output_path, dump_date).await;
         }
 
+        StatsCommands::AddDistanceMatrix {
+            args,
+            num_pages,
+            ranking,
+            threads,
+            overwrite,
+        } => {
+            handle_add_distance_matrix(args, num_pages, ranking, threads, overwrite).await;
+        }
+
+        StatsCommands::AddLinkChurn {
+            args,
+            old_db_path,
+            overwrite,
+        } => {
+            handle_add_link_churn(args, old_db_path, overwrite).await;
+        }
+
         StatsCommands::Generate {
             args,
             add_sample,
             sample_args,
+            ranking_args,
             add_web_wiki_sizes,
+            only,
+            skip,
+            store_path,
+        } => {
+            let title_patterns: Vec<&String> = ranking_args
+                .exclude_titles
+                .iter()
+                .filter(|pattern| !pattern.is_empty())
+                .collect();
+            let filter = RankingFilter::new(
+                &title_patterns,
+                !ranking_args.include_redirects,
+                !ranking_args.include_disambiguation,
+            )
+            .unwrap_or_else(|e| print_error_and_exit!("Invalid title pattern: {e}"));
+            let selection = StatSelection {
+                only,
+                skip,
+                ranking: RankingOptions {
+                    top: ranking_args.top,
+                    filter,
+                },
+            };
+            handle_generate_stats(
+                args,
+                add_sample,
+                add_web_wiki_sizes,
+                sample_args,
+                selection,
+                store_path,
+            )
+            .await;
+        }
+
+        StatsCommands::Diff {
+            old,
+            new,
+            output_path,
+        } => {
+            handle_stats_diff(&old, &new, output_path);
+        }
+
+        StatsCommands::TimeSeries {
+            stats_path,
+            output_path,
+        } => {
+            handle_time_series(&stats_path, &output_path);
+        }
+
+        StatsCommands::Import {
+            stats_paths,
+            store_path,
+        } => {
+            handle_store_import(&stats_paths, &store_path);
+        }
+
+        StatsCommands::Export {
+            store_path,
+            dump_date,
+            output_path,
         } => {
-            handle_generate_stats(args, add_sample, add_web_wiki_sizes, sample_args).await;
+            handle_store_export(&store_path, &dump_date, &output_path);
         }
     }
 }
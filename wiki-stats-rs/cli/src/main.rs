@@ -20,6 +20,10 @@ async fn main() {
 
 #[cfg(test)]
 mod cli_test {
+    use clap::Parser;
+    use wiki_stats::stats::queries::{DEFAULT_EXCLUDED_TITLES, RankingFilter};
+
+    use crate::args::{Commands, StatsCommands};
     use crate::{Cli, validation::validate_wiki_names};
 
     #[test]
@@ -28,6 +32,46 @@ mod cli_test {
         Cli::command().debug_assert();
     }
 
+    #[test]
+    fn test_exclude_titles() {
+        let exclude_titles = |extra_args: &[&str]| {
+            let args = [
+                "wiki-stats",
+                "stats",
+                "generate",
+                "--output-path",
+                "stats.json",
+                "--db-path",
+                "sqlite",
+                "--wikis",
+                "dewiki",
+            ];
+            let cli = Cli::try_parse_from(args.iter().chain(extra_args)).unwrap();
+            let Commands::Stats {
+                subcommands: StatsCommands::Generate { ranking_args, .. },
+            } = cli.command
+            else {
+                panic!("Parsed the wrong command");
+            };
+            ranking_args.exclude_titles
+        };
+
+        // the default regexes contain commas
+        let defaults = exclude_titles(&[]);
+        assert_eq!(defaults, DEFAULT_EXCLUDED_TITLES);
+        assert!(RankingFilter::new(&defaults, true, true).is_ok());
+
+        assert_eq!(
+            exclude_titles(&[
+                "--exclude-titles",
+                r"^\d{1,4}$",
+                "--exclude-titles",
+                "^List_"
+            ]),
+            vec![r"^\d{1,4}$", "^List_"]
+        );
+    }
+
     #[tokio::test]
     async fn test_validate_wikis() {
         assert!(validate_wiki_names(&["enwiki"]).await.is_ok());
@@ -15,9 +15,9 @@ use crate::calc::bfs::{bfs, build_path};
 use crate::sqlite;
 use crate::sqlite::load::load_sql_part_map;
 use crate::sqlite::page_links::{get_links_of_id, load_link_to_map_db_limit};
-use crate::sqlite::paths::build_sp;
+use crate::sqlite::paths::{SpDirection, build_sp};
 use crate::sqlite::{db_sp_wiki_path, db_wiki_path};
-use crate::stats::queries::select_link_count_groupby;
+use crate::stats::queries::{RankingFilter, select_link_count_groupby};
 use crate::utils::default_bar;
 
 // slow
@@ -46,20 +46,28 @@ pub fn test_limit() {
     let path = db_wiki_path(wiki_name);
 
     let t1 = Instant::now();
-    let cached_entries: Vec<PageId> =
-        select_link_count_groupby(1000, &wiki_name, "WikiLink.page_id")
-            .into_iter()
-            .map(|(pid, _)| PageId(pid as u32))
-            .collect();
+    let cached_entries: Vec<PageId> = select_link_count_groupby(
+        1000,
+        wiki_name,
+        "WikiLink.page_id",
+        &RankingFilter::default(),
+    )
+    .into_iter()
+    .map(|(pid, _)| PageId(pid as u32))
+    .collect();
     let cache = load_link_to_map_db_limit(&path, cached_entries, false);
     dbg!(&t1.elapsed());
 
     let t1 = Instant::now();
-    let cached_entries: Vec<PageId> =
-        select_link_count_groupby(100_000, &wiki_name, "WikiLink.page_id")
-            .into_iter()
-            .map(|(pid, _)| PageId(pid as u32))
-            .collect();
+    let cached_entries: Vec<PageId> = select_link_count_groupby(
+        100_000,
+        wiki_name,
+        "WikiLink.page_id",
+        &RankingFilter::default(),
+    )
+    .into_iter()
+    .map(|(pid, _)| PageId(pid as u32))
+    .collect();
     let cache = load_link_to_map_db_limit(&path, cached_entries, false);
 
     dbg!(&t1.elapsed());
@@ -83,16 +91,15 @@ pub fn test_save_sp() {
     let end_link_id = sqlite::title_id_conv::page_title_to_id(&end_link, &conn).unwrap();
     dbg!(&end_link_id);
 
-    let cache = load_link_to_map_db_limit(&path, vec![], false);
-    crate::calc::precalc_interlinks_most_popular_threaded(&path_sp, &path, &cache, wiki_name);
+    crate::calc::precalc_shortest_path_trees(&path, &path_sp, 5, 8);
 
-    // let (depth_histogram, prev_map, num_visited, deepest_id) =
-    //     bfs(&start_link_id, None, None, &cache, &path);
-    //
-    // let mut links_map: FxHashMap<(PageId, PageId, PageId), usize> = FxHashMap::default();
-    // save_shortest_paths_json(&path, &prev_map, &mut links_map, &start_link_id);
-
-    build_sp(&start_link_id, &end_link_id);
+    let conn_sp = Connection::open(&path_sp).unwrap();
+    build_sp(
+        &conn_sp,
+        start_link_id,
+        SpDirection::FromSource,
+        end_link_id,
+    );
 }
 
 pub fn test_bfs() {
@@ -102,11 +109,15 @@ pub fn test_bfs() {
     let conn = Connection::open(&path).unwrap();
 
     let t1 = Instant::now();
-    let cached_entries: Vec<PageId> =
-        select_link_count_groupby(1000, &wiki_name, "WikiLink.page_id")
-            .into_iter()
-            .map(|(pid, _)| PageId(pid as u32))
-            .collect();
+    let cached_entries: Vec<PageId> = select_link_count_groupby(
+        1000,
+        wiki_name,
+        "WikiLink.page_id",
+        &RankingFilter::default(),
+    )
+    .into_iter()
+    .map(|(pid, _)| PageId(pid as u32))
+    .collect();
 
     let cache = load_link_to_map_db_limit(&path, cached_entries, false);
     // let cache = FxHashMap::default();
@@ -2,13 +2,12 @@ use std::{
     collections::{HashMap, VecDeque},
     hash::BuildHasher,
     path::Path,
-    sync::{Arc, Mutex, atomic::Ordering},
+    sync::atomic::{AtomicU64, Ordering},
     thread,
     time::Instant,
 };
 
 use async_stream::stream;
-use crossbeam::queue::SegQueue;
 use futures::Stream;
 use fxhash::{FxHashMap, FxHashSet};
 use log::{debug, info, trace};
@@ -19,108 +18,260 @@ use serde::{Deserialize, Serialize};
 use crate::{
     DBCache, DepthHistogram, DistanceMap, PrevMap,
     calc::{HITS, MISSES, get_incoming_links, get_links},
-    sqlite::{self, page_links::get_links_of_ids},
+    sqlite::{
+        self,
+        page_links::{GraphSize, LinkCaches, get_links_of_ids_cached},
+    },
     utils::default_bar_unknown,
 };
 
-fn bfs_worker(
-    id_queue: Arc<SegQueue<(PageId, u32)>>,
-    cache: Arc<DBCache>,
-    max_depth_opt: Option<u32>,
-    end_link_id_opt: Option<PageId>,
-    visited: Arc<Mutex<FxHashSet<PageId>>>,
-) -> thread::JoinHandle<()> {
-    thread::spawn(move || {
-        let conn =
-            Connection::open("/home/gareth/dev/Rust/WikiGame/pagelinks_full_ids.db").unwrap();
-
-        while !id_queue.is_empty() {
-            let (current_id, depth) = id_queue.pop().unwrap();
-            // dbg!(&current_id);
-            // if visited.contains(&current_id) {
-            //     continue;
-            // }
+/// Fixed size bitset over page ids, which can be set concurrently from multiple threads
+struct AtomicBitmap {
+    words: Vec<AtomicU64>,
+}
 
-            // visited_counter += 1;
+impl AtomicBitmap {
+    fn new(max_id: u32) -> Self {
+        let num_words = max_id as usize / 64 + 1;
+        Self {
+            words: (0..num_words).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
 
-            // if visited_counter % 100_000 == 0 {
-            //     dbg!(&visited_counter);
-            //     dbg!(&visited.lock().unwrap().len());
-            // }
+    fn contains(&self, id: PageId) -> bool {
+        let (word, bit) = (id.0 as usize / 64, id.0 % 64);
+        self.words[word].load(Ordering::Relaxed) & (1 << bit) != 0
+    }
 
-            if let Some(end_link_id) = end_link_id_opt {
-                if current_id == end_link_id {
-                    println!("Found endlink");
-                    break;
-                }
-            }
-            //
-            // skip adding additional links if they are out of "reach"
-            if let Some(max_depth) = max_depth_opt {
-                if depth + 1 > max_depth {
-                    continue;
-                }
-            }
+    /// Sets the bit of id. Returns true if it was not set before,
+    /// i.e. exactly one thread "wins" each id.
+    fn insert(&self, id: PageId) -> bool {
+        let (word, bit) = (id.0 as usize / 64, id.0 % 64);
+        self.words[word].fetch_or(1 << bit, Ordering::Relaxed) & (1 << bit) == 0
+    }
 
-            for link in get_links(&conn, &current_id, &cache) {
-                // && !to_visit.contains(&link)
-                // dbg!(&link);
+    fn clear(&self) {
+        self.words
+            .iter()
+            .for_each(|word| word.store(0, Ordering::Relaxed));
+    }
+}
 
-                if !visited.lock().unwrap().contains(&link) {
-                    // dbg!(&link);
-                    visited.lock().unwrap().insert(link);
-                    id_queue.push((link, depth + 1));
-                    // prev.insert(link, current_id);
-                    // println!("{:?} -> {:?}", current_id, link);
-                }
-            }
+/// Switch to bottom-up, if the edges to check from the frontier exceed the unexplored edges / ALPHA
+const BFS_ALPHA: u64 = 14;
+/// Switch back to top-down, if the frontier shrinks below num_vertices / BFS_BETA
+/// (num_vertices is approximated by the largest page id)
+const BFS_BETA: u64 = 24;
+/// Frontiers smaller than this are expanded on the calling thread
+const MIN_PARALLEL_FRONTIER: usize = 1024;
+
+/// Splits items in num_threads chunks and runs f on each chunk in its own thread.
+/// Returns the concatenated (next frontier, (page, parent)) pairs.
+fn par_chunks<T: Sync>(
+    items: &[T],
+    num_threads: usize,
+    f: impl Fn(&[T]) -> (Vec<PageId>, Vec<(PageId, PageId)>) + Sync,
+) -> (Vec<PageId>, Vec<(PageId, PageId)>) {
+    if num_threads <= 1 || items.len() < MIN_PARALLEL_FRONTIER {
+        return f(items);
+    }
+
+    let chunk_size = items.len().div_ceil(num_threads);
+    thread::scope(|s| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| s.spawn(|| f(chunk)))
+            .collect();
+
+        let mut next = vec![];
+        let mut parents = vec![];
+        for handle in handles {
+            let (chunk_next, chunk_parents) = handle.join().expect("bfs thread panicked");
+            next.extend(chunk_next);
+            parents.extend(chunk_parents);
         }
+        (next, parents)
     })
 }
 
-fn bfs_parallel(
+/// Level synchronous parallel bfs over in-memory caches, returning the same result as [`bfs`].
+///
+/// Unlike [`bfs`], there is no db fallback: `cache` has to contain the outgoing links of every
+/// page (e.g. [`crate::sqlite::page_links::load_link_to_map_db`]), `size` is its [`GraphSize`].
+/// If `incoming_cache` is given, levels with a large frontier are expanded bottom-up
+/// (every unvisited page checks whether one of its incoming links is in the frontier),
+/// which needs far less edge checks in the middle levels of a small world graph like wikipedia.
+///
+/// When searching for end_link_id_opt, the search stops once the level containing it was
+/// discovered, so num_visited can differ from [`bfs`]. Paths in prev_map are shortest paths,
+/// but can differ from [`bfs`] if there are multiple.
+pub fn bfs_parallel(
     start_link_id: &PageId,
     end_link_id_opt: Option<&PageId>,
     max_depth_opt: Option<u32>,
     cache: &DBCache,
-) -> (DistanceMap, PrevMap, u32) {
-    let mut dist: DistanceMap = FxHashMap::default();
+    incoming_cache: Option<&DBCache>,
+    size: GraphSize,
+    num_threads: usize,
+) -> BfsResult {
     let mut prev: PrevMap = FxHashMap::default();
+    let mut histogram: DepthHistogram = FxHashMap::default();
+    let mut visited: FxHashSet<PageId> = FxHashSet::default();
+    visited.insert(*start_link_id);
+    let mut deepest_id = *start_link_id;
+
+    let ctx = BfsLevelContext {
+        cache,
+        incoming_cache,
+        size,
+        num_threads,
+    };
+    bfs_parallel_levels(
+        &ctx,
+        start_link_id,
+        end_link_id_opt,
+        max_depth_opt,
+        |depth, level, parents| {
+            histogram.insert(depth, level.len() as u64);
+            deepest_id = level[0];
+            visited.extend(level.iter().copied());
+            prev.extend(parents.iter().copied());
+        },
+    );
 
-    // let mut to_visit: VecDeque<(PageId, u32)> = VecDeque::from([(*start_link_id, 0)]);
-    // let conn = Connection::open("/home/gareth/dev/Rust/WikiGame/pagelinks_full_ids.db").unwrap();
+    let len_deepest_sp = histogram.keys().max().copied().unwrap_or(0);
 
-    let mut visited: Arc<Mutex<FxHashSet<PageId>>> = Arc::new(Mutex::new(FxHashSet::default()));
-    visited.lock().unwrap().insert(*start_link_id);
+    BfsResult {
+        num_visited: (visited.len() - 1) as u32,
+        visited,
+        depth_histogram: histogram,
+        prev_map: prev,
+        deepest_id,
+        len_deepest_sp,
+    }
+}
 
-    dbg!(&visited.lock().unwrap().len());
-    let mut visited_counter = 0;
+/// The graph and threads of a [`bfs_parallel_levels`] traversal, see [`bfs_parallel`]
+#[derive(Clone, Copy)]
+pub(crate) struct BfsLevelContext<'a> {
+    pub cache: &'a DBCache,
+    pub incoming_cache: Option<&'a DBCache>,
+    pub size: GraphSize,
+    pub num_threads: usize,
+}
 
-    let id_queue: Arc<SegQueue<(PageId, u32)>> = Arc::new(SegQueue::new());
-    id_queue.push((*start_link_id, 0));
+/// The traversal of [`bfs_parallel`]. Calls on_level(depth, pages, (page, parent) pairs)
+/// for every non-empty level after the start page.
+pub(crate) fn bfs_parallel_levels(
+    ctx: &BfsLevelContext,
+    start_link_id: &PageId,
+    end_link_id_opt: Option<&PageId>,
+    max_depth_opt: Option<u32>,
+    mut on_level: impl FnMut(u32, &[PageId], &[(PageId, PageId)]),
+) {
+    let BfsLevelContext {
+        cache,
+        incoming_cache,
+        size,
+        num_threads,
+    } = *ctx;
+    // the start page can be missing from the graph, if it has no links
+    let max_id = size.max_id.max(start_link_id.0);
+    let num_edges = size.num_edges;
+    // pages with incoming links are the only ones bottom-up can discover
+    let bottom_up_candidates: Vec<PageId> = incoming_cache
+        .map(|c| c.keys().copied().collect())
+        .unwrap_or_default();
+
+    let visited_bitmap = AtomicBitmap::new(max_id);
+    let frontier_bitmap = AtomicBitmap::new(max_id);
+    visited_bitmap.insert(*start_link_id);
+
+    let mut frontier: Vec<PageId> = vec![*start_link_id];
+    let mut depth = 0;
+    let mut explored_edges: u64 = 0;
+    let mut bottom_up = false;
+
+    while !frontier.is_empty() {
+        if end_link_id_opt.is_some_and(|end_link_id| visited_bitmap.contains(*end_link_id)) {
+            trace!("Found endlink");
+            break;
+        }
 
-    let mut thread_handles_aq: Vec<thread::JoinHandle<()>> = Vec::new();
+        // skip adding additional links if they are out of "reach"
+        if max_depth_opt.is_some_and(|max_depth| depth + 1 > max_depth) {
+            break;
+        }
 
-    let arc_cache = Arc::new(cache.clone());
+        let frontier_edges: u64 = frontier
+            .iter()
+            .map(|id| cache.get(id).map_or(0, |links| links.len() as u64))
+            .sum();
+
+        if incoming_cache.is_some() {
+            let unexplored_edges = num_edges.saturating_sub(explored_edges);
+            if !bottom_up && frontier_edges > unexplored_edges / BFS_ALPHA {
+                bottom_up = true;
+            } else if bottom_up && (frontier.len() as u64) < max_id as u64 / BFS_BETA {
+                bottom_up = false;
+            }
+        }
+        explored_edges += frontier_edges;
+
+        let (next, parents) = if bottom_up {
+            debug!("[depth {depth}] bottom-up, frontier: {}", frontier.len());
+            let incoming_cache = incoming_cache.unwrap();
+
+            frontier_bitmap.clear();
+            frontier.iter().for_each(|id| {
+                frontier_bitmap.insert(*id);
+            });
+
+            par_chunks(&bottom_up_candidates, num_threads, |chunk| {
+                let mut next = vec![];
+                let mut parents = vec![];
+                for page_id in chunk {
+                    if visited_bitmap.contains(*page_id) {
+                        continue;
+                    }
+                    let Some(parent) = incoming_cache[page_id]
+                        .iter()
+                        .find(|source| frontier_bitmap.contains(**source))
+                    else {
+                        continue;
+                    };
+
+                    if visited_bitmap.insert(*page_id) {
+                        next.push(*page_id);
+                        parents.push((*page_id, *parent));
+                    }
+                }
+                (next, parents)
+            })
+        } else {
+            debug!("[depth {depth}] top-down, frontier: {}", frontier.len());
+
+            par_chunks(&frontier, num_threads, |chunk| {
+                let mut next = vec![];
+                let mut parents = vec![];
+                for page_id in chunk {
+                    for link in cache.get(page_id).into_iter().flatten() {
+                        if !visited_bitmap.contains(*link) && visited_bitmap.insert(*link) {
+                            next.push(*link);
+                            parents.push((*link, *page_id));
+                        }
+                    }
+                }
+                (next, parents)
+            })
+        };
 
-    for i in 0..6 {
-        println!("started thread {i}");
-        thread_handles_aq.push(bfs_worker(
-            id_queue.clone(),
-            arc_cache.clone(),
-            max_depth_opt,
-            end_link_id_opt.copied(),
-            visited.clone(),
-        ));
+        depth += 1;
+        if !next.is_empty() {
+            on_level(depth, &next, &parents);
+        }
+        frontier = next;
     }
-
-    thread_handles_aq
-        .into_iter()
-        .for_each(|th| th.join().expect("can't join thread"));
-
-    dbg!(&visited.lock().unwrap().len());
-    dbg!(&prev.len());
-    return (dist, prev, visited_counter);
 }
 
 pub struct BfsResult {
@@ -159,12 +310,41 @@ pub struct SpBiStream {
     pub visited: u64,
     pub elapsed_ms: u128,
     pub paths: Option<FxHashSet<Vec<String>>>,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// cache_hits / (cache_hits + cache_misses), 0 if nothing was looked up yet
+    pub cache_hit_ratio: f64,
+    #[serde(default)]
+    pub source: PathSource,
+}
+
+/// Where the paths of a [`SpBiStream`] come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathSource {
+    /// All shortest paths, found by the bidirectional bfs
+    #[default]
+    Bfs,
+    /// A single shortest path, read from a precomputed shortest path tree.
+    /// The tree only stores one predecessor per page, so other shortest paths are missing
+    StoredTree,
+}
+
+fn cache_hit_ratio(hits: u64, misses: u64) -> f64 {
+    if hits + misses == 0 {
+        return 0.0;
+    }
+    hits as f64 / (hits + misses) as f64
 }
 
+/// Bidirectional bfs from start to end, expanding the smaller frontier each step.
+/// Outgoing links are served from `caches.outgoing` and incoming links from `caches.incoming`,
+/// only pages missing from the cache are queried from the db.
 pub async fn bfs_bidirectional(
     start_link_id: PageId,
     end_link_id_opt: PageId,
     db_path: impl AsRef<Path> + 'static,
+    caches: LinkCaches,
 ) -> impl Stream<Item = SpBiStream> + 'static {
     stream! {
         let conn = Connection::open(db_path).unwrap();
@@ -185,19 +365,25 @@ pub async fn bfs_bidirectional(
 
         let start_time = Instant::now();
         let mut total_visited = 0;
+        let mut cache_hits = 0;
+        let mut cache_misses = 0;
 
         while paths.is_empty() && (!unvisited_forward.is_empty() && !unvisited_backward.is_empty()) {
            // tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
             // this won't really yield?? or send a http request without delay IDK????
            tokio::time::sleep(tokio::time::Duration::from_millis(0)).await;
 
-            let forward_links = get_links_of_ids(
+            let (forward_links, hits, misses) = get_links_of_ids_cached(
                 &conn,
-                unvisited_forward.keys().cloned().collect::<Vec<PageId>>(), false);
+                unvisited_forward.keys().cloned().collect::<Vec<PageId>>(), &caches.outgoing, false);
+            cache_hits += hits;
+            cache_misses += misses;
 
-            let backward_links = get_links_of_ids(
+            let (backward_links, hits, misses) = get_links_of_ids_cached(
                 &conn,
-                unvisited_backward.keys().cloned().collect::<Vec<PageId>>(), true);
+                unvisited_backward.keys().cloned().collect::<Vec<PageId>>(), &caches.incoming, true);
+            cache_hits += hits;
+            cache_misses += misses;
 
             if forward_links.len() < backward_links.len() {
                 forward_depth += 1;
@@ -210,7 +396,11 @@ pub async fn bfs_bidirectional(
                 yield SpBiStream {
                     visited: total_visited as u64,
                     elapsed_ms: start_time.elapsed().as_millis(),
-                    paths: None
+                    paths: None,
+                    cache_hits,
+                    cache_misses,
+                    cache_hit_ratio: cache_hit_ratio(cache_hits, cache_misses),
+                    source: PathSource::Bfs,
                 };
 
                 // dbg!(&total_visited);
@@ -235,7 +425,11 @@ pub async fn bfs_bidirectional(
                 yield SpBiStream {
                     visited: total_visited as u64,
                     elapsed_ms: start_time.elapsed().as_millis(),
-                    paths: None
+                    paths: None,
+                    cache_hits,
+                    cache_misses,
+                    cache_hit_ratio: cache_hit_ratio(cache_hits, cache_misses),
+                    source: PathSource::Bfs,
                 };
                 // dbg!(&total_visited);
 
@@ -275,7 +469,11 @@ pub async fn bfs_bidirectional(
             visited: total_visited as u64,
             elapsed_ms: start_time.elapsed().as_millis(),
             paths: Some(paths.iter().map(|v|
-                v.iter().map(|pid| sqlite::title_id_conv::page_id_to_title(pid, &conn).unwrap().0).collect::<Vec<String>>()).collect())
+                v.iter().map(|pid| sqlite::title_id_conv::page_id_to_title(pid, &conn).unwrap().0).collect::<Vec<String>>()).collect()),
+            cache_hits,
+            cache_misses,
+            cache_hit_ratio: cache_hit_ratio(cache_hits, cache_misses),
+            source: PathSource::Bfs,
         };
         // dbg!(&total_visited);
 
@@ -481,3 +679,59 @@ pub fn build_path<S: BuildHasher>(
     }
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::calc::bfs::{bfs, bfs_parallel, build_path};
+    use crate::sqlite::page_links::GraphSize;
+    use crate::test_utils::random_graph;
+
+    #[test]
+    fn test_bfs_parallel_matches_bfs() {
+        let (outgoing, incoming) = random_graph(20_000, 100_000, 42);
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test_database.sqlite");
+
+        let start = PageId(1);
+        let expected = bfs(&start, None, None, &outgoing, &db_path);
+
+        let size = GraphSize::of(&outgoing);
+        for incoming_cache in [None, Some(&incoming)] {
+            let result = bfs_parallel(&start, None, None, &outgoing, incoming_cache, size, 4);
+
+            assert_eq!(result.num_visited, expected.num_visited);
+            assert_eq!(result.visited, expected.visited);
+            assert_eq!(result.depth_histogram, expected.depth_histogram);
+            assert_eq!(result.len_deepest_sp, expected.len_deepest_sp);
+
+            // every path has to be a shortest path made of existing links
+            for page_id in &result.visited {
+                let path = build_path(page_id, &result.prev_map);
+                assert_eq!(path.front(), Some(&start));
+                assert_eq!(path.len(), build_path(page_id, &expected.prev_map).len());
+                for (from, to) in path.iter().zip(path.iter().skip(1)) {
+                    assert!(outgoing[from].contains(to));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bfs_parallel_max_depth() {
+        let (outgoing, incoming) = random_graph(2_000, 6_000, 42);
+        let result = bfs_parallel(
+            &PageId(1),
+            None,
+            Some(2),
+            &outgoing,
+            Some(&incoming),
+            GraphSize::of(&outgoing),
+            4,
+        );
+
+        assert!(result.len_deepest_sp <= 2);
+        assert!(result.depth_histogram.keys().all(|depth| *depth <= 2));
+    }
+}
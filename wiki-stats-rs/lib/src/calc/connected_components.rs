@@ -3,11 +3,15 @@ use std::fs;
 use fxhash::FxHashSet;
 use log::info;
 use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
 
 use crate::{
     WikiIdent,
     calc::bfs::bfs_undirected,
-    sqlite::{page_links::get_cache, title_id_conv::load_rows_from_page},
+    sqlite::{
+        page_links::get_cache, page_props::load_disambiguation_pages,
+        title_id_conv::load_rows_from_page,
+    },
     stats::queries::query_page,
     utils::ProgressBarBuilder,
 };
@@ -27,6 +31,7 @@ pub fn find_wcc(wiki_ident: WikiIdent) {
     .into_iter()
     .map(|p| p.page_id as u32)
     .collect::<FxHashSet<_>>();
+    let disambiguation_pages = load_disambiguation_pages(&Connection::open(&db_path).unwrap());
 
     let mut components: Vec<FxHashSet<u32>> = Vec::new();
     let mut visited: FxHashSet<u32> = FxHashSet::default();
@@ -56,15 +61,14 @@ pub fn find_wcc(wiki_ident: WikiIdent) {
     }
     info!("Num components: {}", components.len());
 
-    // remove all redirects
+    // remove all redirects and disambiguation pages
     components.retain_mut(|c| {
-        c.retain(|p| !redirects.contains(p));
+        c.retain(|p| !redirects.contains(p) && !disambiguation_pages.contains(&PageId(*p)));
         c.len() > 1
     });
 
     components.sort_by(|a, b| a.len().cmp(&b.len())); // ascending
     components.pop(); // remove last, so largest component
-    // todo: also remove Begriffsklärungsseiten
 
     info!("Num components after pruning: {}", components.len());
 
@@ -0,0 +1,166 @@
+use fxhash::{FxHashMap, FxHashSet};
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+
+use crate::sqlite::page_links::{LinkCaches, get_links_of_ids_cached};
+
+/// Pages per database query when expanding a bfs level
+const LEVEL_BATCH_SIZE: usize = 10_000;
+
+/// Shortest cycle through page_id of at most max_length links, as pages starting and ending with
+/// page_id ([page_id, page_id] for a self link). Bfs from page_id over the outgoing links, until
+/// a page linking back to page_id is found
+pub fn shortest_cycle(
+    page_id: PageId,
+    conn: &Connection,
+    caches: &LinkCaches,
+    max_length: u32,
+) -> Option<Vec<PageId>> {
+    let (backlinks, _, _) = get_links_of_ids_cached(conn, vec![page_id], &caches.incoming, true);
+    let linking_back: FxHashSet<PageId> = backlinks.into_iter().map(|(source, _)| source).collect();
+
+    let build_cycle = |prev: &FxHashMap<PageId, PageId>, last: PageId| {
+        let mut cycle = vec![page_id, last];
+        let mut current = last;
+        while current != page_id {
+            current = prev[&current];
+            cycle.push(current);
+        }
+        cycle.reverse();
+        cycle
+    };
+
+    let mut prev: FxHashMap<PageId, PageId> = FxHashMap::default();
+    if linking_back.contains(&page_id) {
+        return Some(build_cycle(&prev, page_id));
+    }
+
+    let mut visited: FxHashSet<PageId> = FxHashSet::from_iter([page_id]);
+    let mut level = vec![page_id];
+    // the n-th level is n links away from page_id, closing the cycle takes one more
+    for _ in 0..max_length.saturating_sub(1) {
+        let mut next = vec![];
+        for chunk in level.chunks(LEVEL_BATCH_SIZE) {
+            let (links, _, _) =
+                get_links_of_ids_cached(conn, chunk.to_vec(), &caches.outgoing, false);
+            for (source, link) in links {
+                if visited.insert(link) {
+                    prev.insert(link, source);
+                    next.push(link);
+                }
+            }
+        }
+
+        if let Some(last) = next.iter().find(|pid| linking_back.contains(pid)) {
+            return Some(build_cycle(&prev, *last));
+        }
+        if next.is_empty() {
+            break;
+        }
+        level = next;
+    }
+
+    None
+}
+
+/// All pairs (a, b) with a < b where a links to b and b links to a
+pub fn mutual_links(conn: &Connection) -> Vec<(PageId, PageId)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT a.page_id, a.page_link FROM WikiLink a
+            JOIN WikiLink b ON b.page_id = a.page_link AND b.page_link = a.page_id
+            WHERE a.page_id < a.page_link
+            ORDER BY a.page_id, a.page_link",
+        )
+        .unwrap();
+
+    stmt.query_map([], |row| Ok((PageId(row.get(0)?), PageId(row.get(1)?))))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect()
+}
+
+/// Number of reciprocated link pairs, every pair counted once
+pub fn num_mutual_links(conn: &Connection) -> u64 {
+    conn.query_row(
+        "SELECT COUNT(*) FROM WikiLink a
+        JOIN WikiLink b ON b.page_id = a.page_link AND b.page_link = a.page_id
+        WHERE a.page_id < a.page_link",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+/// The top pages with the most mutual links, as (page_id, number of mutual links)
+pub fn most_mutual_links(conn: &Connection, top: usize) -> Vec<(PageId, u64)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT a.page_id, COUNT(*) FROM WikiLink a
+            JOIN WikiLink b ON b.page_id = a.page_link AND b.page_link = a.page_id
+            WHERE a.page_id != a.page_link
+            GROUP BY a.page_id ORDER BY COUNT(*) DESC, a.page_id LIMIT ?1",
+        )
+        .unwrap();
+
+    stmt.query_map([top], |row| Ok((PageId(row.get(0)?), row.get(1)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::calc::cycles::{most_mutual_links, mutual_links, num_mutual_links, shortest_cycle};
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::test_utils::graph_db;
+
+    #[test]
+    fn test_cycles_and_mutual_links() {
+        // 1 -> 2 -> 3 -> 4 -> 1, 1 -> 5 -> 6 -> 1, 2 <-> 7, 2 <-> 8, 9 -> 9, 10 -> 1
+        let conn = graph_db(&[
+            (1, 2),
+            (2, 3),
+            (3, 4),
+            (4, 1),
+            (1, 5),
+            (5, 6),
+            (6, 1),
+            (2, 7),
+            (7, 2),
+            (2, 8),
+            (8, 2),
+            (9, 9),
+            (10, 1),
+        ]);
+        let caches = LinkCaches::default();
+        let ids = |ids: &[u32]| ids.iter().map(|id| PageId(*id)).collect::<Vec<_>>();
+
+        assert_eq!(
+            shortest_cycle(PageId(1), &conn, &caches, 10),
+            Some(ids(&[1, 5, 6, 1]))
+        );
+        assert_eq!(shortest_cycle(PageId(1), &conn, &caches, 2), None);
+        assert_eq!(
+            shortest_cycle(PageId(7), &conn, &caches, 10),
+            Some(ids(&[7, 2, 7]))
+        );
+        assert_eq!(
+            shortest_cycle(PageId(9), &conn, &caches, 10),
+            Some(ids(&[9, 9]))
+        );
+        assert_eq!(shortest_cycle(PageId(10), &conn, &caches, 10), None);
+
+        assert_eq!(
+            mutual_links(&conn),
+            vec![(PageId(2), PageId(7)), (PageId(2), PageId(8))]
+        );
+        assert_eq!(num_mutual_links(&conn), 2);
+        assert_eq!(
+            most_mutual_links(&conn, 2),
+            vec![(PageId(2), 2), (PageId(7), 1)]
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use fxhash::FxHashMap;
+use log::debug;
+use parse_mediawiki_sql::field_types::PageId;
+use rand::Rng;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::sqlite::page_links::{LinkCaches, get_links_of_ids_cached};
+use crate::sqlite::title_id_conv::get_random_page;
+
+/// Weights of the single features in [`Difficulty::score`], summing up to 1
+const DISTANCE_WEIGHT: f64 = 0.4;
+const PATHS_WEIGHT: f64 = 0.2;
+const IN_DEGREE_WEIGHT: f64 = 0.2;
+const WALK_WEIGHT: f64 = 0.2;
+
+/// Distance at which the distance feature is maxed out
+const HARDEST_DISTANCE: u32 = 6;
+/// Number of shortest paths (and in-degree) from which on a pair counts as trivial in that feature
+const EASIEST_NUM_PATHS: f64 = 1000.0;
+const EASIEST_IN_DEGREE: f64 = 10_000.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DifficultyOptions {
+    /// Pairs further apart count as unreachable
+    pub max_distance: u32,
+    /// Random walks used to estimate the hitting probability
+    pub num_walks: u32,
+    /// Steps per random walk
+    pub walk_length: u32,
+    /// Leaves disambiguation pages out of the generated pairs
+    pub exclude_disambiguation: bool,
+}
+
+impl Default for DifficultyOptions {
+    fn default() -> Self {
+        Self {
+            max_distance: 10,
+            num_walks: 1000,
+            walk_length: 20,
+            exclude_disambiguation: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Difficulty {
+    /// Length of the shortest path
+    pub distance: u32,
+    /// Number of different shortest paths (saturating)
+    pub num_shortest_paths: u64,
+    /// Number of pages linking to the target
+    pub target_in_degree: u32,
+    /// Share of random walks from the start reaching the target
+    pub hitting_probability: f64,
+    /// Weighted combination of the features above between 0 (trivial) and 1 (hardest)
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WikiGamePair {
+    pub start: PageId,
+    pub target: PageId,
+    pub difficulty: Difficulty,
+}
+
+fn log_share(value: f64, max: f64) -> f64 {
+    ((1.0 + value).ln() / (1.0 + max).ln()).min(1.0)
+}
+
+/// Combines the features to a score between 0 and 1. Every feature is mapped to [0, 1] first:
+/// longer distances, fewer shortest paths, fewer links to the target and lower hitting
+/// probabilities (in orders of magnitude, down to 1 / num_walks) are harder
+fn difficulty_score(
+    distance: u32,
+    num_shortest_paths: u64,
+    target_in_degree: u32,
+    hitting_probability: f64,
+    num_walks: u32,
+) -> f64 {
+    let distance_score =
+        (distance.saturating_sub(1) as f64 / (HARDEST_DISTANCE - 1) as f64).min(1.0);
+    // a reachable target has at least one shortest path
+    let paths_score = 1.0
+        - log_share(
+            num_shortest_paths.saturating_sub(1) as f64,
+            EASIEST_NUM_PATHS,
+        );
+    let in_degree_score = 1.0 - log_share(target_in_degree as f64, EASIEST_IN_DEGREE);
+
+    let min_probability = 1.0 / num_walks.max(2) as f64;
+    let walk_score = hitting_probability.max(min_probability).log10() / min_probability.log10();
+
+    DISTANCE_WEIGHT * distance_score
+        + PATHS_WEIGHT * paths_score
+        + IN_DEGREE_WEIGHT * in_degree_score
+        + WALK_WEIGHT * walk_score
+}
+
+/// Length and number of shortest paths start -> target, None if target is further than
+/// max_distance away. Bidirectional bfs expanding the smaller frontier, counting the shortest
+/// paths to every visited page on both sides: the paths through the meeting pages are the
+/// products of both counts
+pub fn count_shortest_paths(
+    start: PageId,
+    target: PageId,
+    conn: &Connection,
+    caches: &LinkCaches,
+    max_distance: u32,
+) -> Option<(u32, u64)> {
+    if start == target {
+        return Some((0, 1));
+    }
+
+    // page -> (depth, number of shortest paths from (or to) the root)
+    let mut visited_fwd: FxHashMap<PageId, (u32, u64)> = FxHashMap::from_iter([(start, (0, 1))]);
+    let mut visited_bwd: FxHashMap<PageId, (u32, u64)> = FxHashMap::from_iter([(target, (0, 1))]);
+    let mut frontier_fwd = vec![start];
+    let mut frontier_bwd = vec![target];
+    let mut depth_fwd = 0;
+    let mut depth_bwd = 0;
+
+    while !frontier_fwd.is_empty()
+        && !frontier_bwd.is_empty()
+        && depth_fwd + depth_bwd < max_distance
+    {
+        let incoming = frontier_bwd.len() < frontier_fwd.len();
+        let (frontier, visited, other, depth) = if incoming {
+            (
+                &mut frontier_bwd,
+                &mut visited_bwd,
+                &visited_fwd,
+                &mut depth_bwd,
+            )
+        } else {
+            (
+                &mut frontier_fwd,
+                &mut visited_fwd,
+                &visited_bwd,
+                &mut depth_fwd,
+            )
+        };
+        *depth += 1;
+
+        let (links, _, _) =
+            get_links_of_ids_cached(conn, frontier.clone(), caches.get(incoming), incoming);
+        let mut next = vec![];
+        for (page_id, page_link) in links {
+            let (from, to) = if incoming {
+                (page_link, page_id)
+            } else {
+                (page_id, page_link)
+            };
+            let from_paths = visited[&from].1;
+            match visited.get_mut(&to) {
+                None => {
+                    visited.insert(to, (*depth, from_paths));
+                    next.push(to);
+                }
+                Some((to_depth, to_paths)) if *to_depth == *depth => {
+                    *to_paths = to_paths.saturating_add(from_paths);
+                }
+                _ => {}
+            }
+        }
+
+        // every shortest path passes exactly one page of the new level
+        let mut best: Option<(u32, u64)> = None;
+        for page_id in &next {
+            if let Some((other_depth, other_paths)) = other.get(page_id) {
+                let distance = *depth + other_depth;
+                let paths = visited[page_id].1.saturating_mul(*other_paths);
+                best = match best {
+                    Some((d, p)) if d == distance => Some((d, p.saturating_add(paths))),
+                    Some((d, _)) if d < distance => best,
+                    _ => Some((distance, paths)),
+                };
+            }
+        }
+        if best.is_some() {
+            return best;
+        }
+        *frontier = next;
+    }
+
+    None
+}
+
+/// Share of num_walks random walks of walk_length steps from start that reach target.
+/// Every step follows a uniformly chosen outgoing link, walks ending in a page without links fail
+pub fn hitting_probability(
+    start: PageId,
+    target: PageId,
+    conn: &Connection,
+    caches: &LinkCaches,
+    num_walks: u32,
+    walk_length: u32,
+    rng: &mut impl Rng,
+) -> f64 {
+    // walks pass the same (well linked) pages over and over
+    let mut links: FxHashMap<PageId, Vec<PageId>> = FxHashMap::default();
+    let mut hits = 0;
+
+    for _ in 0..num_walks {
+        let mut current = start;
+        for _ in 0..walk_length {
+            let page_links = links.entry(current).or_insert_with(|| {
+                let (page_links, _, _) =
+                    get_links_of_ids_cached(conn, vec![current], &caches.outgoing, false);
+                page_links.into_iter().map(|(_, link)| link).collect()
+            });
+            if page_links.is_empty() {
+                break;
+            }
+            current = page_links[rng.gen_range(0..page_links.len())];
+            if current == target {
+                hits += 1;
+                break;
+            }
+        }
+    }
+
+    hits as f64 / num_walks.max(1) as f64
+}
+
+/// Difficulty of the wiki game start -> target. None if target is not reachable
+/// within options.max_distance
+pub fn game_difficulty(
+    start: PageId,
+    target: PageId,
+    conn: &Connection,
+    caches: &LinkCaches,
+    options: DifficultyOptions,
+    rng: &mut impl Rng,
+) -> Option<Difficulty> {
+    let (distance, num_shortest_paths) =
+        count_shortest_paths(start, target, conn, caches, options.max_distance)?;
+
+    let (backlinks, _, _) = get_links_of_ids_cached(conn, vec![target], &caches.incoming, true);
+    let target_in_degree = backlinks.len() as u32;
+
+    let hitting_probability = hitting_probability(
+        start,
+        target,
+        conn,
+        caches,
+        options.num_walks,
+        options.walk_length,
+        rng,
+    );
+
+    Some(Difficulty {
+        distance,
+        num_shortest_paths,
+        target_in_degree,
+        hitting_probability,
+        score: difficulty_score(
+            distance,
+            num_shortest_paths,
+            target_in_degree,
+            hitting_probability,
+            options.num_walks,
+        ),
+    })
+}
+
+/// Samples random (non redirect) page pairs until num_pairs of them have a difficulty score in
+/// score_range or max_attempts pairs were tried
+pub fn generate_game_pairs(
+    db_path: &Path,
+    caches: &LinkCaches,
+    score_range: RangeInclusive<f64>,
+    num_pairs: usize,
+    max_attempts: usize,
+    options: DifficultyOptions,
+    rng: &mut impl Rng,
+) -> Vec<WikiGamePair> {
+    let conn = Connection::open(db_path).unwrap();
+    let mut pairs = vec![];
+    let mut attempts = 0;
+
+    while pairs.len() < num_pairs && attempts < max_attempts {
+        let batch_size = (2 * (max_attempts - attempts)).min(1000) as u32;
+        let pages: Vec<PageId> =
+            get_random_page(db_path, batch_size, options.exclude_disambiguation)
+                .into_iter()
+                .filter(|page| !page.is_redirect)
+                .map(|page| PageId(page.id))
+                .collect();
+        if pages.len() < 2 {
+            break;
+        }
+
+        for pair in pages.chunks_exact(2) {
+            if pairs.len() >= num_pairs || attempts >= max_attempts {
+                break;
+            }
+            attempts += 1;
+
+            let (start, target) = (pair[0], pair[1]);
+            let Some(difficulty) = game_difficulty(start, target, &conn, caches, options, rng)
+            else {
+                continue;
+            };
+            debug!("{start:?} -> {target:?}: {difficulty:?}");
+            if score_range.contains(&difficulty.score) {
+                pairs.push(WikiGamePair {
+                    start,
+                    target,
+                    difficulty,
+                });
+            }
+        }
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use crate::calc::difficulty::{
+        DifficultyOptions, count_shortest_paths, difficulty_score, game_difficulty,
+    };
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::test_utils::graph_db;
+
+    #[test]
+    fn test_count_shortest_paths() {
+        // 1 -> {2, 3, 4} -> 5 -> {6, 7} -> 8, 4 -> 6, 1 -> 9 -> 9 (dead end)
+        let conn = graph_db(&[
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 5),
+            (3, 5),
+            (4, 5),
+            (5, 6),
+            (5, 7),
+            (6, 8),
+            (7, 8),
+            (4, 6),
+            (1, 9),
+        ]);
+        let caches = LinkCaches::default();
+
+        // 1 -> 4 -> 6 -> 8 is the only path of length 3
+        assert_eq!(
+            count_shortest_paths(PageId(1), PageId(8), &conn, &caches, 10),
+            Some((3, 1))
+        );
+        // 1 -> {2, 3, 4} -> 5 -> 7
+        assert_eq!(
+            count_shortest_paths(PageId(1), PageId(7), &conn, &caches, 10),
+            Some((3, 3))
+        );
+        assert_eq!(
+            count_shortest_paths(PageId(5), PageId(8), &conn, &caches, 10),
+            Some((2, 2))
+        );
+        assert_eq!(
+            count_shortest_paths(PageId(1), PageId(7), &conn, &caches, 2),
+            None
+        );
+        assert_eq!(
+            count_shortest_paths(PageId(9), PageId(1), &conn, &caches, 10),
+            None
+        );
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let difficulty = game_difficulty(
+            PageId(1),
+            PageId(5),
+            &conn,
+            &caches,
+            DifficultyOptions::default(),
+            &mut rng,
+        )
+        .unwrap();
+        assert_eq!(difficulty.distance, 2);
+        assert_eq!(difficulty.num_shortest_paths, 3);
+        assert_eq!(difficulty.target_in_degree, 3);
+        // walks via 9 or 4 -> 6 -> 8 end in a dead end: 1/4 + 1/4 + 1/8
+        assert!((difficulty.hitting_probability - 0.625).abs() < 0.06);
+    }
+
+    #[test]
+    fn test_difficulty_score() {
+        let easy = difficulty_score(1, 1001, 10_000, 1.0, 1000);
+        let hard = difficulty_score(6, 1, 0, 0.0, 1000);
+        assert!(easy.abs() < 1e-9);
+        assert!((hard - 1.0).abs() < 1e-9);
+        assert!(
+            difficulty_score(3, 10, 100, 0.01, 1000) > difficulty_score(2, 10, 100, 0.01, 1000)
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::Instant;
+
+use fxhash::FxHashMap;
+use log::info;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::WikiIdent;
+use crate::calc::bfs::{BfsLevelContext, bfs_parallel_levels};
+use crate::calc::pagerank::{PageRanking, top_pages};
+use crate::sqlite::page_links::LinkCaches;
+use crate::sqlite::title_id_conv::page_id_to_title;
+use crate::stats::stats::Page;
+
+/// Exact shortest path lengths between the highest ranked pages of a wiki
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct DistanceMatrix {
+    pub ranking: PageRanking,
+    /// pages in ranking order
+    pub pages: Vec<Page>,
+    /// distances[i][j]: length of the shortest path pages[i] -> pages[j], None if unreachable
+    pub distances: Vec<Vec<Option<u32>>>,
+}
+
+impl DistanceMatrix {
+    /// CSV with the page titles as header row and first column. Unreachable pairs are left empty
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for page in &self.pages {
+            csv.push(',');
+            csv.push_str(&csv_field(&page.page_title));
+        }
+        csv.push('\n');
+
+        for (page, row) in self.pages.iter().zip(&self.distances) {
+            csv.push_str(&csv_field(&page.page_title));
+            for distance in row {
+                csv.push(',');
+                if let Some(distance) = distance {
+                    csv.push_str(&distance.to_string());
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One full bfs per page, recording the depth at which the other pages are discovered
+pub fn distance_matrix(
+    pages: &[PageId],
+    caches: &LinkCaches,
+    num_threads: usize,
+) -> Vec<Vec<Option<u32>>> {
+    let index: FxHashMap<PageId, usize> =
+        pages.iter().enumerate().map(|(i, pid)| (*pid, i)).collect();
+    let ctx = BfsLevelContext {
+        cache: &caches.outgoing,
+        incoming_cache: Some(&caches.incoming),
+        size: caches.size,
+        num_threads,
+    };
+
+    pages
+        .iter()
+        .enumerate()
+        .map(|(i, page_id)| {
+            let mut row = vec![None; pages.len()];
+            row[i] = Some(0);
+
+            bfs_parallel_levels(&ctx, page_id, None, None, |depth, level, _| {
+                for pid in level {
+                    if let Some(j) = index.get(pid) {
+                        row[*j] = Some(depth);
+                    }
+                }
+            });
+            row
+        })
+        .collect()
+}
+
+pub fn compute_distance_matrix(
+    wiki: WikiIdent,
+    num_pages: usize,
+    ranking: PageRanking,
+    num_threads: usize,
+) -> DistanceMatrix {
+    let t1 = Instant::now();
+    let caches = LinkCaches::load(&wiki.db_path, None);
+
+    let page_ids = top_pages(&caches.outgoing, &caches.incoming, num_pages, ranking);
+    let distances = distance_matrix(&page_ids, &caches, num_threads);
+
+    let conn = Connection::open(&wiki.db_path).unwrap();
+    let pages = page_ids
+        .iter()
+        .map(|pid| Page {
+            page_title: page_id_to_title(pid, &conn).unwrap().0,
+            page_id: pid.0 as u64,
+            wiki_name: wiki.wiki_name.clone(),
+        })
+        .collect();
+
+    info!(
+        "[{}] distance matrix of {} pages took {:?}",
+        wiki.wiki_name,
+        page_ids.len(),
+        t1.elapsed()
+    );
+
+    DistanceMatrix {
+        ranking,
+        pages,
+        distances,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fxhash::FxHashMap;
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::DBCache;
+    use crate::calc::distance_matrix::{DistanceMatrix, distance_matrix};
+    use crate::calc::pagerank::PageRanking;
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::stats::stats::Page;
+
+    #[test]
+    fn test_distance_matrix() {
+        // 1 -> 2 -> 3 -> 1, 3 -> 4
+        let links = [(1, 2), (2, 3), (3, 1), (3, 4)];
+        let mut outgoing: DBCache = FxHashMap::default();
+        let mut incoming: DBCache = FxHashMap::default();
+        for (from, to) in links {
+            outgoing.entry(PageId(from)).or_default().push(PageId(to));
+            incoming.entry(PageId(to)).or_default().push(PageId(from));
+        }
+
+        let pages = [PageId(1), PageId(3), PageId(4)];
+        let caches = LinkCaches::new(outgoing, incoming);
+        let distances = distance_matrix(&pages, &caches, 1);
+        assert_eq!(
+            distances,
+            vec![
+                vec![Some(0), Some(2), Some(3)],
+                vec![Some(1), Some(0), Some(1)],
+                vec![None, None, Some(0)],
+            ]
+        );
+
+        let matrix = DistanceMatrix {
+            ranking: PageRanking::InDegree,
+            pages: ["A", "B, C", "D"]
+                .iter()
+                .zip(pages)
+                .map(|(title, pid)| Page {
+                    page_title: title.to_string(),
+                    page_id: pid.0 as u64,
+                    wiki_name: "test".to_string(),
+                })
+                .collect(),
+            distances,
+        };
+        assert_eq!(
+            matrix.to_csv(),
+            ",A,\"B, C\",D\nA,0,2,3\n\"B, C\",1,0,1\nD,,,0\n"
+        );
+    }
+}
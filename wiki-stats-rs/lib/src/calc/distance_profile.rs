@@ -0,0 +1,123 @@
+use std::time::Instant;
+
+use log::info;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::DepthHistogram;
+use crate::calc::reachability::bfs_levels;
+use crate::sqlite::distance_profile::{load_distance_profile, save_distance_profile};
+use crate::sqlite::page_links::LinkCaches;
+use crate::sqlite::paths::SpDirection;
+
+/// Result of a full bfs from (or to) a single page
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DistanceProfile {
+    pub page_id: PageId,
+    /// [`SpDirection::FromSource`]: pages reachable from page_id,
+    /// [`SpDirection::ToSource`]: pages reaching page_id
+    pub direction: SpDirection,
+    /// Without the page itself
+    pub num_reachable: u32,
+    /// One of the pages furthest away
+    pub deepest_id: PageId,
+    pub max_depth: u32,
+    /// depth -> number of pages at that distance
+    pub depth_histogram: DepthHistogram,
+}
+
+/// Full bfs from page_id over the outgoing (FromSource) or incoming (ToSource) links
+pub fn compute_distance_profile(
+    page_id: PageId,
+    direction: SpDirection,
+    conn: &Connection,
+    caches: &LinkCaches,
+) -> DistanceProfile {
+    let t1 = Instant::now();
+    let levels = bfs_levels(
+        page_id,
+        direction == SpDirection::ToSource,
+        None,
+        conn,
+        caches,
+    );
+
+    let profile = DistanceProfile {
+        page_id,
+        direction,
+        num_reachable: levels.iter().skip(1).map(|level| level.len() as u32).sum(),
+        deepest_id: levels.last().unwrap()[0],
+        max_depth: levels.len() as u32 - 1,
+        depth_histogram: levels
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(depth, level)| (depth as u32, level.len() as u64))
+            .collect(),
+    };
+    info!(
+        "Distance profile of {page_id:?} ({direction:?}) took {:?}",
+        t1.elapsed()
+    );
+    profile
+}
+
+/// The profile stored in the wiki db, computed and stored first if there is none yet
+pub fn distance_profile(
+    page_id: PageId,
+    direction: SpDirection,
+    conn: &Connection,
+    caches: &LinkCaches,
+) -> DistanceProfile {
+    if let Some(profile) = load_distance_profile(conn, page_id, direction) {
+        return profile;
+    }
+
+    let profile = compute_distance_profile(page_id, direction, conn, caches);
+    save_distance_profile(conn, &profile);
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::calc::distance_profile::distance_profile;
+    use crate::sqlite::distance_profile::load_distance_profile;
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::sqlite::paths::SpDirection;
+    use crate::test_utils::graph_db;
+
+    #[test]
+    fn test_distance_profile() {
+        // 1 -> 2 -> 3 -> 4, 1 -> 3, 5 -> 1
+        let conn = graph_db(&[(1, 2), (2, 3), (3, 4), (1, 3), (5, 1)]);
+        let caches = LinkCaches::default();
+
+        assert_eq!(
+            load_distance_profile(&conn, PageId(1), SpDirection::FromSource),
+            None
+        );
+        let outgoing = distance_profile(PageId(1), SpDirection::FromSource, &conn, &caches);
+        assert_eq!(outgoing.num_reachable, 3);
+        assert_eq!(outgoing.max_depth, 2);
+        assert_eq!(outgoing.deepest_id, PageId(4));
+        assert_eq!(outgoing.depth_histogram[&1], 2);
+        assert_eq!(
+            load_distance_profile(&conn, PageId(1), SpDirection::FromSource),
+            Some(outgoing)
+        );
+
+        let incoming = distance_profile(PageId(4), SpDirection::ToSource, &conn, &caches);
+        assert_eq!(incoming.num_reachable, 4);
+        assert_eq!(incoming.max_depth, 3);
+        assert_eq!(incoming.deepest_id, PageId(5));
+
+        // pages without links reach nothing
+        let isolated = distance_profile(PageId(5), SpDirection::ToSource, &conn, &caches);
+        assert_eq!(isolated.num_reachable, 0);
+        assert_eq!(isolated.deepest_id, PageId(5));
+        assert!(isolated.depth_histogram.is_empty());
+    }
+}
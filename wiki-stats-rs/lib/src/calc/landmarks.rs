@@ -0,0 +1,386 @@
+use std::time::Instant;
+
+use fxhash::FxHashMap;
+use log::{debug, info};
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::calc::bfs::{BfsLevelContext, bfs_parallel_levels};
+use crate::sqlite::page_links::{LinkCaches, get_links_of_ids_cached};
+
+/// Stored distance for pages a landmark does not reach (or reaches in more than MAX_DISTANCE steps)
+pub const UNKNOWN_DISTANCE: u8 = u8::MAX;
+pub const MAX_DISTANCE: u32 = UNKNOWN_DISTANCE as u32 - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DistanceBounds {
+    /// The shortest path is at least this long
+    pub lower: u32,
+    /// Length of an existing (not necessarily shortest) path, if any landmark connects both pages
+    pub upper: Option<u32>,
+}
+
+/// BFS distances from and to a fixed set of landmark pages.
+/// Distances are stored per page in flat vectors (num_landmarks entries per page),
+/// so [`LandmarkOracle::distance_bounds`] is just 2 * num_landmarks lookups.
+/// Only pages with links get a row, page ids are sparse (the largest is far above the
+/// number of pages), so indexing by page id would mostly store unknown distances.
+#[derive(Debug, Clone)]
+pub struct LandmarkOracle {
+    pub landmarks: Vec<PageId>,
+    /// Row of a page in dist_from and dist_to
+    rows: FxHashMap<PageId, u32>,
+    /// dist_from[row * num_landmarks + i]: distance landmark i -> page
+    dist_from: Vec<u8>,
+    /// dist_to[row * num_landmarks + i]: distance page -> landmark i
+    dist_to: Vec<u8>,
+}
+
+impl LandmarkOracle {
+    /// Oracle without any pages
+    pub(crate) fn new(landmarks: Vec<PageId>) -> Self {
+        Self {
+            landmarks,
+            rows: FxHashMap::default(),
+            dist_from: vec![],
+            dist_to: vec![],
+        }
+    }
+
+    /// Runs a forward and a backward bfs from every landmark, caches has to contain all links
+    pub fn compute(landmarks: Vec<PageId>, caches: &LinkCaches, num_threads: usize) -> Self {
+        let (outgoing, incoming) = (&caches.outgoing, &caches.incoming);
+
+        let mut oracle = Self::new(landmarks);
+        let num_landmarks = oracle.landmarks.len();
+        // the bfs only reaches pages with links, so every reached page has a row
+        let unknown = vec![UNKNOWN_DISTANCE; num_landmarks];
+        let pages = oracle.landmarks.clone();
+        for page_id in pages.iter().chain(outgoing.keys()).chain(incoming.keys()) {
+            oracle.set_page_distances(*page_id, &unknown, &unknown);
+        }
+
+        let forward = BfsLevelContext {
+            cache: outgoing,
+            incoming_cache: Some(incoming),
+            size: caches.size,
+            num_threads,
+        };
+        // walking the incoming links gives the distance of every page to the landmark
+        let backward = BfsLevelContext {
+            cache: incoming,
+            incoming_cache: Some(outgoing),
+            ..forward
+        };
+
+        for (i, landmark) in oracle.landmarks.clone().iter().enumerate() {
+            let t1 = Instant::now();
+            let rows = &oracle.rows;
+            let landmark_row = rows[landmark] as usize;
+            oracle.dist_from[landmark_row * num_landmarks + i] = 0;
+            oracle.dist_to[landmark_row * num_landmarks + i] = 0;
+
+            let dist_from = &mut oracle.dist_from;
+            bfs_parallel_levels(
+                &forward,
+                landmark,
+                None,
+                Some(MAX_DISTANCE),
+                |depth, level, _| {
+                    for page_id in level {
+                        dist_from[rows[page_id] as usize * num_landmarks + i] = depth as u8;
+                    }
+                },
+            );
+
+            let dist_to = &mut oracle.dist_to;
+            bfs_parallel_levels(
+                &backward,
+                landmark,
+                None,
+                Some(MAX_DISTANCE),
+                |depth, level, _| {
+                    for page_id in level {
+                        dist_to[rows[page_id] as usize * num_landmarks + i] = depth as u8;
+                    }
+                },
+            );
+
+            info!(
+                "Landmark {}/{num_landmarks} ({landmark:?}) took {:?}",
+                i + 1,
+                t1.elapsed()
+            );
+        }
+
+        oracle
+    }
+
+    pub fn num_landmarks(&self) -> usize {
+        self.landmarks.len()
+    }
+
+    /// Pages with (possibly unknown) distances
+    pub fn pages(&self) -> impl Iterator<Item = PageId> + '_ {
+        self.rows.keys().copied()
+    }
+
+    /// (distances from the landmarks, distances to the landmarks) of page_id.
+    /// Empty for pages without a row (see [`LandmarkOracle::pages`])
+    pub fn page_distances(&self, page_id: PageId) -> (&[u8], &[u8]) {
+        let Some(row) = self.rows.get(&page_id) else {
+            return (&[], &[]);
+        };
+        let k = self.num_landmarks();
+        let start = *row as usize * k;
+        (
+            &self.dist_from[start..start + k],
+            &self.dist_to[start..start + k],
+        )
+    }
+
+    /// Adds a row for page_id, or overwrites its distances if it already has one
+    pub(crate) fn set_page_distances(&mut self, page_id: PageId, dist_from: &[u8], dist_to: &[u8]) {
+        let k = self.num_landmarks();
+        let next_row = self.rows.len() as u32;
+        let row = *self.rows.entry(page_id).or_insert(next_row);
+        if row == next_row {
+            self.dist_from.extend_from_slice(dist_from);
+            self.dist_to.extend_from_slice(dist_to);
+        } else {
+            let start = row as usize * k;
+            self.dist_from[start..start + k].copy_from_slice(dist_from);
+            self.dist_to[start..start + k].copy_from_slice(dist_to);
+        }
+    }
+
+    /// Lower and upper bound of the shortest path length a -> b using the triangle inequality:
+    /// - d(a, b) >= d(L, b) - d(L, a)
+    /// - d(a, b) >= d(a, L) - d(b, L)
+    /// - d(a, b) <= d(a, L) + d(L, b)
+    pub fn distance_bounds(&self, a: PageId, b: PageId) -> DistanceBounds {
+        if a == b {
+            return DistanceBounds {
+                lower: 0,
+                upper: Some(0),
+            };
+        }
+
+        let (from_a, to_a) = self.page_distances(a);
+        let (from_b, to_b) = self.page_distances(b);
+        let known = |d: u8| (d != UNKNOWN_DISTANCE).then_some(d as u32);
+
+        let mut lower = 1;
+        let mut upper: Option<u32> = None;
+
+        for i in 0..from_a.len().min(from_b.len()) {
+            if let (Some(l_a), Some(l_b)) = (known(from_a[i]), known(from_b[i])) {
+                lower = lower.max(l_b.saturating_sub(l_a));
+            }
+            if let (Some(a_l), Some(b_l)) = (known(to_a[i]), known(to_b[i])) {
+                lower = lower.max(a_l.saturating_sub(b_l));
+            }
+            if let (Some(a_l), Some(l_b)) = (known(to_a[i]), known(from_b[i])) {
+                upper = Some(upper.map_or(a_l + l_b, |u| u.min(a_l + l_b)));
+            }
+        }
+
+        DistanceBounds { lower, upper }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AltSearchResult {
+    pub path: Option<Vec<PageId>>,
+    pub bounds: DistanceBounds,
+    pub visited: u64,
+    /// Links not followed, because the landmark bounds ruled them out
+    pub pruned_links: u64,
+    pub elapsed_ms: u128,
+}
+
+/// Bidirectional bfs, that skips pages which can't be on a path shorter than the landmark
+/// upper bound: depth(start -> v) + lower(v -> end) > upper (and the same for the backward search).
+/// Links are served from `caches`, missing pages are queried from the db.
+pub fn bfs_bidirectional_alt(
+    start_link_id: PageId,
+    end_link_id: PageId,
+    conn: &Connection,
+    caches: &LinkCaches,
+    oracle: &LandmarkOracle,
+) -> AltSearchResult {
+    let start_time = Instant::now();
+    let bounds = oracle.distance_bounds(start_link_id, end_link_id);
+    let upper = bounds.upper.unwrap_or(u32::MAX);
+
+    // page -> (depth, parent)
+    let mut visited_forward: FxHashMap<PageId, (u32, Option<PageId>)> = FxHashMap::default();
+    let mut visited_backward: FxHashMap<PageId, (u32, Option<PageId>)> = FxHashMap::default();
+    visited_forward.insert(start_link_id, (0, None));
+    visited_backward.insert(end_link_id, (0, None));
+
+    let mut frontier_forward = vec![start_link_id];
+    let mut frontier_backward = vec![end_link_id];
+    let mut depth_forward = 0;
+    let mut depth_backward = 0;
+    let mut pruned_links = 0;
+
+    // (path length, meeting page)
+    let mut best: Option<(u32, PageId)> =
+        (start_link_id == end_link_id).then_some((0, start_link_id));
+
+    while best.is_none() && !frontier_forward.is_empty() && !frontier_backward.is_empty() {
+        let mut next = vec![];
+
+        if frontier_forward.len() <= frontier_backward.len() {
+            depth_forward += 1;
+            let (links, _, _) =
+                get_links_of_ids_cached(conn, frontier_forward, &caches.outgoing, false);
+
+            for (source, target) in links {
+                if visited_forward.contains_key(&target) {
+                    continue;
+                }
+                if depth_forward + oracle.distance_bounds(target, end_link_id).lower > upper {
+                    pruned_links += 1;
+                    continue;
+                }
+                visited_forward.insert(target, (depth_forward, Some(source)));
+                next.push(target);
+
+                if let Some((depth, _)) = visited_backward.get(&target) {
+                    let length = depth_forward + depth;
+                    if best.is_none_or(|(best_length, _)| length < best_length) {
+                        best = Some((length, target));
+                    }
+                }
+            }
+            frontier_forward = next;
+        } else {
+            depth_backward += 1;
+            let (links, _, _) =
+                get_links_of_ids_cached(conn, frontier_backward, &caches.incoming, true);
+
+            for (source, target) in links {
+                if visited_backward.contains_key(&source) {
+                    continue;
+                }
+                if depth_backward + oracle.distance_bounds(start_link_id, source).lower > upper {
+                    pruned_links += 1;
+                    continue;
+                }
+                visited_backward.insert(source, (depth_backward, Some(target)));
+                next.push(source);
+
+                if let Some((depth, _)) = visited_forward.get(&source) {
+                    let length = depth_backward + depth;
+                    if best.is_none_or(|(best_length, _)| length < best_length) {
+                        best = Some((length, source));
+                    }
+                }
+            }
+            frontier_backward = next;
+        }
+    }
+
+    let path = best.map(|(_, meeting_id)| {
+        let mut path = vec![meeting_id];
+        let mut current = meeting_id;
+        while let Some((_, Some(parent))) = visited_forward.get(&current) {
+            path.push(*parent);
+            current = *parent;
+        }
+        path.reverse();
+
+        let mut current = meeting_id;
+        while let Some((_, Some(parent))) = visited_backward.get(&current) {
+            path.push(*parent);
+            current = *parent;
+        }
+        path
+    });
+
+    debug!("alt search pruned {pruned_links} links, bounds: {bounds:?}");
+
+    AltSearchResult {
+        path,
+        bounds,
+        visited: (visited_forward.len() + visited_backward.len()) as u64,
+        pruned_links,
+        elapsed_ms: start_time.elapsed().as_millis(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use rusqlite::Connection;
+
+    use crate::calc::bfs::{bfs_parallel, build_path};
+    use crate::calc::landmarks::{LandmarkOracle, bfs_bidirectional_alt};
+    use crate::calc::pagerank::{PageRanking, top_pages};
+    use crate::sqlite::landmarks::{load_landmarks, save_landmarks};
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::test_utils::random_graph;
+
+    #[test]
+    fn test_landmark_bounds_and_alt_search() {
+        let (outgoing, incoming) = random_graph(2_000, 5_000, 7);
+        let landmarks = top_pages(&outgoing, &incoming, 4, PageRanking::Degree);
+        assert_eq!(landmarks.len(), 4);
+        let caches = LinkCaches::new(outgoing.clone(), incoming.clone());
+        let computed = LandmarkOracle::compute(landmarks, &caches, 2);
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test_database.sqlite");
+        save_landmarks(&db_path, &computed);
+        let oracle = load_landmarks(&db_path).unwrap();
+        assert_eq!(oracle.landmarks, computed.landmarks);
+
+        // pages without incoming links are not cached, so the search looks them up in the db
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute(
+            "CREATE TABLE WikiLink (page_id INTEGER NOT NULL, page_link INTEGER NOT NULL)",
+            (),
+        )
+        .unwrap();
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..20 {
+            let start = PageId(rng.gen_range(1..=2_000));
+            let result = bfs_parallel(
+                &start,
+                None,
+                None,
+                &outgoing,
+                Some(&incoming),
+                caches.size,
+                2,
+            );
+
+            for _ in 0..10 {
+                let end = PageId(rng.gen_range(1..=2_000));
+                let bounds = oracle.distance_bounds(start, end);
+                let alt = bfs_bidirectional_alt(start, end, &conn, &caches, &oracle);
+
+                if start == end || result.visited.contains(&end) {
+                    let distance = build_path(&end, &result.prev_map).len() as u32 - 1;
+                    assert!(bounds.lower <= distance);
+                    assert!(bounds.upper.is_none_or(|upper| distance <= upper));
+
+                    let path = alt.path.expect("alt search found no path");
+                    assert_eq!(path.len() as u32 - 1, distance);
+                    assert_eq!((path[0], path[path.len() - 1]), (start, end));
+                    for (from, to) in path.iter().zip(path.iter().skip(1)) {
+                        assert!(outgoing[from].contains(to));
+                    }
+                } else {
+                    assert!(bounds.upper.is_none());
+                    assert!(alt.path.is_none());
+                }
+            }
+        }
+    }
+}
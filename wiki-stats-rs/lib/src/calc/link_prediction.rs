@@ -0,0 +1,191 @@
+use fxhash::{FxHashMap, FxHashSet};
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::sqlite::page_links::{LinkCaches, get_links_of_ids_cached};
+
+/// Pages per database query when fetching neighbourhoods
+const NEIGHBOURHOOD_BATCH_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkScore {
+    /// Number of shared neighbours
+    CommonNeighbours,
+    /// Shared neighbours weighted by 1 / ln(degree), so shared hubs count less
+    AdamicAdar,
+    /// Shared neighbours divided by the size of the union of both neighbourhoods
+    Jaccard,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkSuggestion {
+    pub page_id: PageId,
+    pub common_neighbours: u32,
+    pub adamic_adar: f64,
+    pub jaccard: f64,
+}
+
+impl LinkSuggestion {
+    fn score(&self, score: LinkScore) -> f64 {
+        match score {
+            LinkScore::CommonNeighbours => self.common_neighbours as f64,
+            LinkScore::AdamicAdar => self.adamic_adar,
+            LinkScore::Jaccard => self.jaccard,
+        }
+    }
+}
+
+/// Undirected neighbourhood (pages linking to or linked by the page, without itself) of every id.
+/// Links are served from caches where possible
+fn neighbourhoods(
+    conn: &Connection,
+    caches: &LinkCaches,
+    ids: &[PageId],
+) -> FxHashMap<PageId, Vec<PageId>> {
+    let mut neighbourhoods: FxHashMap<PageId, Vec<PageId>> =
+        ids.iter().map(|id| (*id, Vec::new())).collect();
+
+    for chunk in ids.chunks(NEIGHBOURHOOD_BATCH_SIZE) {
+        let (outgoing, _, _) =
+            get_links_of_ids_cached(conn, chunk.to_vec(), &caches.outgoing, false);
+        for (page_id, page_link) in outgoing {
+            neighbourhoods.get_mut(&page_id).unwrap().push(page_link);
+        }
+        let (incoming, _, _) =
+            get_links_of_ids_cached(conn, chunk.to_vec(), &caches.incoming, true);
+        for (page_id, page_link) in incoming {
+            neighbourhoods.get_mut(&page_link).unwrap().push(page_id);
+        }
+    }
+
+    for (id, neighbours) in neighbourhoods.iter_mut() {
+        neighbours.sort_unstable_by_key(|pid| pid.0);
+        neighbours.dedup();
+        neighbours.retain(|pid| pid != id);
+    }
+    neighbourhoods
+}
+
+/// Ranks the pages that probably should link to page_id (incoming) or that page_id probably should
+/// link to (not incoming), by the overlap of their neighbourhoods with the one of page_id.
+/// Only pages sharing at least one neighbour are candidates, pages already linked are left out.
+/// Returns the num_suggestions best by score, best first
+pub fn suggest_links(
+    page_id: PageId,
+    conn: &Connection,
+    caches: &LinkCaches,
+    incoming: bool,
+    score: LinkScore,
+    num_suggestions: usize,
+) -> Vec<LinkSuggestion> {
+    let neighbours = neighbourhoods(conn, caches, &[page_id])
+        .remove(&page_id)
+        .unwrap();
+    let neighbours_of_neighbours = neighbourhoods(conn, caches, &neighbours);
+
+    let (linked, _, _) =
+        get_links_of_ids_cached(conn, vec![page_id], caches.get(incoming), incoming);
+    let already_linked: FxHashSet<PageId> = linked
+        .into_iter()
+        .map(|(source, target)| if incoming { source } else { target })
+        .collect();
+
+    let mut candidates: FxHashMap<PageId, LinkSuggestion> = FxHashMap::default();
+    for neighbour_links in neighbours_of_neighbours.values() {
+        // the neighbour links to page_id and the candidate, so the degree is at least 2
+        let weight = 1.0 / (neighbour_links.len() as f64).ln();
+        for candidate in neighbour_links {
+            if *candidate == page_id || already_linked.contains(candidate) {
+                continue;
+            }
+            let suggestion = candidates.entry(*candidate).or_insert(LinkSuggestion {
+                page_id: *candidate,
+                common_neighbours: 0,
+                adamic_adar: 0.0,
+                jaccard: 0.0,
+            });
+            suggestion.common_neighbours += 1;
+            suggestion.adamic_adar += weight;
+        }
+    }
+
+    let mut suggestions: Vec<LinkSuggestion> = candidates.into_values().collect();
+    let by_score = |suggestions: &mut Vec<LinkSuggestion>, score: LinkScore| {
+        suggestions.sort_by(|a, b| {
+            b.score(score)
+                .total_cmp(&a.score(score))
+                .then(a.page_id.0.cmp(&b.page_id.0))
+        })
+    };
+
+    // the jaccard index needs the neighbourhood of every candidate, so unless the ranking
+    // depends on it, it is only calculated for the returned suggestions
+    if score != LinkScore::Jaccard {
+        by_score(&mut suggestions, score);
+        suggestions.truncate(num_suggestions);
+    }
+
+    let candidate_ids: Vec<PageId> = suggestions.iter().map(|s| s.page_id).collect();
+    let candidate_neighbourhoods = neighbourhoods(conn, caches, &candidate_ids);
+    for suggestion in suggestions.iter_mut() {
+        let degree = candidate_neighbourhoods[&suggestion.page_id].len();
+        let union = degree + neighbours.len() - suggestion.common_neighbours as usize;
+        suggestion.jaccard = suggestion.common_neighbours as f64 / union as f64;
+    }
+
+    by_score(&mut suggestions, score);
+    suggestions.truncate(num_suggestions);
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::calc::link_prediction::{LinkScore, suggest_links};
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::test_utils::graph_db;
+
+    #[test]
+    fn test_suggest_links() {
+        // neighbourhoods: 1: {2, 3, 4, 5}, 2: {1, 8}, 3: {1, 4}, 4: {1, 3, 6, 7}, 5: {1, 6}
+        let conn = graph_db(&[
+            (2, 1),
+            (3, 1),
+            (1, 4),
+            (1, 5),
+            (3, 4),
+            (6, 4),
+            (6, 5),
+            (7, 4),
+            (8, 2),
+        ]);
+        let caches = LinkCaches::default();
+        let ids = |incoming, score, num| -> Vec<u32> {
+            suggest_links(PageId(1), &conn, &caches, incoming, score, num)
+                .iter()
+                .map(|s| s.page_id.0)
+                .collect()
+        };
+
+        // 2 and 3 already link to 1. 6 shares 4 and 5, 4 shares 3, 7 shares 4, 8 shares 2
+        assert_eq!(ids(true, LinkScore::CommonNeighbours, 10), vec![6, 4, 7, 8]);
+        // 4 is a hub among the neighbours, so sharing it counts less
+        assert_eq!(ids(true, LinkScore::AdamicAdar, 10), vec![6, 4, 8, 7]);
+        assert_eq!(ids(true, LinkScore::AdamicAdar, 2), vec![6, 4]);
+        // 4 has the largest neighbourhood itself
+        assert_eq!(ids(true, LinkScore::Jaccard, 10), vec![6, 7, 8, 4]);
+        // 1 already links to 4 and 5, but not to 3
+        assert_eq!(
+            ids(false, LinkScore::CommonNeighbours, 10),
+            vec![6, 3, 7, 8]
+        );
+
+        let best = &suggest_links(PageId(1), &conn, &caches, true, LinkScore::AdamicAdar, 1)[0];
+        assert_eq!(best.common_neighbours, 2);
+        assert_eq!(best.jaccard, 0.5);
+        assert!((best.adamic_adar - (1.0 / 4f64.ln() + 1.0 / 2f64.ln())).abs() < 1e-9);
+    }
+}
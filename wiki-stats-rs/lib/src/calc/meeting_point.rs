@@ -0,0 +1,250 @@
+use std::collections::hash_map::Entry;
+
+use fxhash::{FxHashMap, FxHashSet};
+use log::debug;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::calc::traversal::{TraversalMode, expand};
+use crate::sqlite::page_links::LinkCaches;
+
+/// What a meeting point minimises
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MeetingObjective {
+    /// The largest distance from any input page
+    Max,
+    /// The sum of the distances from all input pages
+    Sum,
+}
+
+impl MeetingObjective {
+    fn score(self, distances: &[u32]) -> u32 {
+        match self {
+            MeetingObjective::Max => distances.iter().copied().max().unwrap_or(0),
+            MeetingObjective::Sum => distances.iter().sum(),
+        }
+    }
+
+    /// The other objective, to break ties
+    fn tie_breaker(self) -> Self {
+        match self {
+            MeetingObjective::Max => MeetingObjective::Sum,
+            MeetingObjective::Sum => MeetingObjective::Max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeetingPoint {
+    pub page_id: PageId,
+    /// distances[i]: distance from the i-th input page
+    pub distances: Vec<u32>,
+    /// paths[i]: a shortest path from the i-th input page to the meeting point, as walked in the
+    /// traversal mode (for Reverse, every page of it is linked by the next one)
+    pub paths: Vec<Vec<PageId>>,
+}
+
+/// Bfs state of a single input page
+struct InputBfs {
+    frontier: Vec<PageId>,
+    /// distance and predecessor of every reached page
+    reached: FxHashMap<PageId, (u32, Option<PageId>)>,
+}
+
+impl InputBfs {
+    fn path_to(&self, page_id: PageId) -> Vec<PageId> {
+        let mut path = vec![page_id];
+        let mut current = page_id;
+        while let Some((_, Some(prev))) = self.reached.get(&current) {
+            path.push(*prev);
+            current = *prev;
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// The num pages that are closest to all input pages, by the objective over their distances from
+/// the inputs (ties broken by the other objective, then by page id). Runs a bfs from every input
+/// at the same time, one level each round, up to max_depth levels. The inputs themselves are not
+/// meeting points.
+/// Stops as soon as no other page can reach the score of the found ones anymore: a page not yet
+/// reached by every bfs after d rounds is at least d + 1 links away from the inputs that did not
+/// reach it
+pub fn meeting_points(
+    inputs: &[PageId],
+    mode: TraversalMode,
+    objective: MeetingObjective,
+    max_depth: u32,
+    num: usize,
+    conn: &Connection,
+    caches: &LinkCaches,
+) -> Vec<MeetingPoint> {
+    let input_set: FxHashSet<PageId> = inputs.iter().copied().collect();
+    let mut bfs: Vec<InputBfs> = inputs
+        .iter()
+        .map(|input| InputBfs {
+            frontier: vec![*input],
+            reached: FxHashMap::from_iter([(*input, (0, None))]),
+        })
+        .collect();
+
+    let mut found: Vec<(u32, u32, PageId)> = vec![];
+    for depth in 1..=max_depth {
+        for input_bfs in bfs.iter_mut() {
+            let mut next = vec![];
+            for (from, to, _) in expand(&input_bfs.frontier, mode, conn, caches) {
+                if let Entry::Vacant(entry) = input_bfs.reached.entry(to) {
+                    entry.insert((depth, Some(from)));
+                    next.push(to);
+                }
+            }
+            input_bfs.frontier = next;
+        }
+
+        // objective over the known distances, missing ones counted as depth + 1:
+        // the exact score of pages reached by all, a lower bound for the rest
+        let distances_bound = |page_id: &PageId| -> (Vec<u32>, bool) {
+            let mut complete = true;
+            let distances = bfs
+                .iter()
+                .map(|input_bfs| match input_bfs.reached.get(page_id) {
+                    Some((distance, _)) => *distance,
+                    None => {
+                        complete = false;
+                        depth + 1
+                    }
+                })
+                .collect();
+            (distances, complete)
+        };
+
+        found.clear();
+        let mut best_bound = objective.score(&vec![depth + 1; inputs.len()]);
+        let mut seen: FxHashSet<PageId> = FxHashSet::default();
+        for page_id in bfs.iter().flat_map(|input_bfs| input_bfs.reached.keys()) {
+            if input_set.contains(page_id) || !seen.insert(*page_id) {
+                continue;
+            }
+            let (distances, complete) = distances_bound(page_id);
+            if complete {
+                found.push((
+                    objective.score(&distances),
+                    objective.tie_breaker().score(&distances),
+                    *page_id,
+                ));
+            } else {
+                best_bound = best_bound.min(objective.score(&distances));
+            }
+        }
+        found.sort_unstable_by_key(|(score, tie_breaker, page_id)| {
+            (*score, *tie_breaker, page_id.0)
+        });
+        found.truncate(num);
+
+        debug!(
+            "meeting points depth {depth}: {} found, lower bound of the rest {best_bound}",
+            found.len()
+        );
+        let all_done = bfs.iter().all(|input_bfs| input_bfs.frontier.is_empty());
+        if all_done
+            || (found.len() == num && found.last().is_none_or(|(score, _, _)| *score < best_bound))
+        {
+            break;
+        }
+    }
+
+    found
+        .into_iter()
+        .map(|(_, _, page_id)| MeetingPoint {
+            page_id,
+            distances: bfs
+                .iter()
+                .map(|input_bfs| input_bfs.reached[&page_id].0)
+                .collect(),
+            paths: bfs
+                .iter()
+                .map(|input_bfs| input_bfs.path_to(page_id))
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::calc::meeting_point::{MeetingObjective, meeting_points};
+    use crate::calc::traversal::TraversalMode;
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::test_utils::graph_db;
+
+    #[test]
+    fn test_meeting_points() {
+        // 1 -> 3, 2 -> 3, 1 -> 4 -> 5 -> 6, 2 -> 6, 3 -> 7
+        let conn = graph_db(&[(1, 3), (2, 3), (1, 4), (4, 5), (5, 6), (2, 6), (3, 7)]);
+        let caches = LinkCaches::default();
+        let inputs = [PageId(1), PageId(2)];
+
+        let points = meeting_points(
+            &inputs,
+            TraversalMode::Directed,
+            MeetingObjective::Max,
+            10,
+            3,
+            &conn,
+            &caches,
+        );
+        let ids: Vec<u32> = points.iter().map(|point| point.page_id.0).collect();
+        // 3: (1, 1), 7: (2, 2), 6: (3, 1)
+        assert_eq!(ids, vec![3, 7, 6]);
+        assert_eq!(points[0].distances, vec![1, 1]);
+        assert_eq!(
+            points[1].paths,
+            vec![
+                vec![PageId(1), PageId(3), PageId(7)],
+                vec![PageId(2), PageId(3), PageId(7)]
+            ]
+        );
+
+        let points = meeting_points(
+            &inputs,
+            TraversalMode::Directed,
+            MeetingObjective::Sum,
+            10,
+            2,
+            &conn,
+            &caches,
+        );
+        let ids: Vec<u32> = points.iter().map(|point| point.page_id.0).collect();
+        // 7 and 6 both have the sum 4, 7 wins by its smaller max distance
+        assert_eq!(ids, vec![3, 7]);
+
+        // nothing links to both inputs
+        let points = meeting_points(
+            &inputs,
+            TraversalMode::Reverse,
+            MeetingObjective::Max,
+            10,
+            3,
+            &conn,
+            &caches,
+        );
+        assert!(points.is_empty());
+
+        // 1 and 3 are both at most 2 links away from 4 and 7 (sum 3), 1 wins by its id
+        let points = meeting_points(
+            &[PageId(4), PageId(7)],
+            TraversalMode::Undirected,
+            MeetingObjective::Max,
+            10,
+            1,
+            &conn,
+            &caches,
+        );
+        assert_eq!(points[0].page_id, PageId(1));
+        assert_eq!(points[0].distances, vec![1, 2]);
+    }
+}
@@ -26,19 +26,31 @@ use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::calc::bfs::build_path;
-use crate::sqlite::page_links::{get_links_of_ids, load_link_to_map_db_wiki};
-use crate::sqlite::paths::SPStat;
+use crate::calc::bfs::{bfs_parallel, build_path};
+use crate::sqlite::page_links::{LinkCaches, get_links_of_ids, load_link_to_map_db_wiki};
+use crate::sqlite::paths::SpDirection;
 use crate::sqlite::title_id_conv::{load_id_title_map, load_title_id_map};
-use crate::sqlite::{db_sp_wiki_path, db_wiki_path, paths};
+use crate::sqlite::{db_wiki_path, paths};
 use crate::stats::queries::top_linked_ids;
 use crate::utils::{bar_color, default_bar, default_bar_unknown};
-use crate::web::get_most_popular_pages;
 use crate::{DBCache, DepthHistogram, DistanceMap, PrevMap, PrevMapEntry, sqlite};
 
 pub mod bfs;
 pub mod connected_components;
-mod floyd_warshall;
+pub mod cycles;
+pub mod difficulty;
+pub mod distance_matrix;
+pub mod distance_profile;
+pub mod landmarks;
+pub mod link_prediction;
+pub mod meeting_point;
+pub mod pagerank;
+pub mod reachability;
+pub mod redirects;
+pub mod robustness;
+pub mod similarity;
+pub mod traversal;
+pub mod weighted_paths;
 // TODO: create sqlite3 database containing only pageid and pagetable
 
 // mod utils;
@@ -49,206 +61,67 @@ mod floyd_warshall;
 
 pub const MAX_SIZE: u32 = 210_712_457; // 225_574_049
 
-pub fn precalc_interlinks_most_popular_threaded_quick(wiki_name: impl AsRef<str>) {
-    let wiki_name = wiki_name.as_ref();
-    let path = db_wiki_path(wiki_name);
-    let path_sp = db_sp_wiki_path(wiki_name);
-    let cache = load_link_to_map_db_wiki(&path);
-    precalc_interlinks_most_popular_threaded(path_sp, &path, &cache, wiki_name);
-}
-
-pub(crate) fn precalc_interlinks_most_popular_threaded(
-    db_sp_path: impl AsRef<Path>,
+/// Precomputes the bfs trees from and to the num_sources pages with the most incoming links
+/// and stores them in the sp database (see [`paths::save_shortest_paths`]).
+/// Trees that are already stored for the dump of the wiki db are skipped, so an interrupted run
+/// can be resumed, trees of an older dump are rebuilt.
+pub fn precalc_shortest_path_trees(
     db_path: impl AsRef<Path>,
-    cache: &DBCache,
-    wiki_name: &str,
+    db_sp_path: impl AsRef<Path>,
+    num_sources: usize,
+    num_threads: usize,
 ) {
-    paths::create_db(&db_sp_path);
-    let num_ids = 5;
-    const NUM_THREADS: u32 = 5;
-
-    let pid_queue: Arc<ArrayQueue<PageId>> = Arc::new(ArrayQueue::new(num_ids));
-    // let prev_queue: PrevMapQueue = Arc::new(SegQueue::new());
-
-    let arc_cache = Arc::new(cache.clone());
+    let db_sp_path = db_sp_path.as_ref();
+    paths::create_db(db_sp_path);
 
-    let title_to_id_map = load_title_id_map(db_path);
-    // let top_ids = top_link_ids(num_ids, wiki_name);
-    let top_ids: Vec<&PageId> = get_most_popular_pages(wiki_name)
+    let mut sources: Vec<PageId> = top_linked_ids(num_sources, None, &db_path)
         .into_iter()
-        .filter_map(|(p, c)| title_to_id_map.get(&PageTitle(p)))
-        .take(num_ids)
         .collect();
+    sources.sort_unstable_by_key(|pid| pid.0);
+
+    let caches = LinkCaches::load(&db_path, None);
+    let dump_date = sqlite::wiki::dump_date(&Connection::open(&db_path).unwrap());
+
+    for direction in [SpDirection::FromSource, SpDirection::ToSource] {
+        let precalced_ids = paths::precalced_path_ids(db_sp_path, direction, dump_date.as_deref());
+        let todo: Vec<&PageId> = sources
+            .iter()
+            .filter(|pid| !precalced_ids.contains(pid))
+            .collect();
+
+        println!(
+            "{direction:?}: Already calculated: {} | Now in queue: {}",
+            sources.len() - todo.len(),
+            todo.len()
+        );
 
-    dbg!(&top_ids.len());
-    // dbg!(&top_ids);
-
-    // let precalced_ids = Arc::new(sqlite::paths::precalced_path_ids(&db_sp_path));
-    let precalced_ids = Arc::new(FxHashSet::default());
-
-    for pid in top_ids {
-        if !precalced_ids.contains(pid) {
-            pid_queue.push(*pid).unwrap();
+        let (cache, reverse_cache) = match direction {
+            SpDirection::FromSource => (&caches.outgoing, &caches.incoming),
+            SpDirection::ToSource => (&caches.incoming, &caches.outgoing),
+        };
+
+        let bar = default_bar(todo.len() as u64);
+        for source_id in todo {
+            let result = bfs_parallel(
+                source_id,
+                None,
+                None,
+                cache,
+                Some(reverse_cache),
+                caches.size,
+                num_threads,
+            );
+            paths::save_shortest_paths(
+                db_sp_path,
+                *source_id,
+                direction,
+                dump_date.as_deref(),
+                &result,
+            );
+            bar.inc(1);
         }
+        bar.finish();
     }
-
-    // pid_queue.push(PageId(3454512)).unwrap();
-    //     pid_queue.push(PageId(1)).unwrap();
-
-    println!("Already calculated: {}", precalced_ids.len());
-    println!("Now in queue: {}", pid_queue.len());
-
-    let m = Arc::new(MultiProgress::new());
-
-    let bar = Arc::new(Mutex::new(m.add(default_bar(pid_queue.len() as u64))));
-    let bar2 = Arc::new(Mutex::new(
-        m.add(bar_color("magenta", pid_queue.len() as u64)),
-    ));
-
-    let mut thread_handles_aq: Vec<thread::JoinHandle<()>> = Vec::new();
-
-    type ChannelContent = (DepthHistogram, PrevMapEntry, u32, VecDeque<PageId>);
-
-    fn worker(
-        tid: u32,
-        queue: Arc<ArrayQueue<PageId>>,
-        cache: Arc<DBCache>,
-        precalced_ids: Arc<FxHashSet<PageId>>,
-        bar: Arc<Mutex<ProgressBar>>,
-        mbar: Arc<MultiProgress>,
-        wiki_name: String,
-        prev_queue_sender: Sender<ChannelContent>,
-    ) -> thread::JoinHandle<()> {
-        thread::spawn(move || {
-            while !queue.is_empty() {
-                let start_link_id = queue.pop().unwrap();
-                // println!("[{tid}]: {:?}", start_link_id);
-                let t1 = Instant::now();
-                if precalced_ids.contains(&start_link_id) {
-                    bar.lock().unwrap().inc(1);
-                    continue;
-                }
-
-                let r = bfs::bfs(&start_link_id, None, None, &cache, db_wiki_path(&wiki_name));
-
-                let mut longest_path = build_path(&r.deepest_id, &r.prev_map);
-                longest_path.pop_front(); // skip first as its known
-
-                // prev_queue.push((prev, start_link_id, wiki_name.clone()));
-                prev_queue_sender
-                    .send((
-                        r.depth_histogram,
-                        (r.prev_map, start_link_id, wiki_name.clone()),
-                        r.num_visited,
-                        longest_path,
-                    ))
-                    .unwrap();
-                bar.lock().unwrap().inc(1);
-                // println!("Send {start_link_id:?}");
-
-                // let end_link = PageTitle("Kuba".to_string());
-                // let end_link_id = sqlite::title_id_conv::page_title_to_id(&end_link).unwrap(); // https://de.wikipedia.org/?curid=5885036
-                //
-                // dbg!(build_path(&end_link_id, &prev));
-                // println!("[{tid}]: Done: {:?} {:?}", start_link_id, t1.elapsed());
-            }
-            mbar.println(format!("[{tid}]: Done"));
-            drop(prev_queue_sender);
-        })
-    }
-
-    fn save(
-        wiki_name: String,
-        prev_queue_receiver: Receiver<ChannelContent>,
-        pid_queue: Arc<ArrayQueue<PageId>>,
-        bar2: Arc<Mutex<ProgressBar>>,
-    ) -> thread::JoinHandle<()> {
-        println!("Save thread started");
-        thread::spawn(move || {
-            let id_title_map = load_id_title_map(db_wiki_path(wiki_name));
-            let mut links_map: FxHashMap<(PageId, PageId, PageId), usize> = FxHashMap::default();
-
-            while let Ok(entry) = prev_queue_receiver.recv() {
-                let (
-                    depth_histogram,
-                    (prev_map, start_link_id, wiki_name),
-                    num_visited,
-                    longest_path_ids,
-                ) = entry;
-
-                println!("Received {start_link_id:?}");
-                paths::save_shortest_paths(
-                    &db_sp_wiki_path(&wiki_name),
-                    &prev_map,
-                    &mut links_map,
-                    &start_link_id,
-                );
-
-                // sqlite::paths::save_shortest_paths_json(&db_sp_wiki_path(&wiki_name),
-                //                                         &prev_map,
-                //                                         &mut links_map,
-                //                                         &start_link_id);
-
-                let start_link_title = id_title_map.get(&start_link_id).unwrap();
-                let longest_path = longest_path_ids
-                    .iter()
-                    .map(|pid| id_title_map.get(pid).unwrap().clone().0)
-                    .collect();
-
-                sqlite::paths::save_stats(
-                    wiki_name,
-                    start_link_title.clone().0,
-                    SPStat {
-                        longest_path,
-                        num_visited,
-                        depth_histogram,
-                    },
-                );
-                // println!("Saved {start_link_id:?}");
-
-                bar2.lock().unwrap().inc(1);
-
-                // if pid_queue.is_empty() {
-                //     break;
-                // }
-            }
-            println!("Receiver finished");
-        })
-    }
-
-    let (s, r) = unbounded();
-
-    thread_handles_aq.push(save(
-        wiki_name.to_string(),
-        r,
-        pid_queue.clone(),
-        bar2.clone(),
-    ));
-
-    for tid in 0..NUM_THREADS {
-        println!("started thread {tid}");
-        thread_handles_aq.push(worker(
-            tid,
-            pid_queue.clone(),
-            arc_cache.clone(),
-            precalced_ids.clone(),
-            bar.clone(),
-            m.clone(),
-            wiki_name.to_string(),
-            s.clone(),
-        ));
-    }
-
-    dbg!(&pid_queue.is_empty());
-
-    // let saved_thread = save(r);
-
-    thread_handles_aq
-        .into_iter()
-        .for_each(|th| th.join().expect("can't join thread"));
-
-    println!("Finished");
-    // saved_thread.join();
 }
 
 // fn calc_interlinks<S: BuildHasher>(start: &str, end: &str, max_depth: i32,
@@ -0,0 +1,119 @@
+use fxhash::FxHashMap;
+use log::debug;
+use parse_mediawiki_sql::field_types::PageId;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::DBCache;
+
+pub const DEFAULT_DAMPING: f64 = 0.85;
+
+/// PageRank of every page in the outgoing link cache, computed with `iterations` rounds of
+/// power iteration. The rank of pages without outgoing links is spread evenly over all pages,
+/// so the ranks always sum up to 1.
+pub fn pagerank(cache: &DBCache, damping: f64, iterations: u32) -> FxHashMap<PageId, f64> {
+    let mut ids: Vec<PageId> = cache
+        .iter()
+        .flat_map(|(id, links)| std::iter::once(id).chain(links))
+        .copied()
+        .collect();
+    ids.sort_unstable_by_key(|id| id.0);
+    ids.dedup();
+
+    let num_pages = ids.len();
+    if num_pages == 0 {
+        return FxHashMap::default();
+    }
+
+    let index: FxHashMap<PageId, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let links: Vec<Vec<usize>> = ids
+        .iter()
+        .map(|id| {
+            cache
+                .get(id)
+                .map(|links| links.iter().map(|link| index[link]).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut rank = vec![1.0 / num_pages as f64; num_pages];
+    let mut next_rank = vec![0.0; num_pages];
+
+    for iteration in 0..iterations {
+        let dangling: f64 = links
+            .iter()
+            .zip(&rank)
+            .filter(|(links, _)| links.is_empty())
+            .map(|(_, r)| r)
+            .sum();
+
+        let base = (1.0 - damping) / num_pages as f64 + damping * dangling / num_pages as f64;
+        next_rank.iter_mut().for_each(|r| *r = base);
+
+        for (i, targets) in links.iter().enumerate() {
+            if targets.is_empty() {
+                continue;
+            }
+            let share = damping * rank[i] / targets.len() as f64;
+            for target in targets {
+                next_rank[*target] += share;
+            }
+        }
+
+        let delta: f64 = rank
+            .iter()
+            .zip(&next_rank)
+            .map(|(a, b)| (a - b).abs())
+            .sum();
+        debug!("pagerank iteration {iteration}: delta {delta}");
+        std::mem::swap(&mut rank, &mut next_rank);
+    }
+
+    ids.into_iter().zip(rank).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PageRanking {
+    /// Pages with the most incoming + outgoing links
+    Degree,
+    /// Pages with the most incoming links
+    InDegree,
+    /// Pages with the highest PageRank
+    PageRank,
+}
+
+/// The num_pages highest ranked pages, best first
+pub fn top_pages(
+    outgoing: &DBCache,
+    incoming: &DBCache,
+    num_pages: usize,
+    ranking: PageRanking,
+) -> Vec<PageId> {
+    let mut scores: Vec<(PageId, f64)> = match ranking {
+        PageRanking::Degree => {
+            let mut degrees: FxHashMap<PageId, usize> = FxHashMap::default();
+            for (page_id, links) in outgoing.iter().chain(incoming.iter()) {
+                *degrees.entry(*page_id).or_default() += links.len();
+            }
+            degrees
+                .into_iter()
+                .map(|(page_id, degree)| (page_id, degree as f64))
+                .collect()
+        }
+        PageRanking::InDegree => incoming
+            .iter()
+            .map(|(page_id, links)| (*page_id, links.len() as f64))
+            .collect(),
+        PageRanking::PageRank => pagerank(outgoing, DEFAULT_DAMPING, 20)
+            .into_iter()
+            .collect(),
+    };
+
+    scores.sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then(a_id.0.cmp(&b_id.0)));
+    scores
+        .into_iter()
+        .take(num_pages)
+        .map(|(page_id, _)| page_id)
+        .collect()
+}
@@ -0,0 +1,126 @@
+use std::time::Instant;
+
+use fxhash::FxHashSet;
+use log::info;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::sqlite::page_links::{LinkCaches, get_links_of_ids_cached};
+
+/// Pages per database query when expanding a level
+const LEVEL_BATCH_SIZE: usize = 10_000;
+
+/// The pages that can reach a target page within max_depth links ("in-neighbourhood cone")
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReverseReachability {
+    pub target: PageId,
+    pub max_depth: u32,
+    /// pages_per_depth[d]: pages whose shortest path to the target has length d (0: the target)
+    pub pages_per_depth: Vec<Vec<PageId>>,
+}
+
+impl ReverseReachability {
+    pub fn num_per_depth(&self) -> Vec<usize> {
+        self.pages_per_depth.iter().map(Vec::len).collect()
+    }
+
+    /// Number of pages reaching the target, without the target itself
+    pub fn num_reaching(&self) -> usize {
+        self.pages_per_depth.iter().skip(1).map(Vec::len).sum()
+    }
+
+    /// One `page_id,depth` row per page
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("page_id,depth\n");
+        for (depth, pages) in self.pages_per_depth.iter().enumerate() {
+            for page_id in pages {
+                csv.push_str(&format!("{},{depth}\n", page_id.0));
+            }
+        }
+        csv
+    }
+}
+
+/// Levels of a bfs from start over the incoming (or outgoing) links, up to max_depth levels
+/// (unlimited if None). levels[d] holds the pages at distance d, levels[0] is [start].
+/// Every level is fetched in batches, from the link caches where possible
+pub(crate) fn bfs_levels(
+    start: PageId,
+    incoming: bool,
+    max_depth: Option<u32>,
+    conn: &Connection,
+    caches: &LinkCaches,
+) -> Vec<Vec<PageId>> {
+    let mut visited: FxHashSet<PageId> = FxHashSet::from_iter([start]);
+    let mut levels = vec![vec![start]];
+
+    while max_depth.is_none_or(|max_depth| levels.len() <= max_depth as usize) {
+        let mut next = vec![];
+        for chunk in levels.last().unwrap().chunks(LEVEL_BATCH_SIZE) {
+            let (links, _, _) =
+                get_links_of_ids_cached(conn, chunk.to_vec(), caches.get(incoming), incoming);
+            for (page_id, page_link) in links {
+                let link = if incoming { page_id } else { page_link };
+                if visited.insert(link) {
+                    next.push(link);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        levels.push(next);
+    }
+
+    levels
+}
+
+/// Bfs over the incoming links of target, up to max_depth levels
+pub fn reverse_reachability(
+    target: PageId,
+    max_depth: u32,
+    conn: &Connection,
+    caches: &LinkCaches,
+) -> ReverseReachability {
+    let t1 = Instant::now();
+    let pages_per_depth = bfs_levels(target, true, Some(max_depth), conn, caches);
+
+    let reachability = ReverseReachability {
+        target,
+        max_depth,
+        pages_per_depth,
+    };
+    info!(
+        "{} pages reach {target:?} within {max_depth} links ({:?})",
+        reachability.num_reaching(),
+        t1.elapsed()
+    );
+    reachability
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::calc::reachability::reverse_reachability;
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::test_utils::graph_db;
+
+    #[test]
+    fn test_reverse_reachability() {
+        // 2 -> 1, 3 -> 1, 4 -> 2, 4 -> 3, 5 -> 4, 1 -> 6 (6 does not reach 1), 1 -> 5 (cycle)
+        let conn = graph_db(&[(2, 1), (3, 1), (4, 2), (4, 3), (5, 4), (1, 6), (1, 5)]);
+
+        let reachability = reverse_reachability(PageId(1), 10, &conn, &LinkCaches::default());
+        assert_eq!(reachability.num_per_depth(), vec![1, 2, 1, 1]);
+        assert_eq!(reachability.num_reaching(), 4);
+        assert_eq!(reachability.pages_per_depth[3], vec![PageId(5)]);
+
+        let reachability = reverse_reachability(PageId(1), 1, &conn, &LinkCaches::default());
+        assert_eq!(reachability.num_per_depth(), vec![1, 2]);
+        let csv = reachability.to_csv();
+        assert!(csv.starts_with("page_id,depth\n1,0\n"));
+        assert_eq!(csv.lines().count(), 4);
+    }
+}
@@ -0,0 +1,119 @@
+use fxhash::FxHashMap;
+use parse_mediawiki_sql::field_types::PageId;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RedirectAnalysis {
+    /// Redirects whose target is a redirect, sorted
+    pub double_redirects: Vec<PageId>,
+    /// Every loop of redirects once, starting at its smallest page id, sorted
+    pub loops: Vec<Vec<PageId>>,
+    /// The redirects of the longest chain that ends at a page, followed by that page
+    pub longest_chain: Vec<PageId>,
+}
+
+/// Where a redirect leads when following all redirects
+#[derive(Clone, Copy)]
+enum Chain {
+    /// Ends at a page after this many redirects
+    Ends(u32),
+    /// Runs into a loop
+    Loops,
+}
+
+/// Double redirects, loops and the longest chain of the redirect -> target map
+pub fn analyze_redirects(redirects: &FxHashMap<PageId, PageId>) -> RedirectAnalysis {
+    let mut double_redirects: Vec<PageId> = redirects
+        .iter()
+        .filter(|(_, target)| redirects.contains_key(target))
+        .map(|(page_id, _)| *page_id)
+        .collect();
+    double_redirects.sort_by_key(|page_id| page_id.0);
+
+    let mut chains: FxHashMap<PageId, Chain> = FxHashMap::default();
+    let mut loops = vec![];
+    let mut starts: Vec<PageId> = redirects.keys().copied().collect();
+    starts.sort_by_key(|page_id| page_id.0);
+
+    for start in starts {
+        // follow the redirects until a page, a known redirect or one of this walk
+        let mut walk: Vec<PageId> = vec![];
+        let mut current = start;
+        let end = loop {
+            if let Some(chain) = chains.get(&current) {
+                break *chain;
+            }
+            let Some(target) = redirects.get(&current) else {
+                break Chain::Ends(0);
+            };
+            if let Some(pos) = walk.iter().position(|page_id| *page_id == current) {
+                let mut cycle = walk[pos..].to_vec();
+                let min_pos = (0..cycle.len()).min_by_key(|i| cycle[*i].0).unwrap();
+                cycle.rotate_left(min_pos);
+                loops.push(cycle);
+                break Chain::Loops;
+            }
+            walk.push(current);
+            current = *target;
+        };
+
+        let mut chain = end;
+        for page_id in walk.into_iter().rev() {
+            if let Chain::Ends(length) = chain {
+                chain = Chain::Ends(length + 1);
+            }
+            chains.insert(page_id, chain);
+        }
+    }
+    loops.sort_by_key(|cycle| cycle[0].0);
+
+    let longest_start = chains
+        .iter()
+        .filter_map(|(page_id, chain)| match chain {
+            Chain::Ends(length) => Some((*length, *page_id)),
+            Chain::Loops => None,
+        })
+        .max_by_key(|(length, page_id)| (*length, std::cmp::Reverse(page_id.0)))
+        .map(|(_, page_id)| page_id);
+
+    let mut longest_chain = vec![];
+    if let Some(mut current) = longest_start {
+        longest_chain.push(current);
+        while let Some(target) = redirects.get(&current) {
+            longest_chain.push(*target);
+            current = *target;
+        }
+    }
+
+    RedirectAnalysis {
+        double_redirects,
+        loops,
+        longest_chain,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fxhash::FxHashMap;
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::calc::redirects::analyze_redirects;
+
+    #[test]
+    fn test_analyze_redirects() {
+        // 1 -> 2 -> 3 -> 10, 4 -> 10, 5 -> 6 -> 5, 7 -> 5
+        let redirects: FxHashMap<PageId, PageId> =
+            [(1, 2), (2, 3), (3, 10), (4, 10), (5, 6), (6, 5), (7, 5)]
+                .into_iter()
+                .map(|(from, to)| (PageId(from), PageId(to)))
+                .collect();
+        let ids = |pages: &[PageId]| pages.iter().map(|page_id| page_id.0).collect::<Vec<_>>();
+
+        let analysis = analyze_redirects(&redirects);
+        assert_eq!(ids(&analysis.double_redirects), vec![1, 2, 5, 6, 7]);
+        assert_eq!(analysis.loops.len(), 1);
+        assert_eq!(ids(&analysis.loops[0]), vec![5, 6]);
+        assert_eq!(ids(&analysis.longest_chain), vec![1, 2, 3, 10]);
+
+        assert_eq!(analyze_redirects(&FxHashMap::default()), Default::default());
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::VecDeque;
+
+use fxhash::{FxHashMap, FxHashSet};
+use parse_mediawiki_sql::field_types::PageId;
+use serde::{Deserialize, Serialize};
+
+use crate::DBCache;
+
+/// Pages and links whose removal disconnects the (undirected) link graph
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CutStructure {
+    /// Pages whose removal increases the number of connected components
+    pub articulation_points: Vec<PageId>,
+    /// Links whose removal increases the number of connected components.
+    /// Ordered as (page discovered first, page discovered later) by the dfs
+    pub bridges: Vec<(PageId, PageId)>,
+}
+
+/// Link graph without directions, self links and duplicate links in compressed sparse row format
+struct UndirectedGraph {
+    ids: Vec<PageId>,
+    /// neighbours of node i: neighbours[offsets[i]..offsets[i + 1]]
+    offsets: Vec<usize>,
+    neighbours: Vec<u32>,
+}
+
+impl UndirectedGraph {
+    fn from_cache(outgoing: &DBCache) -> Self {
+        let mut ids: Vec<PageId> = outgoing
+            .iter()
+            .flat_map(|(id, links)| std::iter::once(id).chain(links))
+            .copied()
+            .collect();
+        ids.sort_unstable_by_key(|id| id.0);
+        ids.dedup();
+
+        let index: FxHashMap<PageId, u32> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i as u32))
+            .collect();
+
+        let mut adjacency: Vec<Vec<u32>> = vec![Vec::new(); ids.len()];
+        for (page_id, links) in outgoing {
+            let from = index[page_id];
+            for link in links {
+                let to = index[link];
+                if from != to {
+                    adjacency[from as usize].push(to);
+                    adjacency[to as usize].push(from);
+                }
+            }
+        }
+
+        let mut offsets = Vec::with_capacity(ids.len() + 1);
+        let mut neighbours = Vec::new();
+        offsets.push(0);
+        for mut adjacent in adjacency {
+            adjacent.sort_unstable();
+            adjacent.dedup();
+            neighbours.extend(adjacent);
+            offsets.push(neighbours.len());
+        }
+
+        Self {
+            ids,
+            offsets,
+            neighbours,
+        }
+    }
+}
+
+/// Articulation points and bridges of the link graph, ignoring link directions.
+/// Iterative version of Tarjan's algorithm, so deep link chains don't overflow the stack
+pub fn articulation_points_and_bridges(outgoing: &DBCache) -> CutStructure {
+    let graph = UndirectedGraph::from_cache(outgoing);
+    let num_nodes = graph.ids.len();
+
+    const UNVISITED: u32 = u32::MAX;
+    let mut discovery = vec![UNVISITED; num_nodes];
+    let mut low = vec![0u32; num_nodes];
+    let mut parent = vec![UNVISITED; num_nodes];
+    let mut is_articulation_point = vec![false; num_nodes];
+    let mut bridges = Vec::new();
+    let mut time = 0;
+
+    // (node, position of the next neighbour to visit)
+    let mut stack: Vec<(u32, usize)> = Vec::new();
+
+    for root in 0..num_nodes as u32 {
+        if discovery[root as usize] != UNVISITED {
+            continue;
+        }
+        discovery[root as usize] = time;
+        low[root as usize] = time;
+        time += 1;
+        let mut root_children = 0;
+        stack.push((root, graph.offsets[root as usize]));
+
+        while let Some((node, position)) = stack.last_mut() {
+            let node = *node as usize;
+            if *position < graph.offsets[node + 1] {
+                let neighbour = graph.neighbours[*position];
+                *position += 1;
+
+                if discovery[neighbour as usize] == UNVISITED {
+                    parent[neighbour as usize] = node as u32;
+                    discovery[neighbour as usize] = time;
+                    low[neighbour as usize] = time;
+                    time += 1;
+                    if node as u32 == root {
+                        root_children += 1;
+                    }
+                    stack.push((neighbour, graph.offsets[neighbour as usize]));
+                } else if neighbour != parent[node] {
+                    low[node] = low[node].min(discovery[neighbour as usize]);
+                }
+            } else {
+                stack.pop();
+                if let Some((parent_node, _)) = stack.last() {
+                    let parent_node = *parent_node as usize;
+                    low[parent_node] = low[parent_node].min(low[node]);
+                    if low[node] > discovery[parent_node] {
+                        bridges.push((graph.ids[parent_node], graph.ids[node]));
+                    }
+                    if parent_node as u32 != root && low[node] >= discovery[parent_node] {
+                        is_articulation_point[parent_node] = true;
+                    }
+                }
+            }
+        }
+
+        if root_children > 1 {
+            is_articulation_point[root as usize] = true;
+        }
+    }
+
+    CutStructure {
+        articulation_points: graph
+            .ids
+            .iter()
+            .zip(is_articulation_point)
+            .filter_map(|(id, is_ap)| is_ap.then_some(*id))
+            .collect(),
+        bridges,
+    }
+}
+
+/// Node of the flow network. With vertex disjoint paths every page is split into
+/// In -> Out with capacity 1, otherwise only Out nodes are used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum FlowNode {
+    In(PageId),
+    Out(PageId),
+}
+
+/// Residual arc taken by an augmenting path
+#[derive(Debug, Clone, Copy)]
+enum ResidualArc {
+    /// link from -> to, forward or against existing flow
+    Link {
+        from: PageId,
+        to: PageId,
+        forward: bool,
+    },
+    /// In -> Out of a split page, forward or against existing flow
+    Split { page: PageId, forward: bool },
+}
+
+/// Maximum number of edge disjoint (or, if vertex_disjoint, internally vertex disjoint)
+/// directed paths start -> end. Edmonds-Karp with unit capacities, so it runs at most
+/// min(out degree of start, in degree of end) bfs over the link graph
+pub fn count_disjoint_paths(
+    start: PageId,
+    end: PageId,
+    outgoing: &DBCache,
+    incoming: &DBCache,
+    vertex_disjoint: bool,
+) -> u32 {
+    if start == end {
+        return 0;
+    }
+
+    let link_target = |page: PageId| {
+        if vertex_disjoint {
+            FlowNode::In(page)
+        } else {
+            FlowNode::Out(page)
+        }
+    };
+    let source = FlowNode::Out(start);
+    let sink = link_target(end);
+
+    // links (from, to) and split pages that carry one unit of flow
+    let mut link_flow: FxHashSet<(PageId, PageId)> = FxHashSet::default();
+    let mut split_flow: FxHashSet<PageId> = FxHashSet::default();
+    let mut num_paths = 0;
+
+    loop {
+        let mut prev: FxHashMap<FlowNode, (FlowNode, ResidualArc)> = FxHashMap::default();
+        let mut queue = VecDeque::from([source]);
+        let mut found = false;
+
+        'bfs: while let Some(node) = queue.pop_front() {
+            let mut arcs: Vec<(FlowNode, ResidualArc)> = Vec::new();
+
+            // links are taken forward from Out nodes and against their flow from the node they end in
+            let (page, is_out) = match node {
+                FlowNode::In(page) => (page, false),
+                FlowNode::Out(page) => (page, true),
+            };
+            if is_out {
+                for link in outgoing.get(&page).into_iter().flatten() {
+                    if *link != start && !link_flow.contains(&(page, *link)) {
+                        let arc = ResidualArc::Link {
+                            from: page,
+                            to: *link,
+                            forward: true,
+                        };
+                        arcs.push((link_target(*link), arc));
+                    }
+                }
+            }
+            if !is_out || !vertex_disjoint {
+                for link in incoming.get(&page).into_iter().flatten() {
+                    if link_flow.contains(&(*link, page)) {
+                        let arc = ResidualArc::Link {
+                            from: *link,
+                            to: page,
+                            forward: false,
+                        };
+                        arcs.push((FlowNode::Out(*link), arc));
+                    }
+                }
+            }
+            if vertex_disjoint {
+                let has_flow = split_flow.contains(&page);
+                match node {
+                    FlowNode::In(_) if !has_flow => {
+                        let arc = ResidualArc::Split {
+                            page,
+                            forward: true,
+                        };
+                        arcs.push((FlowNode::Out(page), arc));
+                    }
+                    FlowNode::Out(_) if has_flow => {
+                        let arc = ResidualArc::Split {
+                            page,
+                            forward: false,
+                        };
+                        arcs.push((FlowNode::In(page), arc));
+                    }
+                    _ => {}
+                }
+            }
+
+            for (next, arc) in arcs {
+                if next == source || prev.contains_key(&next) {
+                    continue;
+                }
+                prev.insert(next, (node, arc));
+                if next == sink {
+                    found = true;
+                    break 'bfs;
+                }
+                queue.push_back(next);
+            }
+        }
+
+        if !found {
+            break;
+        }
+
+        let mut node = sink;
+        while node != source {
+            let (previous, arc) = prev[&node];
+            match arc {
+                ResidualArc::Link {
+                    from,
+                    to,
+                    forward: true,
+                } => {
+                    link_flow.insert((from, to));
+                }
+                ResidualArc::Link {
+                    from,
+                    to,
+                    forward: false,
+                } => {
+                    link_flow.remove(&(from, to));
+                }
+                ResidualArc::Split {
+                    page,
+                    forward: true,
+                } => {
+                    split_flow.insert(page);
+                }
+                ResidualArc::Split {
+                    page,
+                    forward: false,
+                } => {
+                    split_flow.remove(&page);
+                }
+            }
+            node = previous;
+        }
+        num_paths += 1;
+    }
+
+    num_paths
+}
+
+#[cfg(test)]
+mod tests {
+    use fxhash::FxHashMap;
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::DBCache;
+    use crate::calc::robustness::{articulation_points_and_bridges, count_disjoint_paths};
+
+    fn caches(links: &[(u32, u32)]) -> (DBCache, DBCache) {
+        let mut outgoing: DBCache = FxHashMap::default();
+        let mut incoming: DBCache = FxHashMap::default();
+        for (from, to) in links {
+            outgoing.entry(PageId(*from)).or_default().push(PageId(*to));
+            incoming.entry(PageId(*to)).or_default().push(PageId(*from));
+        }
+        (outgoing, incoming)
+    }
+
+    #[test]
+    fn test_articulation_points_and_bridges() {
+        // triangle 1-2-3, bridge 3-4, triangle 4-5-6 (with a mutual link 5 <-> 6), separate 7-8
+        let (outgoing, _) = caches(&[
+            (1, 2),
+            (2, 3),
+            (3, 1),
+            (3, 4),
+            (4, 5),
+            (5, 6),
+            (6, 5),
+            (6, 4),
+            (7, 8),
+        ]);
+
+        let cut = articulation_points_and_bridges(&outgoing);
+        assert_eq!(cut.articulation_points, vec![PageId(3), PageId(4)]);
+
+        let mut bridges: Vec<(u32, u32)> = cut
+            .bridges
+            .iter()
+            .map(|(a, b)| (a.0.min(b.0), a.0.max(b.0)))
+            .collect();
+        bridges.sort();
+        assert_eq!(bridges, vec![(3, 4), (7, 8)]);
+    }
+
+    #[test]
+    fn test_count_disjoint_paths() {
+        // 1 -> 2 -> 4, 1 -> 3 -> 4, 1 -> 5 -> 3: two vertex disjoint paths 1 -> 4,
+        // and also two edge disjoint ones since 3 -> 4 is a single link
+        let (outgoing, incoming) = caches(&[(1, 2), (2, 4), (1, 3), (3, 4), (1, 5), (5, 3)]);
+        assert_eq!(
+            count_disjoint_paths(PageId(1), PageId(4), &outgoing, &incoming, false),
+            2
+        );
+        assert_eq!(
+            count_disjoint_paths(PageId(1), PageId(4), &outgoing, &incoming, true),
+            2
+        );
+        assert_eq!(
+            count_disjoint_paths(PageId(4), PageId(1), &outgoing, &incoming, false),
+            0
+        );
+
+        // bowtie: 1 -> {2, 3} -> 4 -> {5, 6} -> 7, 4 is shared by every path
+        let (outgoing, incoming) = caches(&[
+            (1, 2),
+            (1, 3),
+            (2, 4),
+            (3, 4),
+            (4, 5),
+            (4, 6),
+            (5, 7),
+            (6, 7),
+        ]);
+        assert_eq!(
+            count_disjoint_paths(PageId(1), PageId(7), &outgoing, &incoming, false),
+            2
+        );
+        assert_eq!(
+            count_disjoint_paths(PageId(1), PageId(7), &outgoing, &incoming, true),
+            1
+        );
+
+        // finding the second path requires undoing flow on 2 -> 3:
+        // 1 -> 2 -> 3 -> 4 blocks both 1 -> 2 -> 5 -> 4 and 1 -> 6 -> 3 -> 4
+        let (outgoing, incoming) =
+            caches(&[(1, 2), (2, 3), (3, 4), (2, 5), (5, 4), (1, 6), (6, 3)]);
+        assert_eq!(
+            count_disjoint_paths(PageId(1), PageId(4), &outgoing, &incoming, false),
+            2
+        );
+        assert_eq!(
+            count_disjoint_paths(PageId(1), PageId(4), &outgoing, &incoming, true),
+            2
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::thread;
+use std::time::Instant;
+
+use fxhash::{FxHashMap, FxHashSet};
+use log::info;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::DBCache;
+use crate::sqlite::page_links::{LinkCaches, get_links_of_ids_cached};
+use crate::sqlite::similarity::{bucket_pages, load_degrees, load_signatures};
+
+/// Number of MinHash values per page
+pub const NUM_HASHES: usize = 64;
+/// Hashes per LSH band. Pages agreeing in all hashes of at least one band become candidates,
+/// with NUM_HASHES / BAND_SIZE = 16 bands a pair with jaccard 0.5 is found with ~64% probability
+pub const BAND_SIZE: usize = 4;
+pub const NUM_BANDS: usize = NUM_HASHES / BAND_SIZE;
+
+/// Candidates taken from a single bucket, so buckets of very common link sets stay cheap
+const MAX_BUCKET_PAGES: usize = 1000;
+/// Candidates re-ranked by the exact weighted cosine similarity per requested result
+const RERANK_FACTOR: usize = 5;
+
+/// Precomputed summary of the links of a page, see [`compute_signatures`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageSignature {
+    pub num_outgoing: u32,
+    pub num_incoming: u32,
+    /// Minimum hash of the link set for each of the NUM_HASHES hash functions
+    pub minhash: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimilarPage {
+    pub page_id: PageId,
+    /// Share of matching MinHash values, an estimate of the jaccard index of both link sets
+    pub estimated_jaccard: f64,
+    /// Cosine similarity of the link sets, links to/from pages with few links weighted higher
+    pub weighted_cosine: f64,
+}
+
+/// splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Links to a page and links from the same page are different elements of the link set
+fn link_element(page_id: PageId, incoming: bool) -> u64 {
+    ((page_id.0 as u64) << 1) | incoming as u64
+}
+
+pub fn minhash(outgoing: &[PageId], incoming: &[PageId]) -> Vec<u32> {
+    let elements: Vec<u64> = outgoing
+        .iter()
+        .map(|pid| link_element(*pid, false))
+        .chain(incoming.iter().map(|pid| link_element(*pid, true)))
+        .collect();
+
+    (0..NUM_HASHES as u64)
+        .map(|i| {
+            let seed = mix(i);
+            elements
+                .iter()
+                .map(|element| mix(element ^ seed) as u32)
+                .min()
+                .unwrap_or(u32::MAX)
+        })
+        .collect()
+}
+
+/// One bucket per band: a hash of the band's NUM_HASHES / NUM_BANDS minhash values
+pub fn band_buckets(minhash: &[u32]) -> Vec<i64> {
+    minhash
+        .chunks(BAND_SIZE)
+        .map(|band| band.iter().fold(0, |hash, value| mix(hash ^ *value as u64)) as i64)
+        .collect()
+}
+
+pub fn estimated_jaccard(a: &[u32], b: &[u32]) -> f64 {
+    let matching = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    matching as f64 / a.len().max(1) as f64
+}
+
+/// Signatures of every page with at least one link, computed in num_threads threads
+pub fn compute_signatures(
+    outgoing: &DBCache,
+    incoming: &DBCache,
+    num_threads: usize,
+) -> FxHashMap<PageId, PageSignature> {
+    let t1 = Instant::now();
+    let page_ids: Vec<PageId> = outgoing
+        .keys()
+        .chain(incoming.keys())
+        .copied()
+        .collect::<FxHashSet<PageId>>()
+        .into_iter()
+        .collect();
+
+    let chunk_size = page_ids.len().div_ceil(num_threads.max(1)).max(1);
+    let signatures = thread::scope(|s| {
+        let handles: Vec<_> = page_ids
+            .chunks(chunk_size)
+            .map(|chunk| {
+                s.spawn(|| {
+                    chunk
+                        .iter()
+                        .map(|page_id| {
+                            let links = outgoing.get(page_id).map(Vec::as_slice).unwrap_or(&[]);
+                            let backlinks = incoming.get(page_id).map(Vec::as_slice).unwrap_or(&[]);
+                            let signature = PageSignature {
+                                num_outgoing: links.len() as u32,
+                                num_incoming: backlinks.len() as u32,
+                                minhash: minhash(links, backlinks),
+                            };
+                            (*page_id, signature)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("signature thread panicked"))
+            .collect()
+    });
+
+    info!(
+        "Computed the signatures of {} pages in {:?}",
+        page_ids.len(),
+        t1.elapsed()
+    );
+    signatures
+}
+
+/// (outgoing, incoming) links of every id, served from caches where possible
+fn link_sets(
+    conn: &Connection,
+    caches: &LinkCaches,
+    ids: &[PageId],
+) -> FxHashMap<PageId, (Vec<PageId>, Vec<PageId>)> {
+    let mut link_sets: FxHashMap<PageId, (Vec<PageId>, Vec<PageId>)> =
+        ids.iter().map(|id| (*id, Default::default())).collect();
+
+    let (outgoing, _, _) = get_links_of_ids_cached(conn, ids.to_vec(), &caches.outgoing, false);
+    for (page_id, page_link) in outgoing {
+        link_sets.get_mut(&page_id).unwrap().0.push(page_link);
+    }
+    let (incoming, _, _) = get_links_of_ids_cached(conn, ids.to_vec(), &caches.incoming, true);
+    for (page_id, page_link) in incoming {
+        link_sets.get_mut(&page_link).unwrap().1.push(page_id);
+    }
+    link_sets
+}
+
+/// Pages with the most similar links to page_id, most similar first.
+/// Candidates are the pages sharing a LSH bucket with page_id (see [`band_buckets`]), the best of
+/// them by [`estimated_jaccard`] are ranked by their weighted cosine similarity. Every link
+/// element is weighted by 1 / ln(2 + degree), where the degree is the number of incoming links
+/// of the linked page (or outgoing links of the linking page), so shared links to hubs count less.
+///
+/// Requires the signatures stored by [`crate::sqlite::similarity::save_signatures`]. Empty if
+/// page_id has no signature
+pub fn similar_pages(
+    page_id: PageId,
+    conn: &Connection,
+    caches: &LinkCaches,
+    num_results: usize,
+) -> Vec<SimilarPage> {
+    let Some(signature) = load_signatures(conn, &[page_id]).remove(&page_id) else {
+        return vec![];
+    };
+
+    let mut candidate_ids: FxHashSet<PageId> = FxHashSet::default();
+    for (band, bucket) in band_buckets(&signature.minhash).into_iter().enumerate() {
+        candidate_ids.extend(bucket_pages(conn, band, bucket, MAX_BUCKET_PAGES));
+    }
+    candidate_ids.remove(&page_id);
+
+    let candidate_ids: Vec<PageId> = candidate_ids.into_iter().collect();
+    let mut candidates: Vec<(PageId, f64)> = load_signatures(conn, &candidate_ids)
+        .into_iter()
+        .map(|(pid, candidate)| {
+            (
+                pid,
+                estimated_jaccard(&signature.minhash, &candidate.minhash),
+            )
+        })
+        .collect();
+    candidates.sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then(a_id.0.cmp(&b_id.0)));
+    candidates.truncate(num_results * RERANK_FACTOR);
+
+    let ids: Vec<PageId> = std::iter::once(page_id)
+        .chain(candidates.iter().map(|(pid, _)| *pid))
+        .collect();
+    let link_sets = link_sets(conn, caches, &ids);
+
+    let linked_ids: Vec<PageId> = link_sets
+        .values()
+        .flat_map(|(links, backlinks)| links.iter().chain(backlinks))
+        .copied()
+        .collect::<FxHashSet<PageId>>()
+        .into_iter()
+        .collect();
+    let degrees = load_degrees(conn, &linked_ids);
+
+    let weighted_elements = |page_id: &PageId| -> FxHashMap<u64, f64> {
+        let (links, backlinks) = &link_sets[page_id];
+        let weight = |degree: u32| 1.0 / (2.0 + degree as f64).ln();
+        links
+            .iter()
+            .map(|pid| {
+                let (_, num_incoming) = degrees.get(pid).copied().unwrap_or_default();
+                (link_element(*pid, false), weight(num_incoming))
+            })
+            .chain(backlinks.iter().map(|pid| {
+                let (num_outgoing, _) = degrees.get(pid).copied().unwrap_or_default();
+                (link_element(*pid, true), weight(num_outgoing))
+            }))
+            .collect()
+    };
+    let norm =
+        |elements: &FxHashMap<u64, f64>| elements.values().map(|w| w * w).sum::<f64>().sqrt();
+
+    let page_elements = weighted_elements(&page_id);
+    let page_norm = norm(&page_elements);
+
+    let mut similar: Vec<SimilarPage> = candidates
+        .into_iter()
+        .map(|(candidate_id, estimated_jaccard)| {
+            let elements = weighted_elements(&candidate_id);
+            let dot: f64 = elements
+                .iter()
+                .filter_map(|(element, w)| page_elements.get(element).map(|page_w| w * page_w))
+                .sum();
+            let norms = page_norm * norm(&elements);
+            SimilarPage {
+                page_id: candidate_id,
+                estimated_jaccard,
+                weighted_cosine: if norms > 0.0 { dot / norms } else { 0.0 },
+            }
+        })
+        .collect();
+
+    similar.sort_by(|a, b| {
+        b.weighted_cosine
+            .total_cmp(&a.weighted_cosine)
+            .then(a.page_id.0.cmp(&b.page_id.0))
+    });
+    similar.truncate(num_results);
+    similar
+}
+
+#[cfg(test)]
+mod tests {
+    use fxhash::FxHashMap;
+    use parse_mediawiki_sql::field_types::PageId;
+    use rusqlite::Connection;
+
+    use crate::DBCache;
+    use crate::calc::similarity::{
+        NUM_HASHES, compute_signatures, estimated_jaccard, minhash, similar_pages,
+    };
+    use crate::sqlite::page_links::{LinkCaches, db_setup};
+    use crate::sqlite::similarity::save_signatures;
+    use crate::test_utils::insert_links;
+
+    #[test]
+    fn test_minhash() {
+        let ids = |ids: &[u32]| ids.iter().map(|id| PageId(*id)).collect::<Vec<_>>();
+        let a = minhash(&ids(&[1, 2, 3, 4]), &ids(&[5]));
+        assert_eq!(a.len(), NUM_HASHES);
+        assert_eq!(a, minhash(&ids(&[4, 3, 2, 1]), &ids(&[5])));
+        // the same page as incoming link is a different element
+        assert!(estimated_jaccard(&a, &minhash(&ids(&[1, 2, 3, 4, 5]), &[])) < 1.0);
+
+        let disjoint = minhash(&ids(&[10, 11, 12, 13]), &ids(&[14]));
+        assert!(estimated_jaccard(&a, &disjoint) < 0.2);
+    }
+
+    #[test]
+    fn test_similar_pages() {
+        // 1, 2 and 3 link to most of 10..=20, 4 to 20 and 21..=30
+        let mut links: Vec<(u32, u32)> = vec![];
+        links.extend((10..=20).map(|to| (1, to)));
+        links.extend((10..=20).map(|to| (2, to)));
+        links.extend((11..=19).map(|to| (3, to)));
+        links.extend((20..=30).map(|to| (4, to)));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test_database.sqlite");
+        let conn = Connection::open(&path).unwrap();
+        db_setup(&conn);
+        insert_links(&conn, &links);
+        let mut outgoing: DBCache = FxHashMap::default();
+        let mut incoming: DBCache = FxHashMap::default();
+        for (from, to) in &links {
+            outgoing.entry(PageId(*from)).or_default().push(PageId(*to));
+            incoming.entry(PageId(*to)).or_default().push(PageId(*from));
+        }
+
+        let signatures = compute_signatures(&outgoing, &incoming, 2);
+        assert_eq!(signatures.len(), 4 + 21);
+        assert_eq!(signatures[&PageId(1)].num_outgoing, 11);
+        assert_eq!(signatures[&PageId(20)].num_incoming, 3);
+        save_signatures(&path, &signatures);
+
+        let similar = similar_pages(PageId(1), &conn, &LinkCaches::default(), 2);
+        assert_eq!(similar[0].page_id, PageId(2));
+        assert_eq!(similar[0].estimated_jaccard, 1.0);
+        assert!((similar[0].weighted_cosine - 1.0).abs() < 1e-9);
+        assert!(similar.iter().all(|page| page.page_id != PageId(4)));
+
+        assert!(similar_pages(PageId(99), &conn, &LinkCaches::default(), 2).is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+use fxhash::FxHashMap;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::sqlite::page_links::{LinkCaches, get_links_of_ids_cached};
+
+/// Pages per database query when expanding a level
+const LEVEL_BATCH_SIZE: usize = 10_000;
+
+/// Which links a path may follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraversalMode {
+    /// Outgoing links, the usual wiki game path
+    Directed,
+    /// Incoming links, every page of the path is linked by the next one
+    Reverse,
+    /// Outgoing and incoming links
+    Undirected,
+}
+
+impl TraversalMode {
+    /// The mode that walks the same links the other way round (used from the end page)
+    fn inverse(self) -> Self {
+        match self {
+            TraversalMode::Directed => TraversalMode::Reverse,
+            TraversalMode::Reverse => TraversalMode::Directed,
+            TraversalMode::Undirected => TraversalMode::Undirected,
+        }
+    }
+}
+
+/// Direction of the link used by a hop a -> b of a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HopDirection {
+    /// a links to b
+    Forward,
+    /// b links to a
+    Backward,
+}
+
+impl HopDirection {
+    fn flip(self) -> Self {
+        match self {
+            HopDirection::Forward => HopDirection::Backward,
+            HopDirection::Backward => HopDirection::Forward,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirectedPath {
+    pub pages: Vec<PageId>,
+    /// hops[i] is the direction of the link between pages[i] and pages[i + 1]
+    pub hops: Vec<HopDirection>,
+    pub visited: usize,
+}
+
+/// (from, to, direction) of every hop from the pages to their neighbours in the mode
+pub(crate) fn expand(
+    pages: &[PageId],
+    mode: TraversalMode,
+    conn: &Connection,
+    caches: &LinkCaches,
+) -> Vec<(PageId, PageId, HopDirection)> {
+    let mut hops = vec![];
+    for chunk in pages.chunks(LEVEL_BATCH_SIZE) {
+        if mode != TraversalMode::Reverse {
+            let (links, _, _) =
+                get_links_of_ids_cached(conn, chunk.to_vec(), &caches.outgoing, false);
+            hops.extend(
+                links
+                    .into_iter()
+                    .map(|(page_id, link)| (page_id, link, HopDirection::Forward)),
+            );
+        }
+        if mode != TraversalMode::Directed {
+            let (links, _, _) =
+                get_links_of_ids_cached(conn, chunk.to_vec(), &caches.incoming, true);
+            hops.extend(
+                links
+                    .into_iter()
+                    .map(|(source, page_id)| (page_id, source, HopDirection::Backward)),
+            );
+        }
+    }
+    hops
+}
+
+/// One side of the bidirectional search: distance and the hop a page was reached with
+struct Side {
+    frontier: Vec<PageId>,
+    depth: u32,
+    reached: FxHashMap<PageId, (u32, Option<(PageId, HopDirection)>)>,
+}
+
+impl Side {
+    fn new(start: PageId) -> Self {
+        Self {
+            frontier: vec![start],
+            depth: 0,
+            reached: FxHashMap::from_iter([(start, (0, None))]),
+        }
+    }
+
+    /// Pages from page back to the start of this side, with the hops as they were walked
+    fn trace(&self, page_id: PageId) -> (Vec<PageId>, Vec<HopDirection>) {
+        let mut pages = vec![page_id];
+        let mut hops = vec![];
+        let mut current = page_id;
+        while let Some((_, Some((prev, direction)))) = self.reached.get(&current) {
+            pages.push(*prev);
+            hops.push(*direction);
+            current = *prev;
+        }
+        (pages, hops)
+    }
+}
+
+/// Shortest path from start to end following the links allowed by mode, with the direction of
+/// every hop. Bidirectional bfs, always expanding the smaller frontier by a whole level
+pub fn shortest_path_mode(
+    start: PageId,
+    end: PageId,
+    mode: TraversalMode,
+    conn: &Connection,
+    caches: &LinkCaches,
+) -> Option<DirectedPath> {
+    let mut forward = Side::new(start);
+    let mut backward = Side::new(end);
+
+    let mut meeting = (start == end).then_some(start);
+    while meeting.is_none() && !forward.frontier.is_empty() && !backward.frontier.is_empty() {
+        let expand_forward = forward.frontier.len() <= backward.frontier.len();
+        let (side, other, side_mode) = if expand_forward {
+            (&mut forward, &backward, mode)
+        } else {
+            (&mut backward, &forward, mode.inverse())
+        };
+
+        side.depth += 1;
+        let mut next = vec![];
+        for (from, to, direction) in expand(&side.frontier, side_mode, conn, caches) {
+            if side.reached.contains_key(&to) {
+                continue;
+            }
+            side.reached
+                .insert(to, (side.depth, Some((from, direction))));
+            next.push(to);
+        }
+        side.frontier = next;
+
+        // all new pages have the same depth on this side, so the best meeting point is the one
+        // closest to the other side
+        meeting = side
+            .frontier
+            .iter()
+            .filter_map(|pid| other.reached.get(pid).map(|(depth, _)| (*depth, *pid)))
+            .min_by_key(|(depth, pid)| (*depth, pid.0))
+            .map(|(_, pid)| pid);
+    }
+
+    let meeting = meeting?;
+    let (mut pages, mut hops) = forward.trace(meeting);
+    pages.reverse();
+    hops.reverse();
+
+    // the backward side walked the links from the end, so its hops point the other way
+    let (back_pages, back_hops) = backward.trace(meeting);
+    pages.extend(back_pages.into_iter().skip(1));
+    hops.extend(back_hops.into_iter().map(HopDirection::flip));
+
+    Some(DirectedPath {
+        pages,
+        hops,
+        visited: forward.reached.len() + backward.reached.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::calc::traversal::{
+        HopDirection::{Backward, Forward},
+        TraversalMode, shortest_path_mode,
+    };
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::test_utils::graph_db;
+
+    #[test]
+    fn test_shortest_path_modes() {
+        // 1 -> 2 -> 3 -> 4, 5 -> 4, 5 -> 6
+        let conn = graph_db(&[(1, 2), (2, 3), (3, 4), (5, 4), (5, 6)]);
+        let caches = LinkCaches::default();
+        let path = |start: u32, end: u32, mode: TraversalMode| {
+            shortest_path_mode(PageId(start), PageId(end), mode, &conn, &caches).map(|path| {
+                (
+                    path.pages.iter().map(|pid| pid.0).collect::<Vec<_>>(),
+                    path.hops,
+                )
+            })
+        };
+
+        assert_eq!(
+            path(1, 4, TraversalMode::Directed),
+            Some((vec![1, 2, 3, 4], vec![Forward, Forward, Forward]))
+        );
+        assert_eq!(path(4, 1, TraversalMode::Directed), None);
+        assert_eq!(
+            path(4, 1, TraversalMode::Reverse),
+            Some((vec![4, 3, 2, 1], vec![Backward, Backward, Backward]))
+        );
+        assert_eq!(path(1, 4, TraversalMode::Reverse), None);
+        assert_eq!(
+            path(3, 6, TraversalMode::Undirected),
+            Some((vec![3, 4, 5, 6], vec![Forward, Backward, Forward]))
+        );
+        assert_eq!(path(6, 3, TraversalMode::Directed), None);
+        assert_eq!(
+            path(6, 1, TraversalMode::Undirected),
+            Some((
+                vec![6, 5, 4, 3, 2, 1],
+                vec![Backward, Forward, Backward, Backward, Backward]
+            ))
+        );
+        assert_eq!(path(2, 2, TraversalMode::Reverse), Some((vec![2], vec![])));
+    }
+}
@@ -0,0 +1,485 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use fxhash::{FxHashMap, FxHashSet};
+use itertools::Itertools;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::DBCache;
+use crate::sqlite::page_links::{LinkCaches, get_links_of_ids_cached};
+use crate::sqlite::page_props::{DISAMBIGUATION, has_page_props};
+
+/// Title prefixes of list pages, as stored in WikiPage (with underscores)
+pub const DEFAULT_LIST_PREFIXES: [&str; 1] = ["List_of_"];
+
+/// Cost of following a link in [`weighted_shortest_path`]
+pub trait EdgeCost {
+    /// Called with the links of a page before their costs are asked for,
+    /// so implementations can load what they need in one batch
+    fn prepare(&mut self, _conn: &Connection, _links: &[PageId]) {}
+
+    /// Cost of the link from -> to, must not be negative
+    fn cost(&self, from: PageId, to: PageId) -> f64;
+}
+
+impl<C: EdgeCost + ?Sized> EdgeCost for Box<C> {
+    fn prepare(&mut self, conn: &Connection, links: &[PageId]) {
+        (**self).prepare(conn, links)
+    }
+
+    fn cost(&self, from: PageId, to: PageId) -> f64 {
+        (**self).cost(from, to)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathCost {
+    /// Every link costs 1, the same paths as the bfs
+    Unit,
+    /// Links to pages with many incoming links cost more ("most obscure" route)
+    Obscure,
+    /// Links to pages with many incoming links cost less ("most natural" route)
+    Natural,
+    /// Links to pages with a high PageRank cost less
+    PageRank,
+}
+
+/// Every link costs 1
+pub struct UnitCost;
+
+impl EdgeCost for UnitCost {
+    fn cost(&self, _from: PageId, _to: PageId) -> f64 {
+        1.0
+    }
+}
+
+/// ln(1 + x), how much of a hub a page with x (relative) incoming links is
+fn hub_weight(x: f64) -> f64 {
+    x.ln_1p()
+}
+
+/// Cost by the number of incoming links of the target: 1 + ln(1 + in-degree) when penalising hubs,
+/// 1 / (1 + ln(1 + in-degree)) when favouring them.
+/// In-degrees are taken from the incoming cache, pages missing in it are counted in the database
+pub struct InDegreeCost<'a> {
+    incoming: &'a DBCache,
+    favour_hubs: bool,
+    degrees: FxHashMap<PageId, u32>,
+}
+
+impl<'a> InDegreeCost<'a> {
+    pub fn new(incoming: &'a DBCache, favour_hubs: bool) -> Self {
+        Self {
+            incoming,
+            favour_hubs,
+            degrees: FxHashMap::default(),
+        }
+    }
+
+    fn in_degree(&self, page_id: PageId) -> u32 {
+        match self.incoming.get(&page_id) {
+            Some(links) => links.len() as u32,
+            None => self.degrees.get(&page_id).copied().unwrap_or(0),
+        }
+    }
+}
+
+impl EdgeCost for InDegreeCost<'_> {
+    fn prepare(&mut self, conn: &Connection, links: &[PageId]) {
+        let missing: Vec<PageId> = links
+            .iter()
+            .filter(|pid| !self.incoming.contains_key(pid) && !self.degrees.contains_key(pid))
+            .copied()
+            .unique()
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let ids_str = missing.iter().map(|pid| pid.0.to_string()).join(",");
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT page_link, COUNT(*) FROM WikiLink
+                WHERE page_link in ({ids_str}) GROUP BY page_link"
+            ))
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| Ok((PageId(row.get(0)?), row.get(1)?)))
+            .unwrap();
+
+        // links are only stored for pages that have some, so every missing page gets an entry
+        self.degrees.extend(missing.into_iter().map(|pid| (pid, 0)));
+        for row in rows {
+            let (page_id, degree) = row.unwrap();
+            self.degrees.insert(page_id, degree);
+        }
+    }
+
+    fn cost(&self, _from: PageId, to: PageId) -> f64 {
+        let weight = hub_weight(self.in_degree(to) as f64);
+        if self.favour_hubs {
+            1.0 / (1.0 + weight)
+        } else {
+            1.0 + weight
+        }
+    }
+}
+
+/// Favours pages with a high PageRank: 1 / (1 + ln(1 + rank * num_pages)), where rank * num_pages
+/// is the rank relative to the average one. Pages without rank cost 1
+pub struct PageRankCost<'a> {
+    ranks: &'a FxHashMap<PageId, f64>,
+}
+
+impl<'a> PageRankCost<'a> {
+    pub fn new(ranks: &'a FxHashMap<PageId, f64>) -> Self {
+        Self { ranks }
+    }
+}
+
+impl EdgeCost for PageRankCost<'_> {
+    fn cost(&self, _from: PageId, to: PageId) -> f64 {
+        let relative_rank = self.ranks.get(&to).copied().unwrap_or(0.0) * self.ranks.len() as f64;
+        1.0 / (1.0 + hub_weight(relative_rank))
+    }
+}
+
+/// Adds a penalty to the cost of links to redirects, list pages and disambiguation pages
+pub struct PagePenalty<C> {
+    inner: C,
+    redirect_penalty: f64,
+    list_penalty: f64,
+    list_prefixes: Vec<String>,
+    disambiguation_penalty: f64,
+    penalties: FxHashMap<PageId, f64>,
+}
+
+impl<C: EdgeCost> PagePenalty<C> {
+    /// List pages are recognised by [`DEFAULT_LIST_PREFIXES`]
+    pub fn new(inner: C, redirect_penalty: f64, list_penalty: f64) -> Self {
+        Self {
+            inner,
+            redirect_penalty,
+            list_penalty,
+            list_prefixes: DEFAULT_LIST_PREFIXES.map(String::from).to_vec(),
+            disambiguation_penalty: 0.0,
+            penalties: FxHashMap::default(),
+        }
+    }
+
+    /// Disambiguation pages are only known from the page props, without them there is no penalty.
+    /// f64::INFINITY keeps them out of the path unless there is no other one
+    pub fn with_disambiguation_penalty(mut self, disambiguation_penalty: f64) -> Self {
+        self.disambiguation_penalty = disambiguation_penalty;
+        self
+    }
+
+    pub fn with_list_prefixes(mut self, list_prefixes: Vec<String>) -> Self {
+        self.list_prefixes = list_prefixes;
+        self
+    }
+}
+
+impl<C: EdgeCost> EdgeCost for PagePenalty<C> {
+    fn prepare(&mut self, conn: &Connection, links: &[PageId]) {
+        self.inner.prepare(conn, links);
+
+        let missing: Vec<PageId> = links
+            .iter()
+            .filter(|pid| !self.penalties.contains_key(pid))
+            .copied()
+            .unique()
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let ids_str = missing.iter().map(|pid| pid.0.to_string()).join(",");
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT page_id, page_title, is_redirect FROM WikiPage WHERE page_id in ({ids_str})"
+            ))
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    PageId(row.get(0)?),
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<bool>>(2)?.unwrap_or(false),
+                ))
+            })
+            .unwrap();
+
+        self.penalties
+            .extend(missing.into_iter().map(|pid| (pid, 0.0)));
+        for row in rows {
+            let (page_id, title, is_redirect) = row.unwrap();
+            let mut penalty = 0.0;
+            if is_redirect {
+                penalty += self.redirect_penalty;
+            }
+            if self
+                .list_prefixes
+                .iter()
+                .any(|prefix| title.starts_with(prefix.as_str()))
+            {
+                penalty += self.list_penalty;
+            }
+            self.penalties.insert(page_id, penalty);
+        }
+
+        if self.disambiguation_penalty != 0.0 && has_page_props(conn) {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT page_id FROM WikiPageProps
+                    WHERE prop_name = '{DISAMBIGUATION}' AND page_id in ({ids_str})"
+                ))
+                .unwrap();
+            let rows = stmt.query_map([], |row| Ok(PageId(row.get(0)?))).unwrap();
+            for row in rows {
+                *self.penalties.entry(row.unwrap()).or_default() += self.disambiguation_penalty;
+            }
+        }
+    }
+
+    fn cost(&self, from: PageId, to: PageId) -> f64 {
+        self.inner.cost(from, to) + self.penalties.get(&to).copied().unwrap_or(0.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeightedPath {
+    pub path: Vec<PageId>,
+    /// Sum of the link costs along the path
+    pub cost: f64,
+    /// Number of settled pages
+    pub visited: usize,
+}
+
+/// Min-heap entry, ordered by cost (ties by page id, so the search is deterministic)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HeapEntry {
+    cost: f64,
+    page_id: PageId,
+}
+
+impl Eq for HeapEntry {}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| other.page_id.0.cmp(&self.page_id.0))
+    }
+}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Cheapest path from start to end over the outgoing links (Dijkstra), with link costs given by
+/// edge_cost. Gives up after settling max_visited pages (if given).
+/// Returns None if end is not reachable (within max_visited)
+pub fn weighted_shortest_path<C: EdgeCost + ?Sized>(
+    start: PageId,
+    end: PageId,
+    conn: &Connection,
+    caches: &LinkCaches,
+    edge_cost: &mut C,
+    max_visited: Option<usize>,
+) -> Option<WeightedPath> {
+    let mut dist: FxHashMap<PageId, f64> = FxHashMap::from_iter([(start, 0.0)]);
+    let mut prev: FxHashMap<PageId, PageId> = FxHashMap::default();
+    let mut settled: FxHashSet<PageId> = FxHashSet::default();
+    let mut heap = BinaryHeap::from([HeapEntry {
+        cost: 0.0,
+        page_id: start,
+    }]);
+
+    while let Some(HeapEntry { cost, page_id }) = heap.pop() {
+        if !settled.insert(page_id) {
+            continue;
+        }
+
+        if page_id == end {
+            let mut path = vec![end];
+            let mut current = end;
+            while let Some(p) = prev.get(&current) {
+                path.push(*p);
+                current = *p;
+            }
+            path.reverse();
+            return Some(WeightedPath {
+                path,
+                cost,
+                visited: settled.len(),
+            });
+        }
+
+        if max_visited.is_some_and(|max_visited| settled.len() >= max_visited) {
+            break;
+        }
+
+        let (links, _, _) = get_links_of_ids_cached(conn, vec![page_id], &caches.outgoing, false);
+        let links: Vec<PageId> = links
+            .into_iter()
+            .map(|(_, link)| link)
+            .filter(|link| !settled.contains(link))
+            .collect();
+        edge_cost.prepare(conn, &links);
+
+        for link in links {
+            let alternative = cost + edge_cost.cost(page_id, link).max(0.0);
+            if dist.get(&link).is_none_or(|d| alternative < *d) {
+                dist.insert(link, alternative);
+                prev.insert(link, page_id);
+                heap.push(HeapEntry {
+                    cost: alternative,
+                    page_id: link,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use fxhash::FxHashMap;
+    use parse_mediawiki_sql::field_types::PageId;
+    use rusqlite::Connection;
+
+    use crate::calc::weighted_paths::{
+        InDegreeCost, PagePenalty, PageRankCost, UnitCost, weighted_shortest_path,
+    };
+    use crate::sqlite::page_links::LinkCaches;
+    use crate::sqlite::page_props;
+    use crate::test_utils::{graph_db, insert_pages};
+
+    fn path(conn: &Connection, caches: &LinkCaches, cost: &mut impl super::EdgeCost) -> Vec<u32> {
+        weighted_shortest_path(PageId(1), PageId(5), conn, caches, cost, None)
+            .unwrap()
+            .path
+            .iter()
+            .map(|pid| pid.0)
+            .collect()
+    }
+
+    #[test]
+    fn test_weighted_shortest_path() {
+        // 1 -> 2 -> 5 through the hub 2 (linked by 10..30), 1 -> 3 -> 4 -> 5 around it
+        let mut links = vec![(1, 2), (2, 5), (1, 3), (3, 4), (4, 5)];
+        links.extend((10..30).map(|source| (source, 2)));
+        let conn = graph_db(&links);
+        insert_pages(
+            &conn,
+            &[
+                (2, "Hub", false),
+                (3, "List_of_things", false),
+                (4, "Four", true),
+            ],
+        );
+
+        let caches = LinkCaches::default();
+        let result =
+            weighted_shortest_path(PageId(1), PageId(5), &conn, &caches, &mut UnitCost, None)
+                .unwrap();
+        assert_eq!(result.path, vec![PageId(1), PageId(2), PageId(5)]);
+        assert_eq!(result.cost, 2.0);
+
+        // in-degrees from the database and from the cache give the same result
+        assert_eq!(
+            path(
+                &conn,
+                &caches,
+                &mut InDegreeCost::new(&caches.incoming, false)
+            ),
+            vec![1, 3, 4, 5]
+        );
+        let cached = LinkCaches::new(
+            FxHashMap::default(),
+            FxHashMap::from_iter([(PageId(2), (10..30).chain([1]).map(PageId).collect())]),
+        );
+        assert_eq!(
+            path(
+                &conn,
+                &cached,
+                &mut InDegreeCost::new(&cached.incoming, false)
+            ),
+            vec![1, 3, 4, 5]
+        );
+        assert_eq!(
+            path(
+                &conn,
+                &caches,
+                &mut InDegreeCost::new(&caches.incoming, true)
+            ),
+            vec![1, 2, 5]
+        );
+
+        let ranks = FxHashMap::from_iter([
+            (PageId(2), 0.0),
+            (PageId(3), 0.5),
+            (PageId(4), 0.5),
+            (PageId(5), 0.0),
+        ]);
+        assert_eq!(
+            path(&conn, &caches, &mut PageRankCost::new(&ranks)),
+            vec![1, 3, 4, 5]
+        );
+
+        // the list page and the redirect make the way around the hub more expensive
+        let mut penalised = PagePenalty::new(InDegreeCost::new(&caches.incoming, false), 1.0, 1.0);
+        assert_eq!(path(&conn, &caches, &mut penalised), vec![1, 2, 5]);
+
+        let cost = |edge_cost: &mut PagePenalty<UnitCost>| {
+            weighted_shortest_path(PageId(1), PageId(4), &conn, &caches, edge_cost, None)
+                .unwrap()
+                .cost
+        };
+        assert_eq!(cost(&mut PagePenalty::new(UnitCost, 0.0, 1.0)), 3.0);
+        assert_eq!(
+            cost(&mut PagePenalty::new(UnitCost, 0.0, 1.0).with_list_prefixes(vec![])),
+            2.0
+        );
+        assert_eq!(cost(&mut PagePenalty::new(UnitCost, 2.0, 0.0)), 4.0);
+
+        // the hub as disambiguation page is avoided, without page props the penalty is ignored
+        let avoid_disambiguation =
+            || PagePenalty::new(UnitCost, 0.0, 0.0).with_disambiguation_penalty(f64::INFINITY);
+        assert_eq!(
+            path(&conn, &caches, &mut avoid_disambiguation()),
+            vec![1, 2, 5]
+        );
+        page_props::db_setup(&conn);
+        conn.execute(
+            "INSERT INTO WikiPageProps (page_id, prop_name, value) VALUES (2, 'disambiguation', '')",
+            (),
+        )
+        .unwrap();
+        assert_eq!(
+            path(&conn, &caches, &mut avoid_disambiguation()),
+            vec![1, 3, 4, 5]
+        );
+
+        assert_eq!(
+            weighted_shortest_path(PageId(5), PageId(1), &conn, &caches, &mut UnitCost, None),
+            None
+        );
+        assert_eq!(
+            weighted_shortest_path(PageId(1), PageId(5), &conn, &caches, &mut UnitCost, Some(2)),
+            None
+        );
+        let result =
+            weighted_shortest_path(PageId(1), PageId(1), &conn, &caches, &mut UnitCost, None)
+                .unwrap();
+        assert_eq!((result.path, result.cost), (vec![PageId(1)], 0.0));
+    }
+}
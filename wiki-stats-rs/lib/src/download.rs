@@ -196,6 +196,25 @@ pub async fn latest_dump_date(
 
 pub static ALL_DB_TABLES: [&str; 3] = ["page", "pagelinks", "linktarget"];
 
+/// Tables downloaded and inserted into the databases: [`ALL_DB_TABLES`] plus the optional
+/// page_props (disambiguation pages and Wikidata items) and redirect (targets of the redirects)
+pub static DOWNLOAD_DB_TABLES: [&str; 5] =
+    ["page", "pagelinks", "linktarget", "page_props", "redirect"];
+
+/// The tables of [`DOWNLOAD_DB_TABLES`] to download for a wiki. A dump is complete with the ones
+/// of [`ALL_DB_TABLES`], the optional ones are left out when they are not dumped (yet)
+pub async fn download_tables(wiki: &str, dump_date: &str) -> Vec<&'static str> {
+    let mut tables = vec![];
+    for table in DOWNLOAD_DB_TABLES {
+        if ALL_DB_TABLES.contains(&table) || check_dump_complete(wiki, &[table], dump_date).await {
+            tables.push(table);
+        } else {
+            warn!("[{dump_date}] Optional table [{table}] for {wiki} is missing, skipping it");
+        }
+    }
+    tables
+}
+
 static MIRROR_URLS: [&str; 5] = [
     "https://mirror.accum.se/mirror/wikimedia.org/dumps", // sweden 12MiB/s
     "https://wikimedia.mirror.clarkson.edu",              // new york 10MiB/s
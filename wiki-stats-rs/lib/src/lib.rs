@@ -18,6 +18,8 @@ pub mod calc;
 pub mod download;
 pub mod process;
 pub mod stats;
+#[cfg(test)]
+mod test_utils;
 pub mod utils;
 pub mod validate;
 pub mod web;
@@ -21,11 +21,13 @@ use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
 use crate::download::{self, clean_downloads};
-use crate::download::{ALL_DB_TABLES, unpack_gz_pb};
+use crate::download::{ALL_DB_TABLES, DOWNLOAD_DB_TABLES, download_tables, unpack_gz_pb};
 use crate::sqlite::load::load_linktarget_map;
 use crate::sqlite::title_id_conv::TitleIdMap;
 use crate::sqlite::to_sqlite::{LinkTargetTitleMap, ToSqlite};
-use crate::sqlite::{join_db_wiki_path, page_links, title_id_conv, wiki};
+use crate::sqlite::{
+    join_db_wiki_path, page_links, page_props, red_links, redirects, title_id_conv,
+};
 
 // 1_591_804_203 20240401 pagelinks
 // 785_164_001 20240301 pagelinks
@@ -53,6 +55,8 @@ pub async fn process_wiki_to_db(
         &format!("{base_sql_str}-pagelinks.sql"),
         &format!("{base_sql_str}-page.sql"),
         &format!("{base_sql_str}-linktarget.sql"),
+        format!("{base_sql_str}-page_props.sql"),
+        format!("{base_sql_str}-redirect.sql"),
     );
 
     tosqlite.post_insert(&db_path);
@@ -103,6 +107,7 @@ pub async fn process_wikis_seq(
     dump_date_option: Option<String>,
     overwrite_sql: bool,
 ) -> String {
+    // the optional tables of DOWNLOAD_DB_TABLES don't count for a complete dump
     let tables = ALL_DB_TABLES;
 
     let dump_date = if let Some(dump_date) = dump_date_option {
@@ -128,7 +133,7 @@ pub async fn process_wikis_seq(
     let multi_pb = MultiProgress::new();
 
     for wiki_name in &wiki_names {
-        for table_name in tables {
+        for table_name in download_tables(wiki_name, &dump_date).await {
             download::download_wikis(
                 &[wiki_name],
                 &[table_name],
@@ -166,17 +171,18 @@ pub async fn process_threaded(
     overwrite_sql: bool,
 ) -> String {
     let t1 = Instant::now();
-    let processed_tables = ALL_DB_TABLES;
+    // the optional tables of DOWNLOAD_DB_TABLES don't count for a complete dump
+    let required_tables = ALL_DB_TABLES;
     let wiki_names: Vec<String> = wiki_names.iter().map(|s| s.as_ref().to_string()).collect();
 
     let dump_date = if let Some(dump_date) = dump_date_option {
-        if download::check_dump_complete_all(&wiki_names, &processed_tables, &dump_date).await {
+        if download::check_dump_complete_all(&wiki_names, &required_tables, &dump_date).await {
             dump_date
         } else {
             panic!("No new complete dump ready");
         }
     } else {
-        download::latest_dump_date(&wiki_names, &processed_tables, false, false)
+        download::latest_dump_date(&wiki_names, &required_tables, false, false)
             .await
             .expect("No new complete dump ready")
     };
@@ -190,7 +196,11 @@ pub async fn process_threaded(
     let mut wiki_names: Vec<String> = wiki_names.into_iter().map(|s| s.to_string()).collect();
     wiki_names.retain(|x| !done_wiki_names.contains(x));
 
-    let num_jobs = wiki_names.len() * processed_tables.len();
+    let mut wiki_tables = vec![];
+    for wiki_name in &wiki_names {
+        wiki_tables.push((wiki_name, download_tables(wiki_name, &dump_date).await));
+    }
+    let num_jobs = wiki_tables.iter().map(|(_, tables)| tables.len()).sum();
     let num_sql_threads = 2;
     let num_download_threads = 2;
 
@@ -209,10 +219,10 @@ pub async fn process_threaded(
 
     let job_queue: Arc<ArrayQueue<(String, String, usize)>> = Arc::new(ArrayQueue::new(num_jobs));
     let mut job_counter = 0;
-    for wiki_name in &wiki_names {
-        for table_name in processed_tables {
+    for (wiki_name, tables) in &wiki_tables {
+        for table_name in tables {
             job_queue
-                .push((wiki_name.clone(), table_name.to_string(), job_counter))
+                .push((wiki_name.to_string(), table_name.to_string(), job_counter))
                 .unwrap();
             job_counter += 1;
         }
@@ -285,7 +295,7 @@ pub async fn process_threaded(
     for wiki_name in &wiki_names {
         sql_wiki_queues.insert(
             wiki_name.clone(),
-            ArrayQueue::<String>::new(processed_tables.len()),
+            ArrayQueue::<String>::new(DOWNLOAD_DB_TABLES.len()),
         );
     }
 
@@ -343,6 +353,9 @@ struct PageLinksData {
     title_id_map: Option<TitleIdMap>,
     linktarget_title_map: Option<LinkTargetTitleMap>,
     pagelinks_sql_path: Option<PathBuf>,
+    /// page_props was inserted, its rows of missing pages are removed once WikiPage is done
+    page_props_done: bool,
+    redirect_sql_path: Option<PathBuf>,
 }
 
 impl PageLinksData {
@@ -351,6 +364,8 @@ impl PageLinksData {
             title_id_map: None,
             linktarget_title_map: None,
             pagelinks_sql_path: None,
+            page_props_done: false,
+            redirect_sql_path: None,
         }
     }
 }
@@ -428,7 +443,11 @@ fn tosqlite_threaded(
                             let mut w_mutex = wiki_settings_map.lock().await;
                             let pld = w_mutex.get_mut(&wiki_name).unwrap();
                             pld.title_id_map = Some(map);
+                            if pld.page_props_done {
+                                page_props::remove_props_of_missing_pages(&conn);
+                            }
 
+                            try_execute_redirects(&tosqlite, &mut conn, pld);
                             try_execute_pagelinks(&tosqlite, &mut conn, &out_db_path, pld).await;
                         }
                         "linktarget" => {
@@ -441,6 +460,25 @@ fn tosqlite_threaded(
 
                             try_execute_pagelinks(&tosqlite, &mut conn, &out_db_path, pld).await;
                         }
+                        "page_props" => {
+                            tosqlite.create_page_props_db(&sql_file_path, &mut conn);
+                            page_props::create_indices_post_setup(&conn);
+
+                            // WikiPage may still be inserted by another thread
+                            let mut w_mutex = wiki_settings_map.lock().await;
+                            let pld = w_mutex.get_mut(&wiki_name).unwrap();
+                            pld.page_props_done = true;
+                            if pld.title_id_map.is_some() {
+                                page_props::remove_props_of_missing_pages(&conn);
+                            }
+                        }
+                        "redirect" => {
+                            let mut w_mutex = wiki_settings_map.lock().await;
+                            let pld = w_mutex.get_mut(&wiki_name).unwrap();
+
+                            pld.redirect_sql_path = Some(sql_file_path.clone());
+                            try_execute_redirects(&tosqlite, &mut conn, pld);
+                        }
                         "categorylinks" => {
                             tosqlite.create_category_links_db(&sql_file_path, &mut conn)
                         }
@@ -477,6 +515,7 @@ async fn try_execute_pagelinks(
                     false,
                 );
                 page_links::create_indices_post_setup(conn);
+                red_links::create_indices_post_setup(conn);
 
                 // let prefix: String = tosqlite.wiki_name.chars().take(2).collect();
                 // let valid = post_validation(&out_db_path, prefix, 1).await;
@@ -488,6 +527,21 @@ async fn try_execute_pagelinks(
     }
 }
 
+/// The redirect targets are converted with the title_id_map, so WikiPage has to be inserted first
+fn try_execute_redirects(
+    tosqlite: &ToSqlite<'_>,
+    conn: &mut Connection,
+    page_links_data: &PageLinksData,
+) {
+    if let (Some(title_id_map), Some(redirect_sql_path)) = (
+        &page_links_data.title_id_map,
+        &page_links_data.redirect_sql_path,
+    ) {
+        tosqlite.create_redirect_db(redirect_sql_path, conn, title_id_map);
+        redirects::create_indices_post_setup(conn);
+    }
+}
+
 // Parallel download and disk write is heavily bottlenecked?
 pub async fn split_workload<T: Clone>(workload: &[T], num_threads: usize) -> Vec<Vec<T>> {
     assert!(num_threads > 0, "Number of thread should not be zero");
@@ -1,13 +1,18 @@
+use std::cmp::Ordering;
 use std::fs::File;
 use std::hash::Hash;
-use std::io::Write;
+use std::io::{BufWriter, Write};
+use std::ops::AddAssign;
 use std::path::Path;
 
 use fxhash::FxHashSet;
 use indicatif::MultiProgress;
+use log::info;
 use parse_mediawiki_sql::utils::Mmap;
 use parse_mediawiki_sql::{FromSqlTuple, iterate_sql_insertions};
 use rusqlite::{Connection, Row};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use crate::sqlite::load::load_sql_part_set_generic;
 use crate::utils::default_bar;
@@ -32,22 +37,207 @@ pub fn select_all<T: Eq + Hash>(
     data
 }
 
-pub fn diff(path1: impl AsRef<Path>, path2: impl AsRef<Path>) {
-    let old_data = select_all::<(u32, u32)>(&path1, "WikiLink", |row| {
-        (row.get(0).unwrap(), row.get(1).unwrap())
-    });
+/// Counts of the changes between two databases of the same wiki
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DiffSummary {
+    pub added_pages: u64,
+    pub removed_pages: u64,
+    /// Same page id, new title
+    pub renamed_pages: u64,
+    pub added_links: u64,
+    pub removed_links: u64,
+}
 
-    let new_data = select_all::<(u32, u32)>(&path1, "WikiLink", |row| {
-        (row.get(0).unwrap(), row.get(1).unwrap())
-    });
+impl AddAssign for DiffSummary {
+    fn add_assign(&mut self, other: Self) {
+        self.added_pages += other.added_pages;
+        self.removed_pages += other.removed_pages;
+        self.renamed_pages += other.renamed_pages;
+        self.added_links += other.added_links;
+        self.removed_links += other.removed_links;
+    }
+}
+
+/// A single change, one json object per line in the detailed export
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DiffEntry {
+    AddedPage {
+        page_id: u32,
+        page_title: String,
+    },
+    RemovedPage {
+        page_id: u32,
+        page_title: String,
+    },
+    RenamedPage {
+        page_id: u32,
+        old_title: String,
+        new_title: String,
+    },
+    AddedLink {
+        page_id: u32,
+        page_link: u32,
+    },
+    RemovedLink {
+        page_id: u32,
+        page_link: u32,
+    },
+}
+
+enum Merged<T> {
+    Old(T),
+    New(T),
+    Both(T, T),
+}
+
+/// Merge join of two iterators sorted by key, so neither side has to fit into memory
+fn merge_sorted<T, K: Ord>(
+    old: impl Iterator<Item = T>,
+    new: impl Iterator<Item = T>,
+    key: impl Fn(&T) -> K,
+    mut f: impl FnMut(Merged<T>),
+) {
+    let mut old = old.peekable();
+    let mut new = new.peekable();
+    loop {
+        let ord = match (old.peek(), new.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(o), Some(n)) => key(o).cmp(&key(n)),
+        };
+        match ord {
+            Ordering::Less => f(Merged::Old(old.next().unwrap())),
+            Ordering::Greater => f(Merged::New(new.next().unwrap())),
+            Ordering::Equal => f(Merged::Both(old.next().unwrap(), new.next().unwrap())),
+        }
+    }
+}
+
+/// Compares two processed databases of the same wiki (e.g. of two dump dates): added, removed and
+/// renamed pages and added and removed links. Both tables are streamed sorted by their unique
+/// indices, so memory stays constant. Every change is written to export as a json line
+pub fn diff(
+    old_conn: &Connection,
+    new_conn: &Connection,
+    mut export: Option<&mut dyn Write>,
+) -> DiffSummary {
+    let mut summary = DiffSummary::default();
+    let mut write_entry = |entry: DiffEntry| {
+        if let Some(export) = export.as_mut() {
+            serde_json::to_writer(&mut *export, &entry).unwrap();
+            writeln!(export).expect("Failed writing diff export");
+        }
+    };
+
+    let pages_query = "SELECT page_id, page_title FROM WikiPage ORDER BY page_id";
+    let mut old_stmt = old_conn.prepare(pages_query).unwrap();
+    let mut new_stmt = new_conn.prepare(pages_query).unwrap();
+    let page_row = |row: &Row| Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?));
+    let old_pages = old_stmt
+        .query_map([], page_row)
+        .unwrap()
+        .map(Result::unwrap);
+    let new_pages = new_stmt
+        .query_map([], page_row)
+        .unwrap()
+        .map(Result::unwrap);
 
-    // let symm_diff = old_data.symmetric_difference(&new_data);
-    // dbg!(&symm_diff.count());
+    merge_sorted(
+        old_pages,
+        new_pages,
+        |(page_id, _)| *page_id,
+        |merged| match merged {
+            Merged::Old((page_id, page_title)) => {
+                summary.removed_pages += 1;
+                write_entry(DiffEntry::RemovedPage {
+                    page_id,
+                    page_title,
+                });
+            }
+            Merged::New((page_id, page_title)) => {
+                summary.added_pages += 1;
+                write_entry(DiffEntry::AddedPage {
+                    page_id,
+                    page_title,
+                });
+            }
+            Merged::Both((page_id, old_title), (_, new_title)) => {
+                if old_title != new_title {
+                    summary.renamed_pages += 1;
+                    write_entry(DiffEntry::RenamedPage {
+                        page_id,
+                        old_title,
+                        new_title,
+                    });
+                }
+            }
+        },
+    );
 
-    let new = new_data.difference(&old_data);
-    dbg!(&new.count());
-    let deleted = old_data.difference(&new_data);
-    dbg!(deleted.count());
+    let links_query = "SELECT page_id, page_link FROM WikiLink ORDER BY page_id, page_link";
+    let mut old_stmt = old_conn.prepare(links_query).unwrap();
+    let mut new_stmt = new_conn.prepare(links_query).unwrap();
+    let link_row = |row: &Row| Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?));
+    let old_links = old_stmt
+        .query_map([], link_row)
+        .unwrap()
+        .map(Result::unwrap);
+    let new_links = new_stmt
+        .query_map([], link_row)
+        .unwrap()
+        .map(Result::unwrap);
+
+    merge_sorted(
+        old_links,
+        new_links,
+        |link| *link,
+        |merged| match merged {
+            Merged::Old((page_id, page_link)) => {
+                summary.removed_links += 1;
+                write_entry(DiffEntry::RemovedLink { page_id, page_link });
+            }
+            Merged::New((page_id, page_link)) => {
+                summary.added_links += 1;
+                write_entry(DiffEntry::AddedLink { page_id, page_link });
+            }
+            Merged::Both(_, _) => {}
+        },
+    );
+
+    summary
+}
+
+/// [`diff`] of two db files, writing the detailed changes to export_path if given
+pub fn diff_db_files(
+    old_db_path: impl AsRef<Path>,
+    new_db_path: impl AsRef<Path>,
+    export_path: Option<&Path>,
+) -> DiffSummary {
+    let old_conn = Connection::open(&old_db_path).unwrap();
+    let new_conn = Connection::open(&new_db_path).unwrap();
+
+    let mut export = export_path.map(|path| {
+        BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|e| panic!("Failed creating diff export {path:?}: {e}")),
+        )
+    });
+    let summary = diff(
+        &old_conn,
+        &new_conn,
+        export.as_mut().map(|w| w as &mut dyn Write),
+    );
+    if let Some(mut export) = export {
+        export.flush().expect("Failed writing diff export");
+    }
+    info!(
+        "Diff of {:?} to {:?}: {summary:?}",
+        old_db_path.as_ref(),
+        new_db_path.as_ref()
+    );
+    summary
 }
 
 pub fn diff_sqldump<'a, T: FromSqlTuple<'a> + 'a + Eq + Hash>(
@@ -90,3 +280,67 @@ pub fn diff_sqldump<'a, T: FromSqlTuple<'a> + 'a + Eq + Hash>(
     bar.finish();
     dbg!(&new_count);
 }
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::sqlite::diff::{DiffEntry, DiffSummary, diff};
+    use crate::test_utils::{graph_db, insert_pages};
+
+    fn wiki_db(pages: &[(u32, &str)], links: &[(u32, u32)]) -> Connection {
+        let conn = graph_db(links);
+        let pages: Vec<_> = pages
+            .iter()
+            .map(|(page_id, page_title)| (*page_id, *page_title, false))
+            .collect();
+        insert_pages(&conn, &pages);
+        conn
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = wiki_db(&[(1, "A"), (2, "B"), (3, "C")], &[(1, 2), (1, 3), (2, 3)]);
+        let new = wiki_db(
+            &[(1, "A"), (3, "C_new"), (4, "D")],
+            &[(1, 3), (1, 4), (3, 1), (4, 1)],
+        );
+
+        let mut export = vec![];
+        let summary = diff(&old, &new, Some(&mut export));
+        assert_eq!(
+            summary,
+            DiffSummary {
+                added_pages: 1,
+                removed_pages: 1,
+                renamed_pages: 1,
+                added_links: 3,
+                removed_links: 2,
+            }
+        );
+
+        let entries: Vec<DiffEntry> = String::from_utf8(export)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 8);
+        assert_eq!(
+            entries[1],
+            DiffEntry::RenamedPage {
+                page_id: 3,
+                old_title: "C".to_string(),
+                new_title: "C_new".to_string(),
+            }
+        );
+        assert_eq!(
+            entries[3],
+            DiffEntry::RemovedLink {
+                page_id: 1,
+                page_link: 2,
+            }
+        );
+
+        assert_eq!(diff(&new, &new, None), DiffSummary::default());
+    }
+}
@@ -0,0 +1,67 @@
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::calc::distance_profile::DistanceProfile;
+use crate::sqlite::paths::SpDirection;
+use crate::sqlite::table_exists;
+
+/// Stores the profile in the wiki db, replacing an existing one of the same page and direction
+pub fn save_distance_profile(conn: &Connection, profile: &DistanceProfile) {
+    conn.execute(
+        "CREATE TABLE if not exists DistanceProfile (
+            page_id INTEGER NOT NULL,
+            direction INTEGER NOT NULL,
+            num_reachable INTEGER NOT NULL,
+            deepest_id INTEGER NOT NULL,
+            max_depth INTEGER NOT NULL,
+            depth_histogram TEXT NOT NULL,
+            PRIMARY KEY (page_id, direction)
+        )",
+        (),
+    )
+    .expect("Failed creating table 'DistanceProfile'");
+
+    conn.execute(
+        "INSERT OR REPLACE INTO DistanceProfile
+            (page_id, direction, num_reachable, deepest_id, max_depth, depth_histogram)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        (
+            profile.page_id.0,
+            profile.direction as u8,
+            profile.num_reachable,
+            profile.deepest_id.0,
+            profile.max_depth,
+            serde_json::to_string(&profile.depth_histogram).unwrap(),
+        ),
+    )
+    .unwrap();
+}
+
+/// Profile stored by [`save_distance_profile`], None if there is none
+pub fn load_distance_profile(
+    conn: &Connection,
+    page_id: PageId,
+    direction: SpDirection,
+) -> Option<DistanceProfile> {
+    if !table_exists(conn, "DistanceProfile") {
+        return None;
+    }
+
+    conn.query_row(
+        "SELECT num_reachable, deepest_id, max_depth, depth_histogram FROM DistanceProfile
+        WHERE page_id = ?1 AND direction = ?2",
+        (page_id.0, direction as u8),
+        |row| {
+            Ok(DistanceProfile {
+                page_id,
+                direction,
+                num_reachable: row.get(0)?,
+                deepest_id: PageId(row.get(1)?),
+                max_depth: row.get(2)?,
+                depth_histogram: serde_json::from_str(&row.get::<_, String>(3)?).unwrap(),
+            })
+        },
+    )
+    .optional()
+    .unwrap()
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+use std::time::Instant;
+
+use log::info;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+
+use crate::calc::landmarks::{LandmarkOracle, UNKNOWN_DISTANCE};
+use crate::sqlite::table_exists;
+
+/// Stores the landmark distances in the wiki db, replacing previously stored ones.
+/// Pages no landmark reaches and that reach no landmark are not stored.
+pub fn save_landmarks(path: impl AsRef<Path>, oracle: &LandmarkOracle) {
+    let t1 = Instant::now();
+    let mut conn = Connection::open(path).expect("Failed creating database connection");
+
+    conn.execute_batch(
+        "DROP TABLE IF EXISTS Landmark;
+        DROP TABLE IF EXISTS LandmarkDistance;
+        CREATE TABLE Landmark (
+            landmark_index INTEGER PRIMARY KEY,
+            page_id INTEGER NOT NULL
+        );
+        CREATE TABLE LandmarkDistance (
+            page_id INTEGER PRIMARY KEY,
+            dist_from BLOB NOT NULL,
+            dist_to BLOB NOT NULL
+        );",
+    )
+    .expect("Failed creating landmark tables");
+
+    let tx = conn.transaction().unwrap();
+    {
+        let mut stmt = tx
+            .prepare_cached("INSERT INTO Landmark (landmark_index, page_id) VALUES (?1, ?2)")
+            .unwrap();
+        for (i, landmark) in oracle.landmarks.iter().enumerate() {
+            stmt.execute((i, landmark.0)).unwrap();
+        }
+
+        let mut stmt = tx
+            .prepare_cached(
+                "INSERT INTO LandmarkDistance (page_id, dist_from, dist_to) VALUES (?1, ?2, ?3)",
+            )
+            .unwrap();
+        for page_id in oracle.pages() {
+            let (dist_from, dist_to) = oracle.page_distances(page_id);
+            let is_unknown = |dists: &[u8]| dists.iter().all(|d| *d == UNKNOWN_DISTANCE);
+            if is_unknown(dist_from) && is_unknown(dist_to) {
+                continue;
+            }
+            stmt.execute((page_id.0, dist_from, dist_to)).unwrap();
+        }
+    }
+    tx.commit().expect("Failed committing landmark distances");
+
+    info!("Saved landmark distances in {:?}", t1.elapsed());
+}
+
+/// Loads the landmark distances stored by [`save_landmarks`], None if there are none
+pub fn load_landmarks(path: impl AsRef<Path>) -> Option<LandmarkOracle> {
+    let conn = Connection::open(path).expect("Failed creating database connection");
+    if !table_exists(&conn, "Landmark") || !table_exists(&conn, "LandmarkDistance") {
+        return None;
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT page_id FROM Landmark ORDER BY landmark_index")
+        .unwrap();
+    let landmarks: Vec<PageId> = stmt
+        .query_map([], |row| Ok(PageId(row.get(0)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+
+    let mut oracle = LandmarkOracle::new(landmarks);
+
+    let mut stmt = conn
+        .prepare("SELECT page_id, dist_from, dist_to FROM LandmarkDistance")
+        .unwrap();
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                PageId(row.get(0)?),
+                row.get::<_, Vec<u8>>(1)?,
+                row.get::<_, Vec<u8>>(2)?,
+            ))
+        })
+        .unwrap();
+
+    for row in rows {
+        let (page_id, dist_from, dist_to) = row.unwrap();
+        oracle.set_page_distances(page_id, &dist_from, &dist_to);
+    }
+
+    Some(oracle)
+}
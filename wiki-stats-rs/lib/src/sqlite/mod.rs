@@ -4,9 +4,15 @@ use std::path::{Path, PathBuf};
 
 mod category_links;
 pub mod diff;
+pub mod distance_profile;
+pub mod landmarks;
 pub mod load;
 pub mod page_links;
+pub mod page_props;
 pub mod paths;
+pub mod red_links;
+pub mod redirects;
+pub mod similarity;
 pub mod title_id_conv;
 pub mod to_sqlite;
 pub mod wiki;
@@ -19,12 +25,20 @@ pub static PAGELINKS_DB: &str =
     "/run/media/gareth/7FD71CF32A89EF6A/dev/wiki/sqlite/de_database.sqlite";
 
 pub static DATABASE_SUFFIX: &str = "_database.sqlite";
+pub static SP_DATABASE_SUFFIX: &str = "_sp_database.sqlite";
 
 pub fn join_db_wiki_path(db_path: impl Into<PathBuf>, wiki_name: impl AsRef<str>) -> PathBuf {
     db_path
         .into()
         .join(format!("{}{DATABASE_SUFFIX}", wiki_name.as_ref()))
 }
+/// Path of the precomputed shortest paths db of the wiki, see [`paths`]
+pub fn join_db_sp_wiki_path(db_path: impl Into<PathBuf>, wiki_name: impl AsRef<str>) -> PathBuf {
+    db_path
+        .into()
+        .join(format!("{}{SP_DATABASE_SUFFIX}", wiki_name.as_ref()))
+}
+
 // todo: refactor to pathbuf / path?
 pub fn db_wiki_path(wiki_name: impl AsRef<str> + Display) -> String {
     let db_wikis_dir: String =
@@ -38,7 +52,20 @@ pub fn db_wiki_path(wiki_name: impl AsRef<str> + Display) -> String {
 pub fn db_sp_wiki_path(wiki_name: impl AsRef<str> + Display) -> String {
     let db_wikis_dir: String =
         std::env::var("DB_WIKIS_DIR").expect("Please set DB_WIKIS_DIR to db wiki location");
-    format!("{db_wikis_dir}/{wiki_name}_sp_database.sqlite")
+    join_db_sp_wiki_path(db_wikis_dir, wiki_name)
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+pub fn table_exists(conn: &rusqlite::Connection, table_name: &str) -> bool {
+    conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table_name],
+        |row| row.get::<_, u32>(0),
+    )
+    .map(|count| count > 0)
+    .unwrap_or(false)
 }
 
 pub fn get_all_database_files(dir: impl AsRef<Path>) -> std::io::Result<Vec<String>> {
@@ -50,7 +77,10 @@ pub fn get_all_database_files(dir: impl AsRef<Path>) -> std::io::Result<Vec<Stri
         if path.is_file() {
             if let Some(file_name) = path.file_name() {
                 if let Some(file_name_str) = file_name.to_str() {
-                    if file_name_str.ends_with(DATABASE_SUFFIX) {
+                    // the sp databases share the suffix
+                    if file_name_str.ends_with(DATABASE_SUFFIX)
+                        && !file_name_str.ends_with(SP_DATABASE_SUFFIX)
+                    {
                         // only return the wikiname
                         matching_files.push(
                             file_name_str
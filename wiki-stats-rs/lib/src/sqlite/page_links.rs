@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::path::Path;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 use std::time::Instant;
 
 use fxhash::{FxBuildHasher, FxHashMap, FxHashSet};
@@ -12,7 +12,7 @@ use parse_mediawiki_sql::field_types::PageId;
 use rusqlite::{Connection, Transaction};
 
 use crate::sqlite::title_id_conv::load_wiki_pages;
-use crate::stats::queries::select_link_count_groupby;
+use crate::stats::queries::{RankingFilter, select_link_count_groupby};
 use crate::utils::{ProgressBarBuilder, default_bar};
 use crate::{DBCache, sqlite};
 
@@ -292,15 +292,22 @@ pub fn load_link_to_map_db_wiki(
 /// ### Args:
 /// - If num_load_opt is None, load all entries
 /// - If num_load_opt is Some(n), load the links of the n pages with the most links
+///   (or the n most linked pages if incoming is true)
 /// - If num_load_opt is Some(0), load no entries
 pub fn get_cache(path: impl AsRef<Path>, num_load_opt: Option<usize>, incoming: bool) -> DBCache {
+    let group_by = if incoming {
+        "WikiLink.page_link"
+    } else {
+        "WikiLink.page_id"
+    };
+
     let cached_entries: Vec<PageId> = match num_load_opt {
         None => vec![],
         Some(num_load) => {
             if num_load == 0 {
                 return FxHashMap::default();
             } else {
-                select_link_count_groupby(num_load, &path, "WikiLink.page_id")
+                select_link_count_groupby(num_load, &path, group_by, &RankingFilter::default())
                     .into_iter()
                     .map(|(pid, _)| PageId(pid as u32))
                     .collect()
@@ -312,6 +319,65 @@ pub fn get_cache(path: impl AsRef<Path>, num_load_opt: Option<usize>, incoming:
     load_link_to_map_db_limit(path, cached_entries, incoming)
 }
 
+/// Size of the graph of a link cache, needed by [`crate::calc::bfs::bfs_parallel`].
+/// Computing it is a pass over all links, so it is done once per cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphSize {
+    /// Largest page id with a link (in either direction)
+    pub max_id: u32,
+    pub num_edges: u64,
+}
+
+impl GraphSize {
+    /// The size is the same for the outgoing and the incoming cache of a wiki
+    pub fn of(cache: &DBCache) -> Self {
+        let mut size = GraphSize::default();
+        for (page_id, links) in cache {
+            size.num_edges += links.len() as u64;
+            for id in std::iter::once(page_id).chain(links) {
+                size.max_id = size.max_id.max(id.0);
+            }
+        }
+        size
+    }
+}
+
+/// Outgoing and incoming link caches of a single wiki.
+/// Cheap to clone, so it can be moved into long running streams.
+#[derive(Clone, Default)]
+pub struct LinkCaches {
+    pub outgoing: Arc<DBCache>,
+    pub incoming: Arc<DBCache>,
+    /// Size of the cached graph
+    pub size: GraphSize,
+}
+
+impl LinkCaches {
+    pub fn new(outgoing: DBCache, incoming: DBCache) -> Self {
+        Self {
+            size: GraphSize::of(&outgoing),
+            outgoing: Arc::new(outgoing),
+            incoming: Arc::new(incoming),
+        }
+    }
+
+    /// Loads both directions, see [`get_cache`] for the meaning of num_load_opt
+    pub fn load(path: impl AsRef<Path>, num_load_opt: Option<usize>) -> Self {
+        Self::new(
+            get_cache(&path, num_load_opt, false),
+            get_cache(&path, num_load_opt, true),
+        )
+    }
+
+    pub fn get(&self, incoming: bool) -> &DBCache {
+        if incoming {
+            &self.incoming
+        } else {
+            &self.outgoing
+        }
+    }
+}
+
 /// Returns a map of pageid to all the pageids it links to (or links from it)
 /// ### Args:
 /// - path: Database path
@@ -435,6 +501,41 @@ pub fn get_links_of_ids(
     links
 }
 
+/// Like [`get_links_of_ids`], but serves the ids found in cache from memory and only queries the
+/// remaining ids from the database (in a single batch).
+/// Returns the (page_id, page_link) pairs and the number of cache hits and misses
+pub fn get_links_of_ids_cached(
+    conn: &Connection,
+    ids: Vec<PageId>,
+    cache: &DBCache,
+    incoming: bool,
+) -> (Vec<(PageId, PageId)>, u64, u64) {
+    let mut links = vec![];
+    let mut misses = vec![];
+    let mut num_hits = 0;
+
+    for id in ids {
+        match cache.get(&id) {
+            Some(cached_links) => {
+                num_hits += 1;
+                if incoming {
+                    links.extend(cached_links.iter().map(|source| (*source, id)));
+                } else {
+                    links.extend(cached_links.iter().map(|target| (id, *target)));
+                }
+            }
+            None => misses.push(id),
+        }
+    }
+
+    let num_misses = misses.len() as u64;
+    if !misses.is_empty() {
+        links.extend(get_links_of_ids(conn, misses, incoming));
+    }
+
+    (links, num_hits, num_misses)
+}
+
 // dewiki
 // 146_358_594
 // redirects:  1_862_077
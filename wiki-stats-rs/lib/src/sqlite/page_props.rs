@@ -0,0 +1,194 @@
+use fxhash::FxHashSet;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::sqlite::table_exists;
+
+/// Properties of the page_props dump that are stored, the others are skipped
+pub const STORED_PROPS: [&str; 2] = [DISAMBIGUATION, WIKIBASE_ITEM];
+
+/// Set (with an empty value) on disambiguation pages
+pub const DISAMBIGUATION: &str = "disambiguation";
+/// The Wikidata item id of the page, e.g. Q64
+pub const WIKIBASE_ITEM: &str = "wikibase_item";
+
+/// Page properties of the page_props dump, one row per page and property
+pub fn db_setup(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE if not exists WikiPageProps (
+            page_id INTEGER,
+            prop_name TEXT,
+            value TEXT
+        )",
+        (),
+    )
+    .expect("Failed creating table");
+}
+
+pub fn create_unique_index(conn: &Connection) {
+    conn.execute(
+        "CREATE UNIQUE INDEX if not exists WikiPageProps_unique_index ON
+           WikiPageProps(page_id, prop_name)",
+        (),
+    )
+    .expect("Failed creating unique index");
+}
+
+/// Index to find pages by their Wikidata item
+pub fn create_indices_post_setup(conn: &Connection) {
+    conn.execute(
+        "CREATE INDEX if not exists idx_page_props_value ON WikiPageProps(prop_name, value);",
+        (),
+    )
+    .expect("Failed creating index");
+}
+
+/// The dump has the properties of all namespaces, only the ones of pages in WikiPage are kept
+pub fn remove_props_of_missing_pages(conn: &Connection) -> usize {
+    conn.execute(
+        "DELETE FROM WikiPageProps WHERE page_id NOT IN (SELECT page_id FROM WikiPage)",
+        (),
+    )
+    .expect("Failed removing page props")
+}
+
+/// Databases created before page_props was ingested don't have the table
+pub fn has_page_props(conn: &Connection) -> bool {
+    table_exists(conn, "WikiPageProps")
+}
+
+/// Sql condition on a page_id column that leaves out disambiguation pages. None if the database
+/// has no page props
+pub fn not_disambiguation_condition(conn: &Connection, page_id_column: &str) -> Option<String> {
+    has_page_props(conn).then(|| {
+        format!(
+            "{page_id_column} NOT IN \
+            (SELECT page_id FROM WikiPageProps WHERE prop_name = '{DISAMBIGUATION}')"
+        )
+    })
+}
+
+pub fn is_disambiguation(conn: &Connection, page_id: PageId) -> bool {
+    has_page_props(conn)
+        && conn
+            .query_row(
+                "SELECT 1 FROM WikiPageProps WHERE page_id = ?1 AND prop_name = ?2",
+                (page_id.0, DISAMBIGUATION),
+                |_| Ok(()),
+            )
+            .optional()
+            .unwrap()
+            .is_some()
+}
+
+/// Ids of all disambiguation pages, empty if the database has no page props
+pub fn load_disambiguation_pages(conn: &Connection) -> FxHashSet<PageId> {
+    if !has_page_props(conn) {
+        return FxHashSet::default();
+    }
+    let mut stmt = conn
+        .prepare("SELECT page_id FROM WikiPageProps WHERE prop_name = ?1")
+        .unwrap();
+    stmt.query_map([DISAMBIGUATION], |row| Ok(PageId(row.get(0)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+/// None if the database has no page props
+pub fn num_disambiguation_pages(conn: &Connection) -> Option<u64> {
+    has_page_props(conn).then(|| {
+        conn.query_row(
+            "SELECT COUNT(*) FROM WikiPageProps WHERE prop_name = ?1
+             AND page_id IN (SELECT page_id FROM WikiPage)",
+            [DISAMBIGUATION],
+            |row| row.get(0),
+        )
+        .unwrap()
+    })
+}
+
+/// The Wikidata item id of the page, e.g. Q64 for Berlin
+pub fn wikibase_item(conn: &Connection, page_id: PageId) -> Option<String> {
+    if !has_page_props(conn) {
+        return None;
+    }
+    conn.query_row(
+        "SELECT value FROM WikiPageProps WHERE page_id = ?1 AND prop_name = ?2",
+        (page_id.0, WIKIBASE_ITEM),
+        |row| row.get(0),
+    )
+    .optional()
+    .unwrap()
+}
+
+/// The page of the wiki with the Wikidata item, to join pages of different wikis
+pub fn page_by_wikibase_item(conn: &Connection, item: &str) -> Option<PageId> {
+    if !has_page_props(conn) {
+        return None;
+    }
+    conn.query_row(
+        "SELECT page_id FROM WikiPageProps WHERE prop_name = ?1 AND value = ?2",
+        (WIKIBASE_ITEM, item),
+        |row| Ok(PageId(row.get(0)?)),
+    )
+    .optional()
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+    use rusqlite::Connection;
+
+    use crate::sqlite::page_props::{
+        db_setup, is_disambiguation, load_disambiguation_pages, num_disambiguation_pages,
+        page_by_wikibase_item, remove_props_of_missing_pages, wikibase_item,
+    };
+    use crate::sqlite::title_id_conv;
+
+    #[test]
+    fn test_page_props() {
+        let conn = Connection::open_in_memory().unwrap();
+        title_id_conv::db_setup(&conn);
+        assert!(!is_disambiguation(&conn, PageId(2)));
+        assert_eq!(num_disambiguation_pages(&conn), None);
+
+        db_setup(&conn);
+        for (page_id, title) in [(1, "Berlin"), (2, "Berlin_(disambiguation)")] {
+            conn.execute(
+                "INSERT INTO WikiPage (page_id, page_title, is_redirect) VALUES (?1, ?2, 0)",
+                (page_id, title),
+            )
+            .unwrap();
+        }
+        // 3 is a disambiguation page of another namespace
+        let props = [
+            (1, "wikibase_item", "Q64"),
+            (2, "disambiguation", ""),
+            (2, "wikibase_item", "Q1000"),
+            (3, "disambiguation", ""),
+        ];
+        for (page_id, prop_name, value) in props {
+            conn.execute(
+                "INSERT INTO WikiPageProps (page_id, prop_name, value) VALUES (?1, ?2, ?3)",
+                (page_id, prop_name, value),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(num_disambiguation_pages(&conn), Some(1));
+        assert_eq!(remove_props_of_missing_pages(&conn), 1);
+        assert!(is_disambiguation(&conn, PageId(2)));
+        assert!(!is_disambiguation(&conn, PageId(1)));
+        assert_eq!(
+            load_disambiguation_pages(&conn)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![PageId(2)]
+        );
+        assert_eq!(wikibase_item(&conn, PageId(1)), Some("Q64".to_string()));
+        assert_eq!(page_by_wikibase_item(&conn, "Q1000"), Some(PageId(2)));
+        assert_eq!(page_by_wikibase_item(&conn, "Q1"), None);
+    }
+}
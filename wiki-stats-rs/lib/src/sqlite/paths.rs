@@ -1,68 +1,92 @@
-use std::collections::VecDeque;
-use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
-use fxhash::{FxHashMap, FxHashSet};
-use itertools::Itertools;
+use fxhash::FxHashSet;
 use parse_mediawiki_sql::field_types::PageId;
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 
-use crate::sqlite::db_wiki_path;
-use crate::sqlite::title_id_conv::page_id_to_title;
-use crate::{DepthHistogram, PrevMap};
+use crate::DepthHistogram;
+use crate::calc::bfs::BfsResult;
 
-// static PATHS_DB_LOCATION: &str = "/run/media/gareth/7FD71CF32A89EF6A/dev/paths.db";
-// static PATHS_DB_LOCATION: &str = "/run/media/gareth/7FD71CF32A89EF6A/dev/de_paths.db";
+/// Direction of a stored bfs tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SpDirection {
+    /// Shortest paths from the source to every page (bfs over outgoing links)
+    FromSource = 0,
+    /// Shortest paths from every page to the source (bfs over incoming links)
+    ToSource = 1,
+}
 
-// static PATHS_DB_LOCATION: &str = "/run/media/gareth/7FD71CF32A89EF6A/dev/de_paths.db";
+impl SpDirection {
+    fn from_db(value: u8) -> Self {
+        match value {
+            0 => SpDirection::FromSource,
+            1 => SpDirection::ToSource,
+            _ => panic!("Invalid sp direction {value}"),
+        }
+    }
+}
 
 pub(crate) fn create_db(path: impl AsRef<Path>) {
     let conn = Connection::open(path).expect("Failed creating database connection");
 
-    // conn.execute(
-    //     "CREATE TABLE if not exists SP_LinkData (
-    //         id integer primary key,
-    //         previous_id INTEGER NOT NULL,
-    //         page_id INTEGER NOT NULL
-    //     )",
-    //     (),
-    // ).expect("Failed creating table 'SP_LinkData'");
-    //
-    // conn.execute(
-    //     "CREATE TABLE if not exists SP_Link (
-    //         source_id INTEGER NOT NULL,
-    //         data_id integer not null
-    //     )",
-    //     (),
-    // ).expect("Failed creating table 'SP_Link'");
-
+    // one row per page and tree: previous_id is the next page towards the source
     conn.execute(
         "CREATE TABLE if not exists SP_Link (
             source_id INTEGER NOT NULL,
-             previous_id INTEGER NOT NULL,
-            page_id INTEGER NOT NULL
-        )",
+            direction INTEGER NOT NULL,
+            page_id INTEGER NOT NULL,
+            previous_id INTEGER NOT NULL,
+            PRIMARY KEY (source_id, direction, page_id)
+        ) WITHOUT ROWID",
         (),
     )
     .expect("Failed creating table 'SP_Link'");
 
-    conn.execute("PRAGMA synchronous = OFF", ()).unwrap();
-}
-
-pub fn create_index(conn: &Connection) {
     conn.execute(
-        "CREATE INDEX SP_Link_pid_sid ON SP_Link(page_id, source_id);",
+        "CREATE TABLE if not exists SP_Source (
+            source_id INTEGER NOT NULL,
+            direction INTEGER NOT NULL,
+            num_visited INTEGER NOT NULL,
+            deepest_id INTEGER NOT NULL,
+            len_deepest_sp INTEGER NOT NULL,
+            depth_histogram TEXT NOT NULL,
+            dump_date TEXT,
+            PRIMARY KEY (source_id, direction)
+        )",
         (),
     )
-    .expect("Failed creating INDEX SP_Link_pid_sid");
+    .expect("Failed creating table 'SP_Source'");
+
+    // trees stored before the dump date was recorded, they don't match any dump
+    if conn
+        .prepare("SELECT dump_date FROM SP_Source LIMIT 0")
+        .is_err()
+    {
+        conn.execute("ALTER TABLE SP_Source ADD COLUMN dump_date TEXT", ())
+            .expect("Failed adding dump_date to 'SP_Source'");
+    }
+
+    conn.execute("PRAGMA synchronous = OFF", ()).unwrap();
 }
 
-pub fn precalced_path_ids(path: impl AsRef<Path>) -> FxHashSet<PageId> {
+/// Sources whose tree in this direction is stored for the dump date of the wiki db
+/// (see [`crate::sqlite::wiki::dump_date`]). Trees of other dumps are stale, as the links changed
+pub fn precalced_path_ids(
+    path: impl AsRef<Path>,
+    direction: SpDirection,
+    dump_date: Option<&str>,
+) -> FxHashSet<PageId> {
+    create_db(&path);
+
     let conn = Connection::open(path).expect("Failed creating database connection");
-    let mut stmt = conn.prepare("SELECT source_id FROM SP_Link").unwrap();
+    let mut stmt = conn
+        .prepare("SELECT source_id FROM SP_Source WHERE direction = ?1 AND dump_date IS ?2")
+        .unwrap();
 
-    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    let rows = stmt
+        .query_map((direction as u8, dump_date), |row| row.get(0))
+        .unwrap();
 
     let mut ids = FxHashSet::default();
     for id in rows {
@@ -72,169 +96,207 @@ pub fn precalced_path_ids(path: impl AsRef<Path>) -> FxHashSet<PageId> {
     ids
 }
 
-pub fn build_sp(start_link_id: &PageId, end_link_id: &PageId) {
-    let p = "./test-paths.json";
-
-    let existing_json_data = fs::read_to_string(&p).unwrap();
-    let data: Data = serde_json::from_str(&existing_json_data).unwrap();
-    let mut end_ids = vec![];
-    let possible_indices = data.source_ids.get(start_link_id).unwrap();
-    // dbg!(&possible_indices);
-    let mut to_find = end_link_id.0;
-    let mut path = vec![end_link_id.0];
-    let conn = Connection::open(db_wiki_path("dewiki")).unwrap();
-
-    'outer: loop {
-        println!("looping");
-        for (i, (pid, prev)) in data.data.iter().enumerate() {
-            if pid == &to_find && possible_indices.contains(&i) {
-                end_ids.push((pid, prev, i));
-                path.push(*prev);
-                to_find = *prev;
-                dbg!(page_id_to_title(&PageId(*prev), &conn).unwrap().0);
-                if prev == &start_link_id.0 {
-                    print!("found");
-                    break 'outer;
-                }
-            }
-        }
+/// Shortest path between source and page_id, using the tree stored for the source.
+/// For [`SpDirection::FromSource`] the path goes source -> page_id,
+/// for [`SpDirection::ToSource`] page_id -> source.
+/// Returns None if page_id is not reachable (or the tree is not stored)
+pub fn build_sp(
+    conn: &Connection,
+    source_id: PageId,
+    direction: SpDirection,
+    page_id: PageId,
+) -> Option<Vec<PageId>> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT previous_id FROM SP_Link WHERE source_id = ?1 AND direction = ?2 AND page_id = ?3",
+        )
+        .unwrap();
+
+    let mut path = vec![page_id];
+    let mut current = page_id;
+    while current != source_id {
+        let previous: u32 = stmt
+            .query_row((source_id.0, direction as u8, current.0), |row| row.get(0))
+            .optional()
+            .unwrap()?;
+        current = PageId(previous);
+        path.push(current);
     }
 
-    // dbg!(&path.iter()
-    //     .map(|pid| page_id_to_title(PageId(*pid), &conn).unwrap().0).collect::<Vec<String>>());
-    // dbg!(path);
-    dbg!(&end_ids);
-}
-
-#[derive(Serialize, Debug, Deserialize)]
-struct Data {
-    data: Vec<(u32, u32)>,
-    source_ids: FxHashMap<PageId, FxHashSet<usize>>,
-}
-
-pub fn save_shortest_paths_json(
-    path: impl AsRef<Path>,
-    prev_map: &PrevMap,
-    link_set: &mut FxHashMap<(PageId, PageId, PageId), usize>,
-    start_link_id: &PageId,
-) {
-    let p = "./test-paths.json";
-    let mut data: Data;
-
-    if PathBuf::from(&p).exists() {
-        let existing_json_data = fs::read_to_string(&p).unwrap();
-        data = serde_json::from_str(&existing_json_data).unwrap();
-    } else {
-        data = Data {
-            data: Vec::with_capacity(link_set.len()),
-            source_ids: FxHashMap::default(),
-        }
-    }
-    // dbg!(&data);
-
-    for (pid, previous_id) in prev_map {
-        link_set.insert((*start_link_id, *pid, *previous_id), link_set.len());
-    }
-
-    data.data = Vec::with_capacity(link_set.len());
-    for ((source_id, pid, prev), idx) in link_set.iter().sorted_by_key(|x| x.1) {
-        data.data.push((pid.0, prev.0));
-    }
-
-    // let mut source_ids: FxHashMap<PageId, Vec<usize>> = FxHashMap::default();
-
-    for (pid, previous_id) in prev_map {
-        let data_idx = *link_set.get(&(*start_link_id, *pid, *previous_id)).unwrap();
-        // dbg!(&data_idx);
-        data.source_ids
-            .entry(*start_link_id)
-            .or_default()
-            .insert(data_idx);
+    if direction == SpDirection::FromSource {
+        path.reverse();
     }
-
-    // let json = json!({
-    //     "data": data,
-    //    "source_ids": source_ids,
-    // });
-    let json = serde_json::to_string_pretty(&data).unwrap();
-
-    fs::write(&p, json).unwrap();
+    Some(path)
 }
 
+/// Stores the bfs tree (prev_map) and stats of the source, replacing an existing one.
+/// dump_date is the one of the wiki db the tree was computed from
 pub fn save_shortest_paths(
     path: impl AsRef<Path>,
-    prev_map: &PrevMap,
-    link_set: &mut FxHashMap<(PageId, PageId, PageId), usize>,
-    start_link_id: &PageId,
+    source_id: PageId,
+    direction: SpDirection,
+    dump_date: Option<&str>,
+    result: &BfsResult,
 ) {
     create_db(&path);
 
     let mut conn = Connection::open(path).expect("Failed creating database connection");
     let tx = conn.transaction().unwrap();
 
-    // {
-    //     let mut stmt = tx.prepare_cached(
-    //         "INSERT OR IGNORE INTO SP_LinkData (id, previous_id, page_id) VALUES (?1, ?2, ?3)").unwrap();
-    //
-    //     // dbg!(&prev_map.len());
-    //     for (pid, previous_id) in prev_map {
-    //         if link_set.contains_key(&(*pid, *previous_id)) {
-    //             continue;
-    //         }
-    //
-    //         link_set.insert((*pid, *previous_id), link_set.len());
-    //         // dbg!(&pid);
-    //         let previous_id = previous_id.0;
-    //         let page_id = pid.0;
-    //         stmt.execute((link_set.len() - 1, previous_id, page_id)).unwrap();
-    //     }
-    // }
-
-    // {
-    //     let mut stmt = tx.prepare_cached(
-    //         "INSERT INTO SP_Link (source_id, data_id) VALUES (?1, ?2)").unwrap();
-    //
-    //     for (pid, previous_id) in prev_map {
-    //         stmt.execute((start_link_id.0, link_set.get(&(*pid, *previous_id)).unwrap())).unwrap();
-    //     }
-    // }
+    tx.execute(
+        "DELETE FROM SP_Link WHERE source_id = ?1 AND direction = ?2",
+        (source_id.0, direction as u8),
+    )
+    .unwrap();
 
     {
         let mut stmt = tx
             .prepare_cached(
-                "INSERT INTO SP_Link (source_id, previous_id, page_id) VALUES (?1, ?2, ?3)",
+                "INSERT INTO SP_Link (source_id, direction, page_id, previous_id) VALUES (?1, ?2, ?3, ?4)",
             )
             .unwrap();
 
-        for (pid, previous_id) in prev_map {
-            stmt.execute((start_link_id.0, previous_id.0, pid.0))
+        for (pid, previous_id) in &result.prev_map {
+            stmt.execute((source_id.0, direction as u8, pid.0, previous_id.0))
                 .unwrap();
         }
     }
 
+    tx.execute(
+        "INSERT OR REPLACE INTO SP_Source
+            (source_id, direction, num_visited, deepest_id, len_deepest_sp, depth_histogram, dump_date)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        (
+            source_id.0,
+            direction as u8,
+            result.num_visited,
+            result.deepest_id.0,
+            result.len_deepest_sp,
+            serde_json::to_string(&result.depth_histogram).unwrap(),
+            dump_date,
+        ),
+    )
+    .unwrap();
+
     tx.commit().unwrap();
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SPStat {
-    pub longest_path: VecDeque<String>,
+    pub source_id: PageId,
+    pub direction: SpDirection,
     pub num_visited: u32,
+    /// the page furthest away from (or to) the source
+    pub deepest_id: PageId,
+    pub len_deepest_sp: u32,
     pub depth_histogram: DepthHistogram,
 }
 
-pub fn save_stats(wiki_name: String, page_title: String, stat: SPStat) {
-    let path = format!("./sp-stats.json");
-    type WikiName = String;
-    type PageTitle = String;
-    let mut data: FxHashMap<WikiName, FxHashMap<PageTitle, SPStat>> = FxHashMap::default();
+pub fn load_sp_stats(path: impl AsRef<Path>) -> Vec<SPStat> {
+    let conn = Connection::open(path).expect("Failed creating database connection");
+    let mut stmt = conn
+        .prepare(
+            "SELECT source_id, direction, num_visited, deepest_id, len_deepest_sp, depth_histogram
+            FROM SP_Source",
+        )
+        .unwrap();
+
+    stmt.query_map([], |row| {
+        Ok(SPStat {
+            source_id: PageId(row.get(0)?),
+            direction: SpDirection::from_db(row.get(1)?),
+            num_visited: row.get(2)?,
+            deepest_id: PageId(row.get(3)?),
+            len_deepest_sp: row.get(4)?,
+            depth_histogram: serde_json::from_str(&row.get::<_, String>(5)?).unwrap(),
+        })
+    })
+    .unwrap()
+    .map(|row| row.unwrap())
+    .collect()
+}
 
-    if PathBuf::from(&path).exists() {
-        let existing_json_data = fs::read_to_string(&path).unwrap();
-        data = serde_json::from_str(&existing_json_data).unwrap();
-    }
+#[cfg(test)]
+mod tests {
+    use fxhash::FxHashMap;
+    use parse_mediawiki_sql::field_types::PageId;
+    use rusqlite::Connection;
+
+    use crate::DBCache;
+    use crate::calc::bfs::bfs_parallel;
+    use crate::sqlite::page_links::GraphSize;
+    use crate::sqlite::paths::{
+        SpDirection, build_sp, load_sp_stats, precalced_path_ids, save_shortest_paths,
+    };
+
+    #[test]
+    fn test_stored_shortest_paths() {
+        // 1 -> 2 -> 3 -> 4, 1 -> 5 -> 4, 6 -> 1 (6 is not reachable from 1)
+        let links = [(1, 2), (2, 3), (3, 4), (1, 5), (5, 4), (6, 1)];
+        let mut outgoing: DBCache = FxHashMap::default();
+        let mut incoming: DBCache = FxHashMap::default();
+        for (from, to) in links {
+            outgoing.entry(PageId(from)).or_default().push(PageId(to));
+            incoming.entry(PageId(to)).or_default().push(PageId(from));
+        }
 
-    data.entry(wiki_name).or_default().insert(page_title, stat);
-    let json = serde_json::to_string_pretty(&data).unwrap();
-    // dbg!(&json);
-    fs::write(&path, json).unwrap_or_else(|_| panic!("Failed writing to {}", &path));
+        let dir = tempfile::tempdir().unwrap();
+        let sp_path = dir.path().join("test_sp_database.sqlite");
+
+        let size = GraphSize::of(&outgoing);
+        let from_result = bfs_parallel(&PageId(1), None, None, &outgoing, Some(&incoming), size, 1);
+        let dump_date = Some("20240901");
+        save_shortest_paths(
+            &sp_path,
+            PageId(1),
+            SpDirection::FromSource,
+            dump_date,
+            &from_result,
+        );
+        let to_result = bfs_parallel(&PageId(4), None, None, &incoming, Some(&outgoing), size, 1);
+        save_shortest_paths(
+            &sp_path,
+            PageId(4),
+            SpDirection::ToSource,
+            dump_date,
+            &to_result,
+        );
+
+        assert_eq!(
+            precalced_path_ids(&sp_path, SpDirection::FromSource, dump_date)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![PageId(1)]
+        );
+        // trees of another dump are stale
+        assert!(precalced_path_ids(&sp_path, SpDirection::FromSource, Some("20241001")).is_empty());
+        assert!(precalced_path_ids(&sp_path, SpDirection::FromSource, None).is_empty());
+
+        let conn = Connection::open(&sp_path).unwrap();
+        assert_eq!(
+            build_sp(&conn, PageId(1), SpDirection::FromSource, PageId(4)),
+            Some(vec![PageId(1), PageId(5), PageId(4)])
+        );
+        assert_eq!(
+            build_sp(&conn, PageId(1), SpDirection::FromSource, PageId(1)),
+            Some(vec![PageId(1)])
+        );
+        assert_eq!(
+            build_sp(&conn, PageId(1), SpDirection::FromSource, PageId(6)),
+            None
+        );
+        assert_eq!(
+            build_sp(&conn, PageId(4), SpDirection::ToSource, PageId(6)),
+            Some(vec![PageId(6), PageId(1), PageId(5), PageId(4)])
+        );
+
+        let stats = load_sp_stats(&sp_path);
+        assert_eq!(stats.len(), 2);
+        let from_stat = stats
+            .iter()
+            .find(|stat| stat.direction == SpDirection::FromSource)
+            .unwrap();
+        assert_eq!(from_stat.num_visited, 4);
+        assert_eq!(from_stat.len_deepest_sp, 2);
+    }
 }
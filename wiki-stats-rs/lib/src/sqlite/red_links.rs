@@ -0,0 +1,95 @@
+use rusqlite::Connection;
+
+/// Links to pages that don't exist (red links), by link target as there is no page id to
+/// resolve them to. The titles of the link targets are in WikiRedLinkTarget, once per target
+pub fn db_setup(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE if not exists WikiRedLink (
+            page_id INTEGER,
+            target_id INTEGER
+        )",
+        (),
+    )
+    .expect("Failed creating table");
+    conn.execute(
+        "CREATE TABLE if not exists WikiRedLinkTarget (
+            target_id INTEGER PRIMARY KEY,
+            target_title TEXT
+        )",
+        (),
+    )
+    .expect("Failed creating table");
+}
+
+pub fn create_unique_index(conn: &Connection) {
+    conn.execute(
+        "CREATE UNIQUE INDEX if not exists WikiRedLink_unique_index ON
+           WikiRedLink(page_id, target_id)",
+        (),
+    )
+    .expect("Failed creating unique index");
+}
+
+pub fn create_indices_post_setup(conn: &Connection) {
+    conn.execute(
+        "CREATE INDEX if not exists idx_red_link_target ON WikiRedLink(target_id);",
+        (),
+    )
+    .expect("Failed creating index");
+}
+
+pub fn num_red_links(conn: &Connection) -> u64 {
+    conn.query_row("SELECT COUNT(*) FROM WikiRedLink", (), |row| row.get(0))
+        .unwrap()
+}
+
+/// The missing pages with the most linking pages, descending
+pub fn most_wanted_pages(conn: &Connection, top: usize) -> Vec<(String, u64)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT WikiRedLinkTarget.target_title, COUNT(*) AS c FROM WikiRedLink
+             JOIN WikiRedLinkTarget ON WikiRedLinkTarget.target_id = WikiRedLink.target_id
+             GROUP BY WikiRedLink.target_id
+             ORDER BY c DESC, WikiRedLinkTarget.target_title
+             LIMIT ?1",
+        )
+        .unwrap();
+    stmt.query_map([top], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::sqlite::red_links::{db_setup, most_wanted_pages, num_red_links};
+
+    #[test]
+    fn test_red_links() {
+        let conn = Connection::open_in_memory().unwrap();
+        db_setup(&conn);
+        for (target_id, target_title) in [(10, "A"), (11, "B"), (12, "C")] {
+            conn.execute(
+                "INSERT INTO WikiRedLinkTarget (target_id, target_title) VALUES (?1, ?2)",
+                (target_id, target_title),
+            )
+            .unwrap();
+        }
+        let red_links = [(1, 11), (2, 11), (3, 11), (1, 10), (2, 10), (3, 12)];
+        for (page_id, target_id) in red_links {
+            conn.execute(
+                "INSERT INTO WikiRedLink (page_id, target_id) VALUES (?1, ?2)",
+                (page_id, target_id),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(num_red_links(&conn), 6);
+        assert_eq!(
+            most_wanted_pages(&conn, 2),
+            vec![("B".to_string(), 3), ("A".to_string(), 2)]
+        );
+    }
+}
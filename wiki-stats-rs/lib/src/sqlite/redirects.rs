@@ -0,0 +1,148 @@
+use fxhash::FxHashMap;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+
+use crate::sqlite::table_exists;
+
+/// Redirect page -> target page, from the redirect dump (see
+/// [`crate::sqlite::to_sqlite::ToSqlite::create_redirect_db`])
+pub fn db_setup(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE if not exists WikiRedirect (
+            page_id INTEGER,
+            target_id INTEGER
+        )",
+        (),
+    )
+    .expect("Failed creating table 'WikiRedirect'");
+}
+
+pub fn create_unique_index(conn: &Connection) {
+    conn.execute(
+        "CREATE UNIQUE INDEX if not exists WikiRedirect_unique_index ON WikiRedirect(page_id)",
+        (),
+    )
+    .expect("Failed creating unique index");
+}
+
+/// Index to count the redirects of a page
+pub fn create_indices_post_setup(conn: &Connection) {
+    conn.execute(
+        "CREATE INDEX if not exists idx_redirect_target ON WikiRedirect(target_id);",
+        (),
+    )
+    .expect("Failed creating index");
+}
+
+/// The dump has the redirects of all namespaces, only the ones of pages in WikiPage are kept
+pub fn remove_redirects_of_missing_pages(conn: &Connection) -> usize {
+    conn.execute(
+        "DELETE FROM WikiRedirect WHERE page_id NOT IN (SELECT page_id FROM WikiPage)",
+        (),
+    )
+    .expect("Failed removing redirects")
+}
+
+/// Databases created before the redirect dump was ingested don't have the table
+pub fn has_redirects(conn: &Connection) -> bool {
+    table_exists(conn, "WikiRedirect")
+}
+
+/// redirect -> target of all redirects, empty for databases without the WikiRedirect table
+pub fn load_redirects(conn: &Connection) -> FxHashMap<PageId, PageId> {
+    if !has_redirects(conn) {
+        return FxHashMap::default();
+    }
+    let mut stmt = conn
+        .prepare("SELECT page_id, target_id FROM WikiRedirect")
+        .unwrap();
+    stmt.query_map([], |row| Ok((PageId(row.get(0)?), PageId(row.get(1)?))))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+/// The pages with the most redirects to them, descending
+pub fn most_redirected(conn: &Connection, top: usize) -> Vec<(PageId, u64)> {
+    if !has_redirects(conn) {
+        return vec![];
+    }
+    top_counts(
+        conn,
+        "SELECT target_id, COUNT(*) AS c FROM WikiRedirect
+         GROUP BY target_id ORDER BY c DESC, target_id LIMIT ?1",
+        top,
+    )
+}
+
+/// The redirects with the most links to them (links that go through a redirect), descending
+pub fn most_linked_redirects(conn: &Connection, top: usize) -> Vec<(PageId, u64)> {
+    if !has_redirects(conn) {
+        return vec![];
+    }
+    top_counts(
+        conn,
+        "SELECT page_link, COUNT(*) AS c FROM WikiLink
+         WHERE page_link IN (SELECT page_id FROM WikiRedirect)
+         GROUP BY page_link ORDER BY c DESC, page_link LIMIT ?1",
+        top,
+    )
+}
+
+fn top_counts(conn: &Connection, query: &str, top: usize) -> Vec<(PageId, u64)> {
+    let mut stmt = conn.prepare(query).unwrap();
+    stmt.query_map([top], |row| Ok((PageId(row.get(0)?), row.get(1)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parse_mediawiki_sql::field_types::PageId;
+
+    use crate::sqlite::redirects::{
+        db_setup, load_redirects, most_linked_redirects, most_redirected,
+        remove_redirects_of_missing_pages,
+    };
+    use crate::test_utils::{graph_db, insert_pages};
+
+    #[test]
+    fn test_redirect_table() {
+        let conn = graph_db(&[(2, 1), (3, 1), (4, 3), (5, 3), (5, 2), (1, 3)]);
+        assert!(load_redirects(&conn).is_empty());
+        assert!(most_redirected(&conn, 1).is_empty());
+
+        // 2 and 3 redirect to 1, 4 redirects to 3, 9 is not in WikiPage (another namespace)
+        db_setup(&conn);
+        for (page_id, target_id) in [(2, 1), (3, 1), (4, 3), (9, 1)] {
+            conn.execute(
+                "INSERT INTO WikiRedirect (page_id, target_id) VALUES (?1, ?2)",
+                (page_id, target_id),
+            )
+            .unwrap();
+        }
+        insert_pages(
+            &conn,
+            &[
+                (1, "P1", false),
+                (2, "P2", true),
+                (3, "P3", true),
+                (4, "P4", true),
+                (5, "P5", false),
+            ],
+        );
+        assert_eq!(remove_redirects_of_missing_pages(&conn), 1);
+
+        let redirects = load_redirects(&conn);
+        assert_eq!(redirects.len(), 3);
+        assert_eq!(redirects[&PageId(4)], PageId(3));
+
+        assert_eq!(most_redirected(&conn, 1), vec![(PageId(1), 2)]);
+        // 3 is linked by 4, 5 and 1
+        assert_eq!(
+            most_linked_redirects(&conn, 2),
+            vec![(PageId(3), 3), (PageId(2), 1)]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::Path;
+use std::time::Instant;
+
+use fxhash::FxHashMap;
+use itertools::Itertools;
+use log::info;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::Connection;
+
+use crate::calc::similarity::{PageSignature, band_buckets};
+
+/// Ids per `IN (...)` query
+const QUERY_BATCH_SIZE: usize = 10_000;
+
+/// Stores the page signatures and their LSH buckets in the wiki db, replacing previously stored ones
+pub fn save_signatures(path: impl AsRef<Path>, signatures: &FxHashMap<PageId, PageSignature>) {
+    let t1 = Instant::now();
+    let mut conn = Connection::open(path).expect("Failed creating database connection");
+
+    conn.execute_batch(
+        "DROP TABLE IF EXISTS PageSignature;
+        DROP TABLE IF EXISTS PageSignatureBand;
+        CREATE TABLE PageSignature (
+            page_id INTEGER PRIMARY KEY,
+            num_outgoing INTEGER NOT NULL,
+            num_incoming INTEGER NOT NULL,
+            minhash BLOB NOT NULL
+        );
+        CREATE TABLE PageSignatureBand (
+            band INTEGER NOT NULL,
+            bucket INTEGER NOT NULL,
+            page_id INTEGER NOT NULL,
+            PRIMARY KEY (band, bucket, page_id)
+        ) WITHOUT ROWID;",
+    )
+    .expect("Failed creating signature tables");
+
+    let tx = conn.transaction().unwrap();
+    {
+        let mut stmt = tx
+            .prepare_cached(
+                "INSERT INTO PageSignature (page_id, num_outgoing, num_incoming, minhash)
+                VALUES (?1, ?2, ?3, ?4)",
+            )
+            .unwrap();
+        let mut stmt_band = tx
+            .prepare_cached(
+                "INSERT INTO PageSignatureBand (band, bucket, page_id) VALUES (?1, ?2, ?3)",
+            )
+            .unwrap();
+
+        for (page_id, signature) in signatures {
+            let minhash: Vec<u8> = signature
+                .minhash
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect();
+            stmt.execute((
+                page_id.0,
+                signature.num_outgoing,
+                signature.num_incoming,
+                minhash,
+            ))
+            .unwrap();
+
+            for (band, bucket) in band_buckets(&signature.minhash).into_iter().enumerate() {
+                stmt_band.execute((band, bucket, page_id.0)).unwrap();
+            }
+        }
+    }
+    tx.commit().expect("Failed committing page signatures");
+
+    info!(
+        "Saved {} page signatures in {:?}",
+        signatures.len(),
+        t1.elapsed()
+    );
+}
+
+/// Stored signatures of the given ids. Ids without signature are left out
+pub fn load_signatures(conn: &Connection, ids: &[PageId]) -> FxHashMap<PageId, PageSignature> {
+    let mut signatures = FxHashMap::default();
+    for chunk in ids.chunks(QUERY_BATCH_SIZE) {
+        let ids_str = chunk.iter().map(|pid| pid.0.to_string()).join(",");
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT page_id, num_outgoing, num_incoming, minhash FROM PageSignature
+                WHERE page_id in ({ids_str})"
+            ))
+            .unwrap();
+
+        let rows = stmt
+            .query_map([], |row| {
+                let minhash: Vec<u8> = row.get(3)?;
+                Ok((
+                    PageId(row.get(0)?),
+                    PageSignature {
+                        num_outgoing: row.get(1)?,
+                        num_incoming: row.get(2)?,
+                        minhash: minhash
+                            .chunks_exact(4)
+                            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                            .collect(),
+                    },
+                ))
+            })
+            .unwrap();
+
+        for row in rows {
+            let (page_id, signature) = row.unwrap();
+            signatures.insert(page_id, signature);
+        }
+    }
+    signatures
+}
+
+/// (num_outgoing, num_incoming) of the given ids, without loading the minhash values
+pub fn load_degrees(conn: &Connection, ids: &[PageId]) -> FxHashMap<PageId, (u32, u32)> {
+    let mut degrees = FxHashMap::default();
+    for chunk in ids.chunks(QUERY_BATCH_SIZE) {
+        let ids_str = chunk.iter().map(|pid| pid.0.to_string()).join(",");
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT page_id, num_outgoing, num_incoming FROM PageSignature
+                WHERE page_id in ({ids_str})"
+            ))
+            .unwrap();
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((PageId(row.get(0)?), (row.get(1)?, row.get(2)?)))
+            })
+            .unwrap();
+
+        for row in rows {
+            let (page_id, degree) = row.unwrap();
+            degrees.insert(page_id, degree);
+        }
+    }
+    degrees
+}
+
+/// Up to limit pages in the bucket of the band
+pub fn bucket_pages(conn: &Connection, band: usize, bucket: i64, limit: usize) -> Vec<PageId> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT page_id FROM PageSignatureBand WHERE band = ?1 AND bucket = ?2 LIMIT ?3",
+        )
+        .unwrap();
+
+    stmt.query_map((band, bucket, limit), |row| Ok(PageId(row.get(0)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect()
+}
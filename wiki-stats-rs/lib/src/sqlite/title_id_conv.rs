@@ -9,6 +9,8 @@ use parse_mediawiki_sql::utils::Mmap;
 use rusqlite::Connection;
 use std::sync::LazyLock;
 
+use crate::sqlite::page_props;
+
 pub fn page_id_to_title(id: &PageId, conn: &Connection) -> Option<PageTitle> {
     let mut stmt = conn
         .prepare("SELECT page_title FROM WikiPage WHERE page_id = ?1")
@@ -204,11 +206,23 @@ pub fn count_duplicates(db_path: &str) {
     }
 }
 
-pub fn get_random_page(db_path: &Path, num: u32) -> FxHashSet<WikiPage> {
-    let stmt_str =
-        "SELECT page_id, page_title, is_redirect FROM WikiPage ORDER BY RANDOM() LIMIT ?1";
+/// Random pages, without disambiguation pages if exclude_disambiguation is set and the database
+/// has page props
+pub fn get_random_page(
+    db_path: &Path,
+    num: u32,
+    exclude_disambiguation: bool,
+) -> FxHashSet<WikiPage> {
     let conn = Connection::open(db_path).unwrap();
-    let mut stmt = conn.prepare(stmt_str).unwrap();
+    let where_str = exclude_disambiguation
+        .then(|| page_props::not_disambiguation_condition(&conn, "page_id"))
+        .flatten()
+        .map(|condition| format!("WHERE {condition}"))
+        .unwrap_or_default();
+    let stmt_str = format!(
+        "SELECT page_id, page_title, is_redirect FROM WikiPage {where_str} ORDER BY RANDOM() LIMIT ?1"
+    );
+    let mut stmt = conn.prepare(&stmt_str).unwrap();
     // dbg!(&stmt);
 
     let res = stmt
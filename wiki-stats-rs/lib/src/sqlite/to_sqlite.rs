@@ -9,7 +9,7 @@ use fxhash::FxHashMap;
 use indicatif::{MultiProgress, ProgressBar};
 use num_format::{Locale, ToFormattedString};
 use parse_mediawiki_sql::field_types::{LinkTargetId, PageId, PageTitle};
-use parse_mediawiki_sql::schemas::{CategoryLink, Page, PageLink};
+use parse_mediawiki_sql::schemas::{CategoryLink, Page, PageLink, PageProperty, Redirect};
 use parse_mediawiki_sql::utils::{Mmap, memory_map};
 use parse_mediawiki_sql::{FromSqlTuple, iterate_sql_insertions};
 use rusqlite::types::Null;
@@ -18,7 +18,10 @@ use rusqlite::{CachedStatement, Connection};
 use crate::calc::MAX_SIZE;
 use crate::sqlite::category_links::pagetype_to_string;
 use crate::sqlite::title_id_conv::TitleIdMap;
-use crate::sqlite::{category_links, load, page_links, title_id_conv, wiki};
+use crate::sqlite::{
+    category_links, load, page_links, page_props, red_links, redirects, table_exists,
+    title_id_conv, wiki,
+};
 use crate::utils::{default_bar, default_bar_unknown, spinner_bar, write_barstyle};
 
 //
@@ -30,8 +33,10 @@ type UniqueIndexFn = fn(conn: &mut Connection);
 const MAX_ESTIMATED_SIZE: usize = 230_712_457;
 
 pub struct InsertOptions<T, R> {
-    insert_stmt: String,
-    insert_fn: fn(&mut CachedStatement, R, &TitleIdMap, &LinkTargetTitleMap),
+    /// Prepared in the transaction and passed to the insert_fn in this order, a row can go to
+    /// several tables (e.g. a link to WikiLink or WikiRedLink)
+    insert_stmts: Vec<String>,
+    insert_fn: fn(&mut [CachedStatement], R, &TitleIdMap, &LinkTargetTitleMap),
     from_fn: fn(T) -> R,
 }
 
@@ -119,6 +124,8 @@ impl<'a> ToSqlite<'a> {
         pagelinks_sql_path: impl AsRef<Path>,
         page_sql_path: impl AsRef<Path>,
         linktarget_sql_path: impl AsRef<Path>,
+        page_props_sql_path: impl AsRef<Path>,
+        redirect_sql_path: impl AsRef<Path>,
     ) {
         // println!("-#--#- {wiki_name} -#--#-");
         // println!("[{wiki_name}] Inserting into database at: {db_path:?}");
@@ -152,7 +159,7 @@ impl<'a> ToSqlite<'a> {
         conn.execute("PRAGMA synchronous = OFF", ()).unwrap();
         // conn.execute("PRAGMA journal_mode = OFF", ()).unwrap();
 
-        wiki::create_db(&conn, &self.wiki_name);
+        wiki::create_db(&conn, &self.wiki_name, &self.dump_date);
 
         //  167_176_646
         // 6_566_564_65
@@ -172,6 +179,32 @@ impl<'a> ToSqlite<'a> {
             false,
         );
 
+        // downloads of older runs have no page_props dump
+        if page_props_sql_path.as_ref().exists() {
+            self.create_page_props_db(&page_props_sql_path, &mut conn);
+        } else {
+            self.multi_pb
+                .println(format!(
+                    "[{}] No page props at {:?}, disambiguation pages are not marked",
+                    self.wiki_name,
+                    page_props_sql_path.as_ref()
+                ))
+                .unwrap();
+        }
+
+        // downloads of older runs have no redirect dump either
+        if redirect_sql_path.as_ref().exists() {
+            self.create_redirect_db(&redirect_sql_path, &mut conn, &title_id_map);
+        } else {
+            self.multi_pb
+                .println(format!(
+                    "[{}] No redirects at {:?}, there are no redirect stats",
+                    self.wiki_name,
+                    redirect_sql_path.as_ref()
+                ))
+                .unwrap();
+        }
+
         conn.execute(
             "UPDATE Info SET insertion_time_s = ? WHERE id = 0",
             (t1.elapsed().as_secs_f64(),),
@@ -216,6 +249,28 @@ impl<'a> ToSqlite<'a> {
         page_links::create_unique_index(&conn);
         bar2.finish_with_message(format!("{:?}", t2.elapsed()));
 
+        if redirects::has_redirects(&conn) {
+            let t3 = Instant::now();
+            let bar3 = spinner_bar("Creating [WikiRedirect] index");
+            redirects::create_indices_post_setup(&conn);
+            bar3.finish_with_message(format!("{:?}", t3.elapsed()));
+        }
+
+        if table_exists(&conn, "WikiRedLink") {
+            let t4 = Instant::now();
+            let bar4 = spinner_bar("Creating [WikiRedLink] index");
+            red_links::create_indices_post_setup(&conn);
+            bar4.finish_with_message(format!("{:?}", t4.elapsed()));
+        }
+
+        if page_props::has_page_props(&conn) {
+            let t5 = Instant::now();
+            let bar5 = spinner_bar("Creating [WikiPageProps] index");
+            page_props::remove_props_of_missing_pages(&conn);
+            page_props::create_indices_post_setup(&conn);
+            bar5.finish_with_message(format!("{:?}", t5.elapsed()));
+        }
+
         conn.execute(
             "UPDATE Info SET is_done = ?, index_creation_time_s = ? WHERE id = 0",
             (1, t1.elapsed().as_secs_f64()),
@@ -313,10 +368,12 @@ impl<'a> ToSqlite<'a> {
         }
 
         page_links::db_setup(conn);
+        red_links::db_setup(conn);
 
         let opts = DuplicateOptions::skip_duplicates(
             |conn| {
                 page_links::create_unique_index(conn);
+                red_links::create_unique_index(conn);
             },
             1.0,
         );
@@ -324,8 +381,9 @@ impl<'a> ToSqlite<'a> {
         // let opts = DuplicateOptions::allow_duplicates();
         type InsertType = (PageId, LinkTargetId);
 
+        /// Links to existing targets without a page go to WikiRedLink
         fn insert_pagelink(
-            stmt: &mut CachedStatement,
+            stmts: &mut [CachedStatement],
             link: InsertType,
             map: &TitleIdMap,
             lt_pt_map: &LinkTargetTitleMap,
@@ -333,9 +391,16 @@ impl<'a> ToSqlite<'a> {
             let from_id = link.0;
             let target_id = link.1;
 
-            if let Some(pid) = lt_pt_map.get(&target_id).and_then(|pt| map.get(pt)) {
+            let Some(title) = lt_pt_map.get(&target_id) else {
+                return;
+            };
+            if let Some(pid) = map.get(title) {
                 let link = (from_id.0, pid.0);
-                stmt.execute(link).unwrap();
+                stmts[0].execute(link).unwrap();
+            } else {
+                // the title is stored once per link target
+                stmts[1].execute((from_id.0, target_id.0)).unwrap();
+                stmts[2].execute((target_id.0, &title.0)).unwrap();
             }
         }
 
@@ -344,7 +409,12 @@ impl<'a> ToSqlite<'a> {
         }
 
         let insrt_opts = InsertOptions {
-            insert_stmt: "INSERT INTO WikiLink(page_id, page_link) VALUES (?, ?)".to_string(),
+            insert_stmts: vec![
+                "INSERT INTO WikiLink(page_id, page_link) VALUES (?, ?)".to_string(),
+                "INSERT INTO WikiRedLink(page_id, target_id) VALUES (?, ?)".to_string(),
+                "INSERT OR IGNORE INTO WikiRedLinkTarget(target_id, target_title) VALUES (?, ?)"
+                    .to_string(),
+            ],
             insert_fn: insert_pagelink,
             from_fn: from_pagelink,
         };
@@ -428,13 +498,13 @@ impl<'a> ToSqlite<'a> {
         // }
 
         fn insert_page(
-            stmt: &mut CachedStatement,
+            stmts: &mut [CachedStatement],
             insert: InsertType,
             _: &TitleIdMap,
             _: &LinkTargetTitleMap,
         ) {
             // assert!(link.from_namespace.into_inner() == 0);
-            let res = stmt.execute(insert).unwrap();
+            let res = stmts[0].execute(insert).unwrap();
             // if res.is_err() {
             //     duplicates += 1;
             // }
@@ -444,8 +514,10 @@ impl<'a> ToSqlite<'a> {
         }
 
         let insrt_opts = InsertOptions {
-            insert_stmt: "INSERT INTO WikiPage(page_id, page_title, is_redirect) VALUES (?, ?, ?)"
-                .to_string(),
+            insert_stmts: vec![
+                "INSERT INTO WikiPage(page_id, page_title, is_redirect) VALUES (?, ?, ?)"
+                    .to_string(),
+            ],
             insert_fn: insert_page,
             from_fn: from_page,
         };
@@ -504,16 +576,16 @@ impl<'a> ToSqlite<'a> {
         }
 
         fn insert_cl(
-            stmt: &mut CachedStatement,
+            stmts: &mut [CachedStatement],
             insert: InsertType,
             _: &TitleIdMap,
             _: &LinkTargetTitleMap,
         ) {
-            stmt.execute(insert).unwrap();
+            stmts[0].execute(insert).unwrap();
         }
 
         let insrt_opts = InsertOptions {
-            insert_stmt: "INSERT INTO WikiCategoryLinks(page_id_from, category_name, category_type) VALUES (?, ?, ?)".to_string(),
+            insert_stmts: vec!["INSERT INTO WikiCategoryLinks(page_id_from, category_name, category_type) VALUES (?, ?, ?)".to_string()],
             insert_fn: insert_cl,
             from_fn: from_cl,
         };
@@ -541,6 +613,138 @@ impl<'a> ToSqlite<'a> {
         // title_id_conv::create_indices_post_setup(conn);
     }
 
+    /// Disambiguation flags and Wikidata items of the page_props dump (see [`page_props`])
+    pub fn create_page_props_db(&self, sql_file_path: impl AsRef<Path>, conn: &mut Connection) {
+        self.multi_pb
+            .println(format!(
+                "[{}] {}",
+                self.wiki_name,
+                "--- WikiPageProps ---".purple()
+            ))
+            .unwrap();
+        let mmap: Mmap = unsafe { memory_map(sql_file_path).unwrap() };
+
+        page_props::db_setup(conn);
+
+        let opts = DuplicateOptions::skip_duplicates(
+            |conn| {
+                page_props::create_unique_index(conn);
+            },
+            1.0,
+        );
+
+        type InsertType = (u32, String, String);
+
+        fn from_page_prop(pp: PageProperty) -> InsertType {
+            (
+                pp.page.0,
+                pp.name.to_string(),
+                String::from_utf8_lossy(&pp.value).into_owned(),
+            )
+        }
+
+        fn insert_page_prop(
+            stmts: &mut [CachedStatement],
+            insert: InsertType,
+            _: &TitleIdMap,
+            _: &LinkTargetTitleMap,
+        ) {
+            stmts[0].execute(insert).unwrap();
+        }
+
+        let insrt_opts = InsertOptions {
+            insert_stmts: vec![
+                "INSERT INTO WikiPageProps(page_id, prop_name, value) VALUES (?, ?, ?)".to_string(),
+            ],
+            insert_fn: insert_page_prop,
+            from_fn: from_page_prop,
+        };
+
+        let skip_fn = |pp: &PageProperty| -> bool { !page_props::STORED_PROPS.contains(&pp.name) };
+
+        self.insert_directly::<PageProperty, InsertType>(
+            &mmap,
+            conn,
+            MAX_ESTIMATED_SIZE,
+            &insrt_opts,
+            skip_fn,
+            opts,
+            &FxHashMap::default(),
+            &FxHashMap::default(),
+            "page_props",
+        );
+    }
+
+    /// Targets of the redirect dump (see [`redirects`]). Needs WikiPage, the target titles are
+    /// converted with the title_id_map. Redirects to missing pages, to other namespaces or to
+    /// other wikis are left out, as are the redirects of pages that are not in WikiPage
+    pub fn create_redirect_db(
+        &self,
+        sql_file_path: impl AsRef<Path>,
+        conn: &mut Connection,
+        title_id_map: &TitleIdMap,
+    ) {
+        self.multi_pb
+            .println(format!(
+                "[{}] {}",
+                self.wiki_name,
+                "--- WikiRedirect ---".purple()
+            ))
+            .unwrap();
+        let mmap: Mmap = unsafe { memory_map(sql_file_path).unwrap() };
+
+        redirects::db_setup(conn);
+
+        let opts = DuplicateOptions::skip_duplicates(
+            |conn| {
+                redirects::create_unique_index(conn);
+            },
+            1.0,
+        );
+
+        type InsertType = (u32, PageTitle);
+
+        fn from_redirect(redirect: Redirect) -> InsertType {
+            (redirect.from.0, redirect.title)
+        }
+
+        fn insert_redirect(
+            stmts: &mut [CachedStatement],
+            insert: InsertType,
+            title_id_map: &TitleIdMap,
+            _: &LinkTargetTitleMap,
+        ) {
+            if let Some(target) = title_id_map.get(&insert.1) {
+                stmts[0].execute((insert.0, target.0)).unwrap();
+            }
+        }
+
+        let insrt_opts = InsertOptions {
+            insert_stmts: vec![
+                "INSERT INTO WikiRedirect(page_id, target_id) VALUES (?, ?)".to_string(),
+            ],
+            insert_fn: insert_redirect,
+            from_fn: from_redirect,
+        };
+
+        let skip_fn = |redirect: &Redirect| -> bool {
+            redirect.namespace.0 != 0 || redirect.interwiki.is_some_and(|wiki| !wiki.is_empty())
+        };
+
+        self.insert_directly::<Redirect, InsertType>(
+            &mmap,
+            conn,
+            MAX_ESTIMATED_SIZE,
+            &insrt_opts,
+            skip_fn,
+            opts,
+            title_id_map,
+            &FxHashMap::default(),
+            "redirect",
+        );
+        redirects::remove_redirects_of_missing_pages(conn);
+    }
+
     pub fn insert_directly<'b, WikiType, InsertType>(
         &self,
         mmap: &'b Mmap,
@@ -615,10 +819,14 @@ impl<'a> ToSqlite<'a> {
 
         let mut num_inserted = 0;
         {
-            let mut stmt = tx.prepare_cached(&insert_options.insert_stmt).unwrap();
+            let mut stmts: Vec<CachedStatement> = insert_options
+                .insert_stmts
+                .iter()
+                .map(|insert_stmt| tx.prepare_cached(insert_stmt).unwrap())
+                .collect();
             for row in iterator {
                 // stmt.execute((insert_options.insert_fn)(row)).unwrap();
-                (insert_options.insert_fn)(&mut stmt, row, map, lt_map);
+                (insert_options.insert_fn)(&mut stmts, row, map, lt_map);
                 bar.inc(1);
                 num_inserted += 1;
             }
@@ -1,15 +1,26 @@
 use rusqlite::Connection;
 
-pub fn create_db(conn: &Connection, wiki_name: &str) {
+pub fn create_db(conn: &Connection, wiki_name: &str, dump_date: &str) {
     db_setup(conn);
-    conn.execute("INSERT OR IGNORE INTO Wiki(name) VALUES (?1)", (wiki_name,))
-        .unwrap();
+    conn.execute(
+        "INSERT OR IGNORE INTO Wiki(name, dump_date) VALUES (?1, ?2)",
+        (wiki_name, dump_date),
+    )
+    .unwrap();
+}
+
+/// Dump date the db was created from, None for dbs created before it was stored
+pub fn dump_date(conn: &Connection) -> Option<String> {
+    conn.query_row("SELECT dump_date FROM Wiki", (), |row| row.get(0))
+        .ok()
+        .flatten()
 }
 
 fn db_setup(conn: &Connection) {
     conn.execute(
         "CREATE TABLE if not exists Wiki (
-            name TEXT PRIMARY KEY
+            name TEXT PRIMARY KEY,
+            dump_date TEXT
         )",
         (),
     )
@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+
+use fxhash::FxHashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AvgDepthHistogram, DepthHistogram,
+    stats::{
+        Stats,
+        samples::{BfsSample, BiBfsSample},
+        stats::{LinkCount, PageTitle, StatRecord, WikiName},
+    },
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct NumericDelta {
+    pub old: f64,
+    pub new: f64,
+    pub delta: f64,
+    /// delta relative to old, None if old is 0
+    pub relative: Option<f64>,
+}
+
+impl NumericDelta {
+    fn new(old: f64, new: f64) -> Self {
+        Self {
+            old,
+            new,
+            delta: new - old,
+            relative: (old != 0.0).then(|| (new - old) / old),
+        }
+    }
+}
+
+/// Ranks are 1-based. A page only in the new list entered it, one only in the old list left it
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct RankChange {
+    pub page_title: PageTitle,
+    pub old_rank: Option<usize>,
+    pub new_rank: Option<usize>,
+    pub old_count: Option<u64>,
+    pub new_count: Option<u64>,
+}
+
+/// Change of a histogram, both normalized to shares of their total
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct HistogramShift {
+    pub old_mean: f64,
+    pub new_mean: f64,
+    pub mean_shift: f64,
+    /// depth -> new share - old share
+    pub share_deltas: BTreeMap<u32, f64>,
+}
+
+/// Differences between two stats files, per metric per wiki. Only wikis that are in both are
+/// compared
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+pub struct StatsDiff {
+    pub old_dump_date: String,
+    pub new_dump_date: String,
+    /// Every record with numeric values, e.g. num_pages
+    pub numeric: BTreeMap<String, BTreeMap<WikiName, NumericDelta>>,
+    /// Every record of ranked pages, e.g. most_linked and most_links
+    pub rankings: BTreeMap<String, BTreeMap<WikiName, Vec<RankChange>>>,
+    /// The path length histograms of the bidirectional bfs samples and the depth histograms of the
+    /// bfs samples
+    pub histograms: BTreeMap<String, BTreeMap<WikiName, HistogramShift>>,
+}
+
+impl StatsDiff {
+    pub fn new(old: &Stats, new: &Stats) -> Self {
+        let mut diff = StatsDiff {
+            old_dump_date: old.dump_date.clone(),
+            new_dump_date: new.dump_date.clone(),
+            ..Default::default()
+        };
+
+        for (name, old_record) in &old.records {
+            let Some(new_record) = new.records.get(name) else {
+                continue;
+            };
+
+            if let (Some(old_record), Some(new_record)) =
+                (old_record.as_object(), new_record.as_object())
+            {
+                let deltas: BTreeMap<WikiName, NumericDelta> = old_record
+                    .iter()
+                    .filter_map(|(wiki_name, old_value)| {
+                        let old_value = old_value.as_f64()?;
+                        let new_value = new_record.get(wiki_name)?.as_f64()?;
+                        Some((wiki_name.clone(), NumericDelta::new(old_value, new_value)))
+                    })
+                    .collect();
+                if !deltas.is_empty() {
+                    diff.numeric.insert(name.clone(), deltas);
+                    continue;
+                }
+            }
+
+            if let (Some(old_record), Some(new_record)) = (
+                old.record::<Vec<LinkCount>>(name),
+                new.record::<Vec<LinkCount>>(name),
+            ) {
+                let changes: BTreeMap<WikiName, Vec<RankChange>> =
+                    both_wikis(&old_record, &new_record)
+                        .map(|(wiki_name, old_list, new_list)| {
+                            (wiki_name, rank_changes(old_list, new_list))
+                        })
+                        .collect();
+                diff.rankings.insert(name.clone(), changes);
+            }
+        }
+
+        if let (Some(old_samples), Some(new_samples)) = (
+            old.record::<BiBfsSample>("bi_bfs_sample_stats"),
+            new.record::<BiBfsSample>("bi_bfs_sample_stats"),
+        ) {
+            let shifts = both_wikis(&old_samples, &new_samples)
+                .map(|(wiki_name, old_sample, new_sample)| {
+                    let to_f64 = |histogram: &DepthHistogram| {
+                        histogram.iter().map(|(d, c)| (*d, *c as f64)).collect()
+                    };
+                    let shift = histogram_shift(
+                        &to_f64(&old_sample.path_length_histogram),
+                        &to_f64(&new_sample.path_length_histogram),
+                    );
+                    (wiki_name, shift)
+                })
+                .collect();
+            diff.histograms
+                .insert("path_length_histogram".to_string(), shifts);
+        }
+
+        if let (Some(old_samples), Some(new_samples)) = (
+            old.record::<BfsSample>("bfs_sample_stats"),
+            new.record::<BfsSample>("bfs_sample_stats"),
+        ) {
+            let shifts = both_wikis(&old_samples, &new_samples)
+                .map(|(wiki_name, old_sample, new_sample)| {
+                    let to_f64 = |histogram: &AvgDepthHistogram| {
+                        histogram
+                            .iter()
+                            .map(|(d, stat)| (*d, stat.avg_occurences))
+                            .collect()
+                    };
+                    let shift = histogram_shift(
+                        &to_f64(&old_sample.avg_depth_histogram),
+                        &to_f64(&new_sample.avg_depth_histogram),
+                    );
+                    (wiki_name, shift)
+                })
+                .collect();
+            diff.histograms
+                .insert("avg_depth_histogram".to_string(), shifts);
+        }
+
+        diff
+    }
+}
+
+/// (wiki, old, new) of the wikis in both records, sorted by wiki name
+fn both_wikis<'a, T>(
+    old: &'a StatRecord<T>,
+    new: &'a StatRecord<T>,
+) -> impl Iterator<Item = (WikiName, &'a T, &'a T)> {
+    let mut wikis: Vec<&WikiName> = old.keys().filter(|w| new.contains_key(*w)).collect();
+    wikis.sort();
+    wikis
+        .into_iter()
+        .map(|wiki_name| (wiki_name.clone(), &old[wiki_name], &new[wiki_name]))
+}
+
+/// All pages of the new list in order, then the ones that left it. The global list ranks pages of
+/// all wikis, so pages are identified by wiki and id
+fn rank_changes(old: &[LinkCount], new: &[LinkCount]) -> Vec<RankChange> {
+    let key = |link_count: &LinkCount| (link_count.wiki_name.clone(), link_count.page_id);
+    let old_ranks: FxHashMap<(WikiName, u64), (usize, &LinkCount)> = old
+        .iter()
+        .enumerate()
+        .map(|(i, link_count)| (key(link_count), (i + 1, link_count)))
+        .collect();
+    let new_keys: Vec<(WikiName, u64)> = new.iter().map(key).collect();
+
+    let mut changes: Vec<RankChange> = new
+        .iter()
+        .zip(&new_keys)
+        .enumerate()
+        .map(|(i, (link_count, key))| {
+            let old = old_ranks.get(key);
+            RankChange {
+                page_title: link_count.page_title.clone(),
+                old_rank: old.map(|(rank, _)| *rank),
+                new_rank: Some(i + 1),
+                old_count: old.map(|(_, old)| old.count),
+                new_count: Some(link_count.count),
+            }
+        })
+        .collect();
+
+    changes.extend(
+        old.iter()
+            .enumerate()
+            .filter(|(_, link_count)| !new_keys.contains(&key(link_count)))
+            .map(|(i, link_count)| RankChange {
+                page_title: link_count.page_title.clone(),
+                old_rank: Some(i + 1),
+                new_rank: None,
+                old_count: Some(link_count.count),
+                new_count: None,
+            }),
+    );
+    changes
+}
+
+fn histogram_shift(old: &BTreeMap<u32, f64>, new: &BTreeMap<u32, f64>) -> HistogramShift {
+    let shares = |histogram: &BTreeMap<u32, f64>| -> (BTreeMap<u32, f64>, f64) {
+        let total: f64 = histogram.values().sum();
+        if total == 0.0 {
+            return (BTreeMap::new(), 0.0);
+        }
+        let shares: BTreeMap<u32, f64> = histogram
+            .iter()
+            .map(|(depth, count)| (*depth, count / total))
+            .collect();
+        let mean = shares
+            .iter()
+            .map(|(depth, share)| *depth as f64 * share)
+            .sum();
+        (shares, mean)
+    };
+    let (old_shares, old_mean) = shares(old);
+    let (new_shares, new_mean) = shares(new);
+
+    let mut share_deltas = BTreeMap::new();
+    for depth in old_shares.keys().chain(new_shares.keys()) {
+        let old_share = old_shares.get(depth).copied().unwrap_or(0.0);
+        let new_share = new_shares.get(depth).copied().unwrap_or(0.0);
+        share_deltas.insert(*depth, new_share - old_share);
+    }
+
+    HistogramShift {
+        old_mean,
+        new_mean,
+        mean_shift: new_mean - old_mean,
+        share_deltas,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use crate::stats::Stats;
+    use crate::stats::diff::StatsDiff;
+
+    fn link_count(title: &str, page_id: u64, count: u64) -> Value {
+        json!({"page_title": title, "page_id": page_id, "wiki_name": "dewiki", "count": count})
+    }
+
+    fn stats(
+        dump_date: &str,
+        num_pages: Value,
+        most_linked: Vec<Value>,
+        histogram: Value,
+    ) -> Stats {
+        serde_json::from_value(json!({
+            "num_pages": num_pages,
+            "most_linked": {"dewiki": most_linked},
+            "created_at": 0,
+            "dump_date": dump_date,
+            "wikis": ["dewiki"],
+            "seconds_taken": 1,
+            "bi_bfs_sample_stats": {"dewiki": {
+                "sample_size": 4,
+                "longest_path_stat": {"min": [["A", "B"], 1], "max": [["A", "C"], 3], "avg": 2.0},
+                "path_length_histogram": histogram,
+                "seconds_taken": 1,
+            }},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_stats_diff() {
+        let old = stats(
+            "20240201",
+            json!({"dewiki": 100, "enwiki": 5}),
+            vec![link_count("A", 1, 50), link_count("B", 2, 40)],
+            json!({"1": 2, "2": 2}),
+        );
+        let new = stats(
+            "20240301",
+            json!({"dewiki": 150}),
+            vec![link_count("B", 2, 60), link_count("C", 3, 45)],
+            json!({"2": 2, "3": 2}),
+        );
+
+        let diff = StatsDiff::new(&old, &new);
+
+        let num_pages = &diff.numeric["num_pages"];
+        assert_eq!(num_pages.len(), 1);
+        assert_eq!(num_pages["dewiki"].delta, 50.0);
+        assert_eq!(num_pages["dewiki"].relative, Some(0.5));
+
+        let most_linked = &diff.rankings["most_linked"]["dewiki"];
+        let ranks: Vec<(&str, Option<usize>, Option<usize>)> = most_linked
+            .iter()
+            .map(|change| (change.page_title.as_str(), change.old_rank, change.new_rank))
+            .collect();
+        assert_eq!(
+            ranks,
+            vec![
+                ("B", Some(2), Some(1)),
+                ("C", None, Some(2)),
+                ("A", Some(1), None)
+            ]
+        );
+        assert_eq!(most_linked[0].old_count, Some(40));
+
+        let shift = &diff.histograms["path_length_histogram"]["dewiki"];
+        assert_eq!(shift.mean_shift, 1.0);
+        assert_eq!(shift.share_deltas[&1], -0.5);
+        assert_eq!(shift.share_deltas[&2], 0.0);
+        assert_eq!(shift.share_deltas[&3], 0.5);
+        assert!(!diff.histograms.contains_key("avg_depth_histogram"));
+    }
+}
@@ -3,15 +3,17 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use log::info;
+use log::{info, warn};
 
 use crate::{
+    calc::pagerank::PageRanking,
     download::ALL_DB_TABLES,
+    sqlite::{diff::diff_db_files, join_db_wiki_path},
     stats::{
         WikiIdent, create_wiki_idents,
-        samples::{sample_bfs_stats, sample_bidirectional_bfs_stats},
-        stats::{Stats, WebWikiSizes},
-        utils::{global_ignore, make_stat_record_async, make_stat_record_seq},
+        registry::{BfsSampleStat, BiBfsSampleStat, DistanceMatrixStat, DynStat},
+        stats::{LinkChurn, Stats, WebWikiSizes},
+        utils::{make_stat_record_seq, sum_global},
     },
     web::find_smallest_wikis,
 };
@@ -33,6 +35,32 @@ pub async fn add_web_wiki_sizes(output_path: impl AsRef<Path>, dump_date: Option
     save_stats(&stats, output_path);
 }
 
+/// Computes a registered stat and adds its record to the existing json file. Wikis already in the
+/// record are kept unless always
+async fn add_stat_record(
+    output_path: &Path,
+    db_path: PathBuf,
+    wikis: Vec<String>,
+    stat: Box<dyn DynStat>,
+    always: bool,
+) {
+    let wiki_idents: Vec<WikiIdent> = create_wiki_idents(&db_path, wikis);
+    let mut stats = load_stats(output_path);
+
+    let prev = stats.records.get(stat.name()).filter(|_| !always).cloned();
+    let (name, record) = tokio::task::spawn_blocking(move || {
+        (
+            stat.name(),
+            stat.compute_record(&wiki_idents, prev.as_ref()),
+        )
+    })
+    .await
+    .expect("Failed computing stat");
+
+    stats.records.insert(name.to_string(), record);
+    save_stats(&stats, output_path);
+}
+
 /// Calculates bfs stats and adds or overwrites stats to/of existing json file
 pub async fn add_sample_bfs_stats(
     output_path: impl AsRef<Path>,
@@ -41,27 +69,23 @@ pub async fn add_sample_bfs_stats(
     sample_size: usize,
     num_threads: usize,
     cache_max_size: Option<usize>,
+    exclude_disambiguation: bool,
     always: bool,
 ) {
-    let database_path = db_path.into();
-    let wiki_idents: Vec<WikiIdent> = create_wiki_idents(&database_path, wikis);
-    let path: &Path = output_path.as_ref();
-
-    let mut stats = load_stats(path);
-
-    let bfs_sample_stats = make_stat_record_seq(
-        wiki_idents,
-        |w_id: WikiIdent| sample_bfs_stats(w_id, sample_size, num_threads, cache_max_size),
-        global_ignore,
-        if !always {
-            stats.bfs_sample_stats.clone()
-        } else {
-            None
-        },
-    );
-
-    stats.bfs_sample_stats = Some(bfs_sample_stats.await);
-    save_stats(&stats, path);
+    let stat = BfsSampleStat {
+        sample_size,
+        num_threads,
+        cache_max_size,
+        exclude_disambiguation,
+    };
+    add_stat_record(
+        output_path.as_ref(),
+        db_path.into(),
+        wikis,
+        Box::new(stat),
+        always,
+    )
+    .await;
 }
 
 /// Calculates bidirectional bfs stats and adds or overwrites stats to/of existing json file
@@ -72,21 +96,97 @@ pub async fn add_sample_bibfs_stats(
     wikis: Vec<String>,
     sample_size: usize,
     num_threads: usize,
+    exclude_disambiguation: bool,
+) {
+    let stat = BiBfsSampleStat {
+        sample_size,
+        num_threads,
+        exclude_disambiguation,
+    };
+    add_stat_record(
+        output_path.as_ref(),
+        db_path.into(),
+        wikis,
+        Box::new(stat),
+        false,
+    )
+    .await;
+}
+
+/// Calculates the distance matrix of the top pages and adds or overwrites it to/of existing json file
+pub async fn add_distance_matrix_stats(
+    output_path: impl AsRef<Path>,
+    db_path: impl Into<PathBuf>,
+    wikis: Vec<String>,
+    num_pages: usize,
+    ranking: PageRanking,
+    num_threads: usize,
+    always: bool,
+) {
+    let stat = DistanceMatrixStat {
+        num_pages,
+        ranking,
+        num_threads,
+    };
+    add_stat_record(
+        output_path.as_ref(),
+        db_path.into(),
+        wikis,
+        Box::new(stat),
+        always,
+    )
+    .await;
+}
+
+/// Compares the wikis to their databases of an older dump date and adds the summaries to the
+/// existing json file. Wikis without an old database are skipped
+pub async fn add_link_churn_stats(
+    output_path: impl AsRef<Path>,
+    db_path: impl Into<PathBuf>,
+    old_db_path: impl AsRef<Path>,
+    old_dump_date: String,
+    wikis: Vec<String>,
+    always: bool,
 ) {
     let output_path = output_path.as_ref();
-    let database_path = db_path.into();
-    let wiki_idents: Vec<WikiIdent> = create_wiki_idents(&database_path, wikis);
+    let old_db_path = old_db_path.as_ref();
+    let wiki_idents: Vec<WikiIdent> = create_wiki_idents(&db_path.into(), wikis)
+        .into_iter()
+        .filter(|w_id| {
+            let exists = join_db_wiki_path(old_db_path, &w_id.wiki_name).exists();
+            if !exists {
+                warn!("[{}] No database at {old_db_path:?}", w_id.wiki_name);
+            }
+            exists
+        })
+        .collect();
 
     let mut stats = load_stats(output_path);
 
-    let bi_bfs_sample_stats = make_stat_record_async(
+    // summaries against another dump date are outdated
+    let existing = stats
+        .link_churn
+        .take()
+        .filter(|churn| !always && churn.old_dump_date == old_dump_date)
+        .map(|churn| churn.diffs);
+
+    let diffs = make_stat_record_seq(
         wiki_idents,
-        move |w_id: WikiIdent| sample_bidirectional_bfs_stats(w_id, sample_size, num_threads),
-        global_ignore,
-        stats.bi_bfs_sample_stats.clone(),
+        |w_id: WikiIdent| {
+            diff_db_files(
+                join_db_wiki_path(old_db_path, &w_id.wiki_name),
+                &w_id.db_path,
+                None,
+            )
+        },
+        sum_global,
+        existing,
     );
 
-    stats.bi_bfs_sample_stats = Some(bi_bfs_sample_stats.await);
+    stats.link_churn = Some(LinkChurn {
+        old_dump_date,
+        diffs: diffs.await,
+    });
     save_stats(&stats, output_path);
 }
 
@@ -1,11 +1,14 @@
 use crate::{
     WikiIdent,
+    sqlite::page_props::not_disambiguation_condition,
     stats::stats::{Page, WikiName},
     utils::default_bar_unknown,
 };
 use fxhash::FxHashSet;
 use parse_mediawiki_sql::field_types::PageId;
+use regex::Regex;
 use rusqlite::Connection;
+use std::fmt;
 use std::path::Path;
 use tokio::time::Instant;
 
@@ -223,28 +226,138 @@ pub fn query_page(stmt_str: &str, db_path: impl AsRef<Path>, wiki_name: WikiName
 // }
 //
 
-/// returns ids of pages with the most links
+/// Title patterns excluded from the filtered rankings by default: years, dates and lists
+pub const DEFAULT_EXCLUDED_TITLES: [&str; 4] = [
+    r"^\d{1,4}(_(BC|BCE|AD|CE)|年)?$",
+    r"^(\d{1,2}\.?_)?(January|February|March|April|May|June|July|August|September|October|November|December)(_\d{1,2})?$",
+    r"^\d{1,2}月\d{1,2}日$",
+    r"^(List_of|Lists_of|Liste_der|Liste_von|Liste_des)_",
+];
+
+/// Disambiguation pages by their title suffix, for databases without page props
+const DISAMBIGUATION_TITLES: &str = r"_\((disambiguation|Begriffsklärung|homonymie|desambiguación|disambigua|значения|曖昧さ回避)\)$";
+
+/// Pages left out of a ranking. The wiki dbs only contain the main namespace, so there are no
+/// namespaces to exclude
+#[derive(Debug, Clone, Default)]
+pub struct RankingFilter {
+    /// Pages whose title matches any of them
+    pub title_patterns: Vec<Regex>,
+    pub exclude_redirects: bool,
+    /// Marked in the page props, or by title for databases without them
+    pub exclude_disambiguation: bool,
+}
+
+impl RankingFilter {
+    pub fn new(
+        title_patterns: &[impl AsRef<str>],
+        exclude_redirects: bool,
+        exclude_disambiguation: bool,
+    ) -> Result<Self, regex::Error> {
+        Ok(Self {
+            title_patterns: title_patterns
+                .iter()
+                .map(|pattern| Regex::new(pattern.as_ref()))
+                .collect::<Result<_, _>>()?,
+            exclude_redirects,
+            exclude_disambiguation,
+        })
+    }
+
+    /// Years, dates, lists, redirects and disambiguation pages
+    pub fn default_exclusions() -> Self {
+        Self::new(&DEFAULT_EXCLUDED_TITLES, true, true).unwrap()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.title_patterns.is_empty() && !self.exclude_redirects && !self.exclude_disambiguation
+    }
+
+    fn excludes_title(&self, title: &str, disambiguation: Option<&Regex>) -> bool {
+        disambiguation.is_some_and(|re| re.is_match(title))
+            || self.title_patterns.iter().any(|re| re.is_match(title))
+    }
+}
+
+/// The exclusions, part of the settings stored with the rankings
+impl fmt::Display for RankingFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let patterns: Vec<&str> = self.title_patterns.iter().map(Regex::as_str).collect();
+        write!(
+            f,
+            "exclude_titles={patterns:?} exclude_redirects={} exclude_disambiguation={}",
+            self.exclude_redirects, self.exclude_disambiguation
+        )
+    }
+}
+
+/// returns ids of pages with the most links, without the pages excluded by the filter
 pub fn select_link_count_groupby(
     top: usize,
     db_path: impl AsRef<Path>,
     groupby: &str,
+    filter: &RankingFilter,
 ) -> Vec<(u64, u64)> {
     let mut link_count = vec![];
 
     let conn = Connection::open(db_path).unwrap();
+    if filter.is_empty() {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {groupby}, COUNT(*) FROM WikiLink \
+                GROUP BY {groupby} ORDER BY count(*) DESC LIMIT {top}"
+            ))
+            .unwrap();
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0).unwrap(), row.get(1).unwrap())))
+            .unwrap();
+
+        for row in rows {
+            link_count.push(row.unwrap())
+        }
+        return link_count;
+    }
+
+    // the titles are filtered here, so the counts are streamed until there are enough
+    let mut conditions = vec![];
+    if filter.exclude_redirects {
+        conditions.push("WikiPage.is_redirect = 0".to_string());
+    }
+    let mut disambiguation = None;
+    if filter.exclude_disambiguation {
+        match not_disambiguation_condition(&conn, "WikiPage.page_id") {
+            Some(condition) => conditions.push(condition),
+            None => disambiguation = Some(Regex::new(DISAMBIGUATION_TITLES).unwrap()),
+        }
+    }
+    let where_str = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
     let mut stmt = conn
         .prepare(&format!(
-            "SELECT {groupby}, COUNT(*) FROM WikiLink \
-            GROUP BY {groupby} ORDER BY count(*) DESC LIMIT {top}"
+            "SELECT counts.id, counts.num, WikiPage.page_title FROM \
+            (SELECT {groupby} AS id, COUNT(*) AS num FROM WikiLink GROUP BY {groupby}) AS counts \
+            JOIN WikiPage ON WikiPage.page_id = counts.id {where_str} \
+            ORDER BY counts.num DESC"
         ))
         .unwrap();
-
     let rows = stmt
-        .query_map([], |row| Ok((row.get(0).unwrap(), row.get(1).unwrap())))
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?))
+        })
         .unwrap();
 
     for row in rows {
-        link_count.push(row.unwrap())
+        let (page_id, count, page_title) = row.unwrap();
+        if !filter.excludes_title(&page_title, disambiguation.as_ref()) {
+            link_count.push((page_id, count));
+        }
+        if link_count.len() >= top {
+            break;
+        }
     }
 
     link_count
@@ -308,3 +421,71 @@ pub fn top_link_ids(top: usize, db_path: impl AsRef<Path>) -> FxHashSet<PageId>
 
     link_count
 }
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::sqlite::{page_links, page_props};
+    use crate::stats::queries::{RankingFilter, select_link_count_groupby};
+    use crate::test_utils::{insert_links, insert_pages};
+
+    #[test]
+    fn test_filtered_ranking() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test_database.sqlite");
+        let conn = Connection::open(&path).unwrap();
+        page_links::db_setup(&conn);
+        let pages = [
+            (1, "Berlin", false),
+            (2, "1999", false),
+            (3, "Paris_(disambiguation)", false),
+            (4, "List_of_cities", false),
+            (5, "Hauptstadt", true),
+            (6, "Rome", false),
+            (7, "Vienna", false),
+        ];
+        insert_pages(&conn, &pages);
+        // page i is linked by i + 1 pages, so the ranking is 7, 6, ..., 1
+        let links: Vec<(u32, u32)> = pages
+            .iter()
+            .flat_map(|(page_id, _, _)| (0..=*page_id).map(|from| (100 + from, *page_id)))
+            .collect();
+        insert_links(&conn, &links);
+
+        let ranking = |top, filter: &RankingFilter| -> Vec<u64> {
+            select_link_count_groupby(top, &path, "WikiLink.page_link", filter)
+                .into_iter()
+                .map(|(page_id, _)| page_id)
+                .collect()
+        };
+
+        assert_eq!(ranking(3, &RankingFilter::default()), vec![7, 6, 5]);
+        assert_eq!(
+            ranking(3, &RankingFilter::default_exclusions()),
+            vec![7, 6, 1]
+        );
+        assert_eq!(
+            ranking(10, &RankingFilter::default_exclusions()),
+            vec![7, 6, 1]
+        );
+
+        let only_redirects = RankingFilter::new(&[] as &[&str], true, false).unwrap();
+        assert_eq!(ranking(4, &only_redirects), vec![7, 6, 4, 3]);
+        let titles = RankingFilter::new(&["^R", "^V"], false, false).unwrap();
+        assert_eq!(ranking(2, &titles), vec![5, 4]);
+        assert!(RankingFilter::new(&["("], false, false).is_err());
+
+        // with page props, disambiguation pages are the marked ones instead of the titles
+        page_props::db_setup(&conn);
+        conn.execute(
+            "INSERT INTO WikiPageProps (page_id, prop_name, value) VALUES (6, 'disambiguation', '')",
+            (),
+        )
+        .unwrap();
+        assert_eq!(
+            ranking(10, &RankingFilter::default_exclusions()),
+            vec![7, 3, 1]
+        );
+    }
+}
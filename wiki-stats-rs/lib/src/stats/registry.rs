@@ -0,0 +1,1066 @@
+use std::cmp::Reverse;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::bail;
+use fxhash::FxHashMap;
+use log::warn;
+use parse_mediawiki_sql::field_types::PageId;
+use rusqlite::{Connection, OpenFlags};
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use tokio::runtime::Handle;
+use tokio::time::Instant;
+
+use crate::{
+    WikiIdent,
+    calc::{
+        cycles::most_mutual_links,
+        distance_matrix::{DistanceMatrix, compute_distance_matrix},
+        pagerank::PageRanking,
+        redirects::analyze_redirects,
+    },
+    sqlite::{
+        red_links::most_wanted_pages,
+        redirects::{load_redirects, most_linked_redirects, most_redirected},
+        table_exists,
+        title_id_conv::page_id_to_title,
+    },
+    stats::{
+        Stats,
+        queries::{
+            RankingFilter, get_num_dead_orphan_pages, get_num_dead_pages, get_num_linked_redirects,
+            get_num_orphan_pages, longest_name, select_link_count_groupby,
+        },
+        samples::{BfsSample, BiBfsSample, sample_bfs_stats, sample_bidirectional_bfs_stats},
+        stats::{
+            LinkCount, Page, PageTitle, RedirectChain, RedirectExamples, StatRecord, WantedPage,
+            num_disambiguation_pages_stat, num_links_stat, num_mutual_links_stat, num_pages_stat,
+            num_red_links_stat, num_redirects_stat,
+        },
+        titles::{TitleAnalysis, title_analysis},
+        utils::{GLOBAL, max_min_value_record},
+    },
+};
+
+/// A statistic computed for every wiki, stored in the stats json as a [`StatRecord`] under its name
+pub trait Stat: Send + Sync + 'static {
+    type Value: Serialize + DeserializeOwned + JsonSchema + Debug + Send + 'static;
+
+    /// Key of the record in the stats json, also used to select stats from the cli
+    fn name(&self) -> &'static str;
+
+    /// Value of a single wiki
+    fn compute(&self, wiki: WikiIdent) -> Self::Value;
+
+    /// Adds the global entry to a record of all wikis (without a global entry). Default: none
+    fn merge_global(&self, _record: &mut StatRecord<Self::Value>) {}
+
+    /// Expensive stats are only computed when selected by name (or by their own cli command)
+    fn on_demand(&self) -> bool {
+        false
+    }
+
+    /// Computes the wikis one after another, for stats that already use all cores per wiki
+    fn sequential(&self) -> bool {
+        false
+    }
+
+    /// Options the values depend on, e.g. the size of a ranking. They are stored with the record,
+    /// records computed with other settings are not reused
+    fn settings(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Object safe version of [`Stat`], working on the json records, so all stats fit in one registry
+pub trait DynStat: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// See [`Stat::on_demand`]
+    fn on_demand(&self) -> bool;
+
+    /// See [`Stat::settings`]
+    fn settings(&self) -> Option<String>;
+
+    /// The record of all wikis. Wikis already in the stored record prev are not computed again,
+    /// the others are computed in parallel
+    fn compute_record(&self, wikis: &[WikiIdent], prev: Option<&Value>) -> Value {
+        self.compute_record_timed(wikis, prev).0
+    }
+
+    /// [`DynStat::compute_record`], with how long each of the computed wikis took
+    fn compute_record_timed(
+        &self,
+        wikis: &[WikiIdent],
+        prev: Option<&Value>,
+    ) -> (Value, Vec<(String, Duration)>);
+
+    /// Replaces the global entry of a record with the merge of its wikis. Records that don't fit
+    /// the stat are left as they are
+    fn merge_global_record(&self, record: &mut Value);
+
+    /// Json schema of the record
+    fn schema(&self, generator: &mut SchemaGenerator) -> Schema;
+}
+
+impl<S: Stat> DynStat for S {
+    fn name(&self) -> &'static str {
+        Stat::name(self)
+    }
+
+    fn on_demand(&self) -> bool {
+        Stat::on_demand(self)
+    }
+
+    fn settings(&self) -> Option<String> {
+        Stat::settings(self)
+    }
+
+    fn compute_record_timed(
+        &self,
+        wikis: &[WikiIdent],
+        prev: Option<&Value>,
+    ) -> (Value, Vec<(String, Duration)>) {
+        // a record stored in an outdated format is computed from scratch
+        let mut record: StatRecord<S::Value> = prev
+            .and_then(|prev| serde_json::from_value(prev.clone()).ok())
+            .unwrap_or_default();
+        record.remove(GLOBAL);
+
+        let compute = |wiki: &WikiIdent| {
+            let t1 = Instant::now();
+            let value = self.compute(wiki.clone());
+            (wiki.wiki_name.clone(), value, t1.elapsed())
+        };
+        let missing = wikis
+            .iter()
+            .filter(|wiki| !record.contains_key(&wiki.wiki_name));
+        let values: Vec<(String, S::Value, Duration)> = if self.sequential() {
+            missing.map(compute).collect()
+        } else {
+            let compute = &compute;
+            thread::scope(|scope| {
+                let handles: Vec<_> = missing
+                    .map(|wiki| scope.spawn(move || compute(wiki)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("can't join thread"))
+                    .collect()
+            })
+        };
+
+        let mut durations = Vec::with_capacity(values.len());
+        for (wiki_name, value, duration) in values {
+            durations.push((wiki_name.clone(), duration));
+            record.insert(wiki_name, value);
+        }
+        self.merge_global(&mut record);
+
+        (
+            serde_json::to_value(record).expect("Failed serializing stat record"),
+            durations,
+        )
+    }
+
+    fn merge_global_record(&self, record: &mut Value) {
+        let Ok(mut merged) = serde_json::from_value::<StatRecord<S::Value>>(record.clone()) else {
+            return;
+        };
+        merged.remove(GLOBAL);
+        self.merge_global(&mut merged);
+        *record = serde_json::to_value(merged).expect("Failed serializing stat record");
+    }
+
+    fn schema(&self, generator: &mut SchemaGenerator) -> Schema {
+        generator.subschema_for::<StatRecord<S::Value>>()
+    }
+}
+
+/// A stat made of a compute function and a merge function, like the ones of [`all_stats`]
+pub struct FnStat<T> {
+    name: &'static str,
+    compute: fn(WikiIdent) -> T,
+    merge_global: fn(&mut StatRecord<T>),
+}
+
+impl<T> FnStat<T> {
+    pub fn new(
+        name: &'static str,
+        compute: fn(WikiIdent) -> T,
+        merge_global: fn(&mut StatRecord<T>),
+    ) -> Self {
+        Self {
+            name,
+            compute,
+            merge_global,
+        }
+    }
+}
+
+impl<T> Stat for FnStat<T>
+where
+    T: Serialize + DeserializeOwned + JsonSchema + Debug + Send + Sync + 'static,
+{
+    type Value = T;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn compute(&self, wiki: WikiIdent) -> T {
+        (self.compute)(wiki)
+    }
+
+    fn merge_global(&self, record: &mut StatRecord<T>) {
+        (self.merge_global)(record)
+    }
+}
+
+/// Pages with the most incoming (groupby WikiLink.page_link) or outgoing links (WikiLink.page_id)
+pub struct RankingStat {
+    name: &'static str,
+    /// Shown in the progress output
+    label: &'static str,
+    groupby: &'static str,
+    top: usize,
+    filter: RankingFilter,
+}
+
+impl Stat for RankingStat {
+    type Value = Vec<LinkCount>;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn compute(&self, wiki_ident: WikiIdent) -> Vec<LinkCount> {
+        let t1 = Instant::now();
+        let name = wiki_ident.wiki_name;
+        let db_path = wiki_ident.db_path;
+        let label = self.label;
+
+        println!("{label}: {name:?}");
+        let conn = Connection::open(&db_path).unwrap();
+        let res = select_link_count_groupby(self.top, &db_path, self.groupby, &self.filter)
+            .into_iter()
+            .map(|(page_id, count)| {
+                let page_title = page_id_to_title(&PageId(page_id as u32), &conn)
+                    .unwrap_or_else(|| panic!("Failed retrieving page title from id {page_id}"))
+                    .0;
+                LinkCount {
+                    page_title,
+                    page_id,
+                    wiki_name: name.to_string(),
+                    count,
+                }
+            })
+            .collect();
+        println!("DONE. {:?} {label}: {name:?}", t1.elapsed());
+
+        res
+    }
+
+    fn merge_global(&self, record: &mut StatRecord<Vec<LinkCount>>) {
+        global_max_list(record)
+    }
+
+    fn settings(&self) -> Option<String> {
+        Some(format!("top={} {}", self.top, self.filter))
+    }
+}
+
+/// Pages with the most mutual links (a -> b and b -> a)
+pub struct MutualLinksStat {
+    top: usize,
+}
+
+impl Stat for MutualLinksStat {
+    type Value = Vec<LinkCount>;
+
+    fn name(&self) -> &'static str {
+        "most_mutual_links"
+    }
+
+    fn compute(&self, wiki_ident: WikiIdent) -> Vec<LinkCount> {
+        let t1 = Instant::now();
+        let name = wiki_ident.wiki_name;
+        let db_path = wiki_ident.db_path;
+
+        println!("Top mutual links: {name:?}");
+        let conn = Connection::open(&db_path).unwrap();
+
+        let res = most_mutual_links(&conn, self.top)
+            .into_iter()
+            .map(|(page_id, count)| {
+                let page_title = page_id_to_title(&page_id, &conn).unwrap().0;
+                LinkCount {
+                    page_title,
+                    page_id: page_id.0 as u64,
+                    wiki_name: name.to_string(),
+                    count,
+                }
+            })
+            .collect();
+        println!("DONE. {:?} Top mutual links: {name:?}", t1.elapsed());
+
+        res
+    }
+
+    fn merge_global(&self, record: &mut StatRecord<Vec<LinkCount>>) {
+        global_max_list(record)
+    }
+
+    fn settings(&self) -> Option<String> {
+        Some(format!("top={}", self.top))
+    }
+}
+
+/// The [`RedirectStats`] by database, shared by the redirect stats of a registry so the redirects
+/// of a wiki are loaded and analyzed once
+type RedirectStatsCache = Arc<Mutex<FxHashMap<PathBuf, Arc<OnceLock<RedirectStats>>>>>;
+
+/// One of the [`RedirectStats`]
+pub struct RedirectStat<T> {
+    name: &'static str,
+    value: fn(&RedirectStats) -> T,
+    merge_global: fn(&mut StatRecord<T>),
+    cache: RedirectStatsCache,
+}
+
+impl<T> RedirectStat<T> {
+    fn new(
+        name: &'static str,
+        value: fn(&RedirectStats) -> T,
+        merge_global: fn(&mut StatRecord<T>),
+        cache: &RedirectStatsCache,
+    ) -> Self {
+        Self {
+            name,
+            value,
+            merge_global,
+            cache: cache.clone(),
+        }
+    }
+}
+
+impl<T> Stat for RedirectStat<T>
+where
+    T: Serialize + DeserializeOwned + JsonSchema + Debug + Send + Sync + 'static,
+{
+    type Value = T;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn compute(&self, wiki: WikiIdent) -> T {
+        // the other redirect stats of the wiki wait for the first one instead of analyzing again
+        let stats = self
+            .cache
+            .lock()
+            .unwrap()
+            .entry(wiki.db_path.clone())
+            .or_default()
+            .clone();
+        (self.value)(stats.get_or_init(|| redirect_stats(&wiki)))
+    }
+
+    fn merge_global(&self, record: &mut StatRecord<T>) {
+        (self.merge_global)(record)
+    }
+}
+
+/// Bfs from random pages of each wiki (quite expensive, see [`sample_bfs_stats`])
+pub struct BfsSampleStat {
+    pub sample_size: usize,
+    pub num_threads: usize,
+    /// Pages whose links are cached, all if None
+    pub cache_max_size: Option<usize>,
+    /// Leaves disambiguation pages out of the random start pages
+    pub exclude_disambiguation: bool,
+}
+
+impl Default for BfsSampleStat {
+    fn default() -> Self {
+        Self {
+            sample_size: 500,
+            num_threads: 200,
+            cache_max_size: None,
+            exclude_disambiguation: false,
+        }
+    }
+}
+
+impl Stat for BfsSampleStat {
+    type Value = BfsSample;
+
+    fn name(&self) -> &'static str {
+        "bfs_sample_stats"
+    }
+
+    fn compute(&self, wiki: WikiIdent) -> BfsSample {
+        sample_bfs_stats(
+            wiki,
+            self.sample_size,
+            self.num_threads,
+            self.cache_max_size,
+            self.exclude_disambiguation,
+        )
+    }
+
+    fn on_demand(&self) -> bool {
+        true
+    }
+
+    fn sequential(&self) -> bool {
+        true
+    }
+}
+
+/// Bidirectional bfs between random pages of each wiki. Computed on the tokio runtime it is
+/// called from
+pub struct BiBfsSampleStat {
+    pub sample_size: usize,
+    pub num_threads: usize,
+    /// Leaves disambiguation pages out of the random page pairs
+    pub exclude_disambiguation: bool,
+}
+
+impl Default for BiBfsSampleStat {
+    fn default() -> Self {
+        Self {
+            sample_size: 500,
+            num_threads: 200,
+            exclude_disambiguation: false,
+        }
+    }
+}
+
+impl Stat for BiBfsSampleStat {
+    type Value = BiBfsSample;
+
+    fn name(&self) -> &'static str {
+        "bi_bfs_sample_stats"
+    }
+
+    fn compute(&self, wiki: WikiIdent) -> BiBfsSample {
+        Handle::current().block_on(sample_bidirectional_bfs_stats(
+            wiki,
+            self.sample_size,
+            self.num_threads,
+            self.exclude_disambiguation,
+        ))
+    }
+
+    fn on_demand(&self) -> bool {
+        true
+    }
+
+    fn sequential(&self) -> bool {
+        true
+    }
+}
+
+/// Distances between the top pages of each wiki (expensive, one bfs per page)
+pub struct DistanceMatrixStat {
+    pub num_pages: usize,
+    pub ranking: PageRanking,
+    /// Threads per bfs
+    pub num_threads: usize,
+}
+
+impl Default for DistanceMatrixStat {
+    fn default() -> Self {
+        Self {
+            num_pages: 50,
+            ranking: PageRanking::InDegree,
+            num_threads: 8,
+        }
+    }
+}
+
+impl Stat for DistanceMatrixStat {
+    type Value = DistanceMatrix;
+
+    fn name(&self) -> &'static str {
+        "distance_matrix"
+    }
+
+    fn compute(&self, wiki: WikiIdent) -> DistanceMatrix {
+        compute_distance_matrix(wiki, self.num_pages, self.ranking, self.num_threads)
+    }
+
+    fn on_demand(&self) -> bool {
+        true
+    }
+
+    fn sequential(&self) -> bool {
+        true
+    }
+}
+
+/// Size of the most_linked, most_links and most_mutual_links rankings and the pages left out of
+/// the filtered versions (most_linked_filtered, most_links_filtered)
+#[derive(Debug, Clone)]
+pub struct RankingOptions {
+    pub top: usize,
+    pub filter: RankingFilter,
+}
+
+impl Default for RankingOptions {
+    fn default() -> Self {
+        Self {
+            top: 10,
+            filter: RankingFilter::default_exclusions(),
+        }
+    }
+}
+
+/// All registered stats with the default ranking options
+pub fn all_stats() -> Vec<Box<dyn DynStat>> {
+    all_stats_with(&RankingOptions::default())
+}
+
+/// All registered stats, in the order of the stats json. [`Stat::on_demand`] ones are computed
+/// with their default options, their cli commands use their own
+pub fn all_stats_with(ranking: &RankingOptions) -> Vec<Box<dyn DynStat>> {
+    let ranking_stat = |name, label, groupby, filter: &RankingFilter| RankingStat {
+        name,
+        label,
+        groupby,
+        top: ranking.top,
+        filter: filter.clone(),
+    };
+    let unfiltered = RankingFilter::default();
+    let redirect_cache = RedirectStatsCache::default();
+
+    vec![
+        Box::new(FnStat::new("num_pages", num_pages_stat, global_adder)),
+        Box::new(FnStat::new(
+            "num_redirects",
+            num_redirects_stat,
+            global_adder,
+        )),
+        Box::new(FnStat::new("num_links", num_links_stat, global_adder)),
+        Box::new(FnStat::new(
+            "num_linked_redirects",
+            get_num_linked_redirects,
+            global_adder,
+        )),
+        Box::new(ranking_stat(
+            "most_linked",
+            "Top linked",
+            "WikiLink.page_link",
+            &unfiltered,
+        )),
+        Box::new(ranking_stat(
+            "most_links",
+            "Top links",
+            "WikiLink.page_id",
+            &unfiltered,
+        )),
+        Box::new(ranking_stat(
+            "most_linked_filtered",
+            "Top linked filtered",
+            "WikiLink.page_link",
+            &ranking.filter,
+        )),
+        Box::new(ranking_stat(
+            "most_links_filtered",
+            "Top links filtered",
+            "WikiLink.page_id",
+            &ranking.filter,
+        )),
+        Box::new(FnStat::new(
+            "longest_name",
+            |wiki| longest_name(wiki, true),
+            global_longest_name,
+        )),
+        Box::new(FnStat::new(
+            "longest_name_no_redirect",
+            |wiki| longest_name(wiki, false),
+            global_longest_name,
+        )),
+        Box::new(FnStat::new(
+            "num_dead_pages",
+            get_num_dead_pages,
+            global_adder,
+        )),
+        Box::new(FnStat::new(
+            "num_orphan_pages",
+            get_num_orphan_pages,
+            global_adder,
+        )),
+        Box::new(FnStat::new(
+            "num_dead_orphan_pages",
+            get_num_dead_orphan_pages,
+            global_adder,
+        )),
+        // link pairs a -> b, b -> a, counted once per pair
+        Box::new(FnStat::new(
+            "num_mutual_links",
+            num_mutual_links_stat,
+            global_adder,
+        )),
+        Box::new(MutualLinksStat { top: ranking.top }),
+        // redirects whose target is a redirect, with the chain up to the target of the target
+        Box::new(RedirectStat::new(
+            "double_redirects",
+            |stats| stats.double_redirects.clone(),
+            global_sum_examples,
+            &redirect_cache,
+        )),
+        Box::new(RedirectStat::new(
+            "redirect_loops",
+            |stats| stats.redirect_loops.clone(),
+            global_sum_examples,
+            &redirect_cache,
+        )),
+        Box::new(RedirectStat::new(
+            "longest_redirect_chain",
+            |stats| stats.longest_redirect_chain.clone(),
+            global_longest_chain,
+            &redirect_cache,
+        )),
+        Box::new(FnStat::new(
+            "most_redirected",
+            top_ten_redirected,
+            global_max_list,
+        )),
+        Box::new(FnStat::new(
+            "most_linked_redirects",
+            top_ten_linked_redirects,
+            global_max_list,
+        )),
+        Box::new(FnStat::new(
+            "title_analysis",
+            title_analysis,
+            global_title_analysis,
+        )),
+        // links to pages that don't exist, 0 for dbs created without the WikiRedLink table
+        Box::new(FnStat::new(
+            "num_red_links",
+            num_red_links_stat,
+            global_adder,
+        )),
+        Box::new(FnStat::new(
+            "most_wanted_pages",
+            top_ten_wanted_pages,
+            global_max_wanted,
+        )),
+        // 0 for dbs created without page props
+        Box::new(FnStat::new(
+            "num_disambiguation_pages",
+            num_disambiguation_pages_stat,
+            global_adder,
+        )),
+        Box::new(BfsSampleStat::default()),
+        Box::new(BiBfsSampleStat::default()),
+        Box::new(DistanceMatrixStat::default()),
+    ]
+}
+
+/// Which stats of the registry to run: only the ones in `only` (all but the
+/// [`Stat::on_demand`] ones if empty), without `skip`
+#[derive(Debug, Clone, Default)]
+pub struct StatSelection {
+    pub only: Vec<String>,
+    pub skip: Vec<String>,
+    pub ranking: RankingOptions,
+}
+
+impl StatSelection {
+    /// The selected stats. Fails on names that are not in the registry
+    pub fn stats(&self) -> anyhow::Result<Vec<Box<dyn DynStat>>> {
+        let all = all_stats_with(&self.ranking);
+        let unknown: Vec<&String> = self
+            .only
+            .iter()
+            .chain(&self.skip)
+            .filter(|name| !all.iter().any(|stat| stat.name() == name.as_str()))
+            .collect();
+        if !unknown.is_empty() {
+            bail!(
+                "Unknown stats {unknown:?}. Available: {:?}",
+                all.iter().map(|stat| stat.name()).collect::<Vec<_>>()
+            );
+        }
+
+        Ok(all
+            .into_iter()
+            .filter(|stat| {
+                if self.only.is_empty() {
+                    !stat.on_demand()
+                } else {
+                    self.only.iter().any(|n| n == stat.name())
+                }
+            })
+            .filter(|stat| !self.skip.iter().any(|n| n == stat.name()))
+            .collect())
+    }
+}
+
+/// Json schema of the stats file, with the records of all registered stats
+pub fn stats_schema() -> Schema {
+    let mut generator = SchemaGenerator::default();
+    let records: Vec<(&str, Schema, bool)> = all_stats()
+        .iter()
+        .map(|stat| (stat.name(), stat.schema(&mut generator), stat.on_demand()))
+        .collect();
+
+    let mut schema = generator.into_root_schema_for::<Stats>();
+    let object = schema.ensure_object();
+    let properties = object
+        .entry("properties")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .unwrap();
+    for (name, record_schema, _) in &records {
+        properties.insert(name.to_string(), record_schema.clone().to_value());
+    }
+    // records are flattened into the stats, so the derived schema does not require them
+    let required = object
+        .entry("required")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .unwrap();
+    for (name, _, _) in records.iter().filter(|(_, _, on_demand)| !on_demand) {
+        required.push(json!(name));
+    }
+    schema
+}
+
+/// ((max wiki, value), (min wiki, value)) of a u64 record, None if it has no wiki
+pub(crate) fn max_min_u64(
+    record: Option<StatRecord<u64>>,
+) -> Option<((String, u64), (String, u64))> {
+    let record = record?;
+    if record.keys().all(|wiki_name| wiki_name == GLOBAL) {
+        return None;
+    }
+    Some(max_min_value_record(&record, |a, b| a.cmp(b)))
+}
+
+fn global_adder(record: &mut StatRecord<u64>) {
+    let v = record.values().sum();
+    record.insert(GLOBAL.to_string(), v);
+}
+
+fn global_max_list(record: &mut StatRecord<Vec<LinkCount>>) {
+    let mut global_list = vec![];
+    for list in record.clone().into_values() {
+        global_list.extend(list);
+    }
+    global_list.sort_by_key(|c| Reverse(c.count)); // descending
+    record.insert(GLOBAL.to_string(), global_list);
+}
+
+fn global_max_wanted(record: &mut StatRecord<Vec<WantedPage>>) {
+    let mut global_list = vec![];
+    for list in record.clone().into_values() {
+        global_list.extend(list);
+    }
+    global_list.sort_by_key(|c| Reverse(c.count)); // descending
+    record.insert(GLOBAL.to_string(), global_list);
+}
+
+fn global_sum_examples(record: &mut StatRecord<RedirectExamples>) {
+    let count = record.values().map(|examples| examples.count).sum();
+    record.insert(
+        GLOBAL.to_string(),
+        RedirectExamples {
+            count,
+            examples: vec![],
+        },
+    );
+}
+
+fn global_longest_chain(record: &mut StatRecord<RedirectChain>) {
+    let ((_, max_element), _) = max_min_value_record(record, |c1, c2| c1.length.cmp(&c2.length));
+    record.insert(GLOBAL.to_string(), max_element);
+}
+
+fn global_title_analysis(record: &mut StatRecord<TitleAnalysis>) {
+    let merged = TitleAnalysis::merge(record.values());
+    record.insert(GLOBAL.to_string(), merged);
+}
+
+fn global_longest_name(record: &mut StatRecord<Page>) {
+    let ((_, max_element), _) = max_min_value_record(record, |p1, p2| {
+        p1.page_title.len().cmp(&p2.page_title.len())
+    });
+    record.insert(GLOBAL.to_string(), max_element);
+}
+
+fn titles(page_ids: &[PageId], conn: &Connection) -> Vec<PageTitle> {
+    page_ids
+        .iter()
+        .map(|page_id| {
+            page_id_to_title(page_id, conn)
+                .map(|title| title.0)
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// The wiki databases are only read by the stats, they may be open in other processes
+fn open_read_only(db_path: &Path) -> Connection {
+    Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .unwrap_or_else(|_| panic!("Failed opening {db_path:?}"))
+}
+
+/// Double redirects, redirect loops and the longest chain of a wiki, from one
+/// [`analyze_redirects`]
+#[derive(Clone)]
+pub struct RedirectStats {
+    double_redirects: RedirectExamples,
+    redirect_loops: RedirectExamples,
+    longest_redirect_chain: RedirectChain,
+}
+
+fn redirect_stats(wiki_ident: &WikiIdent) -> RedirectStats {
+    let conn = open_read_only(&wiki_ident.db_path);
+    let redirects = load_redirects(&conn);
+    let analysis = analyze_redirects(&redirects);
+
+    let double_redirects = analysis
+        .double_redirects
+        .iter()
+        .take(10)
+        .map(|page_id| {
+            let target = redirects[page_id];
+            titles(&[*page_id, target, redirects[&target]], &conn)
+        })
+        .collect();
+    let loops = analysis
+        .loops
+        .iter()
+        .take(10)
+        .map(|cycle| titles(cycle, &conn))
+        .collect();
+
+    RedirectStats {
+        double_redirects: RedirectExamples {
+            count: analysis.double_redirects.len() as u64,
+            examples: double_redirects,
+        },
+        redirect_loops: RedirectExamples {
+            count: analysis.loops.len() as u64,
+            examples: loops,
+        },
+        longest_redirect_chain: RedirectChain {
+            wiki_name: wiki_ident.wiki_name.clone(),
+            length: analysis.longest_chain.len().saturating_sub(1) as u64,
+            pages: titles(&analysis.longest_chain, &conn),
+        },
+    }
+}
+
+fn link_counts(counts: Vec<(PageId, u64)>, wiki_name: &str, conn: &Connection) -> Vec<LinkCount> {
+    counts
+        .into_iter()
+        .map(|(page_id, count)| LinkCount {
+            page_title: page_id_to_title(&page_id, conn).unwrap().0,
+            page_id: page_id.0 as u64,
+            wiki_name: wiki_name.to_string(),
+            count,
+        })
+        .collect()
+}
+
+fn top_ten_redirected(wiki_ident: WikiIdent) -> Vec<LinkCount> {
+    let conn = open_read_only(&wiki_ident.db_path);
+    link_counts(most_redirected(&conn, 10), &wiki_ident.wiki_name, &conn)
+}
+
+fn top_ten_linked_redirects(wiki_ident: WikiIdent) -> Vec<LinkCount> {
+    let conn = open_read_only(&wiki_ident.db_path);
+    link_counts(
+        most_linked_redirects(&conn, 10),
+        &wiki_ident.wiki_name,
+        &conn,
+    )
+}
+
+fn top_ten_wanted_pages(wiki_ident: WikiIdent) -> Vec<WantedPage> {
+    let t1 = Instant::now();
+    let name = wiki_ident.wiki_name;
+    let conn = Connection::open(&wiki_ident.db_path).unwrap();
+    if !table_exists(&conn, "WikiRedLink") {
+        warn!("[{name}] No WikiRedLink table, recreate the db for red link stats");
+        return vec![];
+    }
+
+    println!("Top wanted pages: {name:?}");
+    let res = most_wanted_pages(&conn, 10)
+        .into_iter()
+        .map(|(page_title, count)| WantedPage {
+            page_title,
+            wiki_name: name.to_string(),
+            count,
+        })
+        .collect();
+    println!("DONE. {:?} Top wanted pages: {name:?}", t1.elapsed());
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+
+    use crate::WikiIdent;
+    use crate::stats::registry::{
+        DynStat, FnStat, RankingOptions, StatSelection, all_stats, all_stats_with, global_adder,
+        stats_schema,
+    };
+
+    fn wiki(name: &str) -> WikiIdent {
+        WikiIdent {
+            wiki_name: name.to_string(),
+            db_path: PathBuf::from(format!("{name}_database.sqlite")),
+        }
+    }
+
+    #[test]
+    fn test_compute_record() {
+        let stat = FnStat::new(
+            "name_length",
+            |wiki| wiki.wiki_name.len() as u64,
+            global_adder,
+        );
+        let wikis = [wiki("dewiki"), wiki("enwiki"), wiki("jawiktionary")];
+
+        let record = stat.compute_record(&wikis, None);
+        assert_eq!(
+            record,
+            json!({"dewiki": 6, "enwiki": 6, "jawiktionary": 12, "global": 24})
+        );
+
+        // stored wikis are kept, the global entry is merged again
+        let prev = json!({"dewiki": 100, "global": 100});
+        let (record, durations) = stat.compute_record_timed(&wikis, Some(&prev));
+        assert_eq!(
+            record,
+            json!({"dewiki": 100, "enwiki": 6, "jawiktionary": 12, "global": 118})
+        );
+        let mut computed: Vec<&str> = durations.iter().map(|(w, _)| w.as_str()).collect();
+        computed.sort();
+        assert_eq!(computed, vec!["enwiki", "jawiktionary"]);
+
+        // a record that does not fit the stat is recomputed
+        let record = stat.compute_record(&wikis[..1], Some(&json!({"dewiki": "six"})));
+        assert_eq!(record, json!({"dewiki": 6, "global": 6}));
+    }
+
+    #[test]
+    fn test_stat_selection() {
+        let names = |selection: StatSelection| -> Vec<&'static str> {
+            selection
+                .stats()
+                .unwrap()
+                .iter()
+                .map(|stat| stat.name())
+                .collect()
+        };
+        let to_strings = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+
+        let num_default = all_stats().iter().filter(|stat| !stat.on_demand()).count();
+        assert_eq!(names(StatSelection::default()).len(), num_default);
+        assert!(!names(StatSelection::default()).contains(&"bfs_sample_stats"));
+        assert_eq!(
+            names(StatSelection {
+                only: to_strings(&["bfs_sample_stats"]),
+                ..Default::default()
+            }),
+            vec!["bfs_sample_stats"]
+        );
+        assert_eq!(
+            names(StatSelection {
+                only: to_strings(&["num_links", "num_pages"]),
+                skip: vec![],
+                ..Default::default()
+            }),
+            vec!["num_pages", "num_links"]
+        );
+        let skipped = names(StatSelection {
+            only: vec![],
+            skip: to_strings(&["most_linked"]),
+            ..Default::default()
+        });
+        assert_eq!(skipped.len(), num_default - 1);
+        assert!(!skipped.contains(&"most_linked"));
+
+        assert!(
+            StatSelection {
+                only: to_strings(&["num_pagez"]),
+                skip: vec![],
+                ..Default::default()
+            }
+            .stats()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_stat_settings() {
+        let settings = |ranking: &RankingOptions| -> Vec<(&str, Option<String>)> {
+            all_stats_with(ranking)
+                .iter()
+                .map(|stat| (stat.name(), stat.settings()))
+                .collect()
+        };
+        let default = settings(&RankingOptions::default());
+        let top = settings(&RankingOptions {
+            top: 20,
+            ..RankingOptions::default()
+        });
+        let changed: Vec<&str> = default
+            .iter()
+            .zip(&top)
+            .filter(|(a, b)| a != b)
+            .map(|((name, _), _)| *name)
+            .collect();
+        assert_eq!(
+            changed,
+            vec![
+                "most_linked",
+                "most_links",
+                "most_linked_filtered",
+                "most_links_filtered",
+                "most_mutual_links"
+            ]
+        );
+
+        let unfiltered = settings(&RankingOptions {
+            filter: Default::default(),
+            ..RankingOptions::default()
+        });
+        let changed: Vec<&str> = default
+            .iter()
+            .zip(&unfiltered)
+            .filter(|(a, b)| a != b)
+            .map(|((name, _), _)| *name)
+            .collect();
+        assert_eq!(changed, vec!["most_linked_filtered", "most_links_filtered"]);
+    }
+
+    #[test]
+    fn test_stats_schema() {
+        let schema = stats_schema().to_value();
+        let required = schema["required"].as_array().unwrap();
+        for stat in all_stats() {
+            assert!(schema["properties"].get(stat.name()).is_some());
+            assert_eq!(required.contains(&json!(stat.name())), !stat.on_demand());
+        }
+        assert!(required.contains(&json!("created_at")));
+    }
+}
@@ -16,9 +16,9 @@ use tokio::{sync::mpsc, time::Instant};
 
 use crate::{
     AvgDepthHistogram, DepthHistogram, WikiIdent,
-    calc::bfs::{SpBiStream, bfs, bfs_bidirectional, build_path},
+    calc::bfs::{SpBiStream, bfs, bfs_bidirectional, bfs_parallel, build_path},
     sqlite::{
-        page_links::get_cache,
+        page_links::{LinkCaches, get_cache},
         title_id_conv::{self, get_random_page},
     },
     stats::{
@@ -58,17 +58,24 @@ pub fn sample_bfs_stats(
     sample_size: usize,
     num_threads: usize,
     cache_max_size: Option<usize>,
+    exclude_disambiguation: bool,
 ) -> BfsSample {
     let t1 = Instant::now();
 
     let db_path = &wiki_ident.db_path;
     let wiki_name = wiki_ident.wiki_name;
 
-    let cache = get_cache(&db_path, cache_max_size, false);
+    // with every link in memory there is no need for the db fallback of bfs, so the direction
+    // optimizing bfs_parallel is used (one thread per sample, the samples run in parallel)
+    let full_caches = cache_max_size
+        .is_none()
+        .then(|| LinkCaches::load(db_path, None));
+    let arc_cache = match &full_caches {
+        Some(caches) => caches.outgoing.clone(),
+        None => Arc::new(get_cache(db_path, cache_max_size, false)),
+    };
     // let cache = load_link_to_map_db(db_path);
-    info!("Cache size: {}", cache.len());
-
-    let arc_cache = Arc::new(cache.clone());
+    info!("Cache size: {}", arc_cache.len());
 
     let num_threads = num_threads.clamp(1, sample_size); // at least 1 thread, at most sample_size threads
     info!("Sample size: {sample_size}");
@@ -83,7 +90,7 @@ pub fn sample_bfs_stats(
     let mut depth_histograms: Vec<FxHashMap<u32, f64>> = Vec::new();
 
     let pid_queue: Arc<ArrayQueue<PageId>> = Arc::new(ArrayQueue::new(sample_size));
-    for page in get_random_page(&db_path, sample_size as u32) {
+    for page in get_random_page(db_path, sample_size as u32, exclude_disambiguation) {
         pid_queue.push(PageId(page.id)).unwrap()
     }
 
@@ -114,6 +121,7 @@ pub fn sample_bfs_stats(
             let thread_sender = s.clone();
             let pid_queue = &pid_queue;
             let cache = &arc_cache;
+            let full_caches = &full_caches;
             let bar = &bfs_bar;
             let m = &m;
             let wiki_name: &String = &wiki_name;
@@ -125,7 +133,18 @@ pub fn sample_bfs_stats(
                     let start_link_id = pid_queue.pop().unwrap();
                     // println!("[{tid}]: {:?}", start_link_id);
 
-                    let result = bfs(&start_link_id, None, None, cache, &db_path);
+                    let result = match full_caches {
+                        Some(caches) => bfs_parallel(
+                            &start_link_id,
+                            None,
+                            None,
+                            &caches.outgoing,
+                            Some(&caches.incoming),
+                            caches.size,
+                            1,
+                        ),
+                        None => bfs(&start_link_id, None, None, cache, db_path),
+                    };
 
                     thread_sender.send((start_link_id, result)).unwrap();
 
@@ -219,6 +238,7 @@ pub async fn sample_bidirectional_bfs_stats(
     wiki_ident: WikiIdent,
     sample_size: usize,
     mut num_threads: usize,
+    exclude_disambiguation: bool,
 ) -> BiBfsSample {
     let t1 = Instant::now();
 
@@ -237,9 +257,14 @@ pub async fn sample_bidirectional_bfs_stats(
     let mut path_length_histogram: DepthHistogram = FxHashMap::default();
 
     let pid_queue: Arc<ArrayQueue<(PageId, PageId)>> = Arc::new(ArrayQueue::new(sample_size));
-    for (start_page, end_page) in get_random_page(&db_path, sample_size as u32)
-        .iter()
-        .zip(get_random_page(&db_path, sample_size as u32))
+    for (start_page, end_page) in
+        get_random_page(&db_path, sample_size as u32, exclude_disambiguation)
+            .iter()
+            .zip(get_random_page(
+                &db_path,
+                sample_size as u32,
+                exclude_disambiguation,
+            ))
     {
         if *start_page == end_page {
             continue;
@@ -274,7 +299,13 @@ pub async fn sample_bidirectional_bfs_stats(
                 let (start_link_id, end_link_id) = pid_queue.pop().unwrap();
                 // println!("[{tid}]: {:?}", start_link_id);
 
-                let stream = bfs_bidirectional(start_link_id, end_link_id, db_path.clone()).await;
+                let stream = bfs_bidirectional(
+                    start_link_id,
+                    end_link_id,
+                    db_path.clone(),
+                    LinkCaches::default(),
+                )
+                .await;
                 pin_mut!(stream);
                 let mut result = stream.next().await;
                 while let Some(v) = stream.next().await {
@@ -1,16 +1,23 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
 use fxhash::FxHashMap;
+use rusqlite::Connection;
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
 use std::fs;
 
 use crate::{
     WikiIdent,
-    stats::{
-        queries::count_from,
-        samples::{BfsSample, BiBfsSample},
+    calc::cycles::num_mutual_links,
+    sqlite::{
+        diff::DiffSummary, page_props::num_disambiguation_pages, red_links::num_red_links,
+        table_exists,
     },
+    stats::queries::count_from,
     web::WebWikiSize,
 };
 
@@ -21,24 +28,21 @@ pub type PageTitle = String;
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Stats {
-    pub num_pages: StatRecord<u64>,
-    pub num_redirects: StatRecord<u64>,
-    pub num_links: StatRecord<u64>,
-    pub num_linked_redirects: StatRecord<u64>,
-
-    pub most_linked: StatRecord<Vec<LinkCount>>,
-    pub most_links: StatRecord<Vec<LinkCount>>,
-
-    pub longest_name: StatRecord<Page>,
-    pub longest_name_no_redirect: StatRecord<Page>,
-
-    pub num_dead_pages: StatRecord<u64>,
-    pub num_orphan_pages: StatRecord<u64>,
-    pub num_dead_orphan_pages: StatRecord<u64>,
-
+    /// The records of the registered stats by name, e.g. num_pages (see [`crate::stats::registry::all_stats`])
+    #[serde(flatten)]
+    pub records: BTreeMap<String, Value>,
+    /// The settings the records were computed with, for the stats that have them
+    /// (see [`crate::stats::registry::Stat::settings`])
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stat_settings: BTreeMap<String, String>,
+
+    #[serde(default)]
     pub max_num_pages: (WikiName, u64),
+    #[serde(default)]
     pub min_num_pages: (WikiName, u64),
+    #[serde(default)]
     pub max_num_links: (WikiName, u64),
+    #[serde(default)]
     pub min_num_links: (WikiName, u64),
 
     /// utc timestamp
@@ -47,12 +51,19 @@ pub struct Stats {
     pub wikis: Vec<WikiName>,
     pub seconds_taken: u64,
 
-    // can take really long
-    pub bfs_sample_stats: Option<StatRecord<BfsSample>>,
-    pub bi_bfs_sample_stats: Option<StatRecord<BiBfsSample>>,
-
     pub web_wiki_sizes: Option<WebWikiSizes>,
     pub local_wiki_sizes: Option<WikiSizes>,
+
+    // changes since an older dump date. Not a registered stat: it is computed against the
+    // databases of another dump, and the summaries are only valid for that old dump date
+    pub link_churn: Option<LinkChurn>,
+}
+
+impl Stats {
+    /// The record of a registered stat, None if it is missing or has another type
+    pub fn record<T: DeserializeOwned>(&self, name: &str) -> Option<StatRecord<T>> {
+        serde_json::from_value(self.records.get(name)?.clone()).ok()
+    }
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -68,6 +79,13 @@ pub struct WikiSizes {
     pub tables: Vec<String>,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct LinkChurn {
+    /// The dump date the wikis were compared to
+    pub old_dump_date: String,
+    pub diffs: StatRecord<DiffSummary>,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct WebWikiSizes {
     pub sizes: Vec<WebWikiSize>,
@@ -89,6 +107,30 @@ pub struct Page {
     pub wiki_name: WikiName,
 }
 
+/// A page that doesn't exist, by the number of links to it
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct WantedPage {
+    pub page_title: PageTitle,
+    pub wiki_name: WikiName,
+    pub count: u64,
+}
+
+/// Number of cases, with up to ten of them as chains of page titles (none in the global entry)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct RedirectExamples {
+    pub count: u64,
+    pub examples: Vec<Vec<PageTitle>>,
+}
+
+/// The redirects of a chain followed by the page it ends at
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct RedirectChain {
+    pub wiki_name: WikiName,
+    /// Number of redirects
+    pub length: u64,
+    pub pages: Vec<PageTitle>,
+}
+
 pub fn num_pages_stat(wiki: WikiIdent) -> u64 {
     count_from("WikiPage", &wiki.db_path, "")
 }
@@ -101,6 +143,24 @@ pub fn num_links_stat(wiki: WikiIdent) -> u64 {
     count_from("WikiLink", &wiki.db_path, "")
 }
 
+pub fn num_mutual_links_stat(wiki: WikiIdent) -> u64 {
+    let conn = Connection::open(&wiki.db_path).unwrap();
+    num_mutual_links(&conn)
+}
+
+pub fn num_red_links_stat(wiki: WikiIdent) -> u64 {
+    let conn = Connection::open(&wiki.db_path).unwrap();
+    if !table_exists(&conn, "WikiRedLink") {
+        return 0;
+    }
+    num_red_links(&conn)
+}
+
+pub fn num_disambiguation_pages_stat(wiki: WikiIdent) -> u64 {
+    let conn = Connection::open(&wiki.db_path).unwrap();
+    num_disambiguation_pages(&conn).unwrap_or(0)
+}
+
 pub async fn get_local_wiki_sizes(base_path: impl AsRef<Path>, tables: &[&str]) -> WikiSizes {
     let download_path = base_path.as_ref().join("downloads");
 
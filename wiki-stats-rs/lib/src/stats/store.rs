@@ -0,0 +1,459 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::Duration;
+
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde_json::{Map, Value, json};
+
+use crate::stats::{
+    Stats,
+    registry::{all_stats, max_min_u64},
+    stats::StatRecord,
+    utils::GLOBAL,
+};
+
+/// Stats of the whole dump instead of single wikis, stored under the wiki name [`GLOBAL`]
+const DUMP_STATS: [&str; 3] = ["web_wiki_sizes", "local_wiki_sizes", STAT_SETTINGS];
+/// The settings of the stored records, see [`Stats::stat_settings`]
+pub(crate) const STAT_SETTINGS: &str = "stat_settings";
+const LINK_CHURN: &str = "link_churn";
+/// The dump date of [`crate::stats::stats::LinkChurn`], the diffs are stored per wiki
+const LINK_CHURN_OLD_DUMP_DATE: &str = "link_churn_old_dump_date";
+/// Fields of [`Stats`] that are derived from the stored values when loading
+const DERIVED_FIELDS: [&str; 8] = [
+    "max_num_pages",
+    "min_num_pages",
+    "max_num_links",
+    "min_num_links",
+    "created_at",
+    "dump_date",
+    "wikis",
+    "seconds_taken",
+];
+
+/// Waiting time for the lock of another writer
+const BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Value of a stat for a single wiki (or the global entry) of a dump
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredStat {
+    pub wiki_name: String,
+    pub dump_date: String,
+    pub stat_name: String,
+    pub value: Value,
+    /// utc timestamp
+    pub computed_at: i64,
+    /// How long computing the value took, None for imported stats
+    pub duration_s: Option<f64>,
+}
+
+impl StoredStat {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let value: String = row.get(3)?;
+        Ok(Self {
+            wiki_name: row.get(0)?,
+            dump_date: row.get(1)?,
+            stat_name: row.get(2)?,
+            value: serde_json::from_str(&value).expect("Failed deserializing stored stat"),
+            computed_at: row.get(4)?,
+            duration_s: row.get(5)?,
+        })
+    }
+}
+
+/// Stats of all dump dates in a sqlite database, one row per wiki, dump date and stat. Values are
+/// written per wiki, so several processes can compute the stats of different wikis at once. The
+/// stats json of a dump date is exported with [`StatsStore::load_stats`], which merges the global
+/// entries of the records from the stored wikis
+pub struct StatsStore {
+    conn: Connection,
+}
+
+impl StatsStore {
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let conn = Connection::open(path)
+            .unwrap_or_else(|_| panic!("Failed opening stats store at {path:?}"));
+        conn.busy_timeout(BUSY_TIMEOUT).unwrap();
+        conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+        conn.execute(
+            "CREATE TABLE if not exists StatValue (
+                wiki_name TEXT NOT NULL,
+                dump_date TEXT NOT NULL,
+                stat_name TEXT NOT NULL,
+                value TEXT NOT NULL,
+                computed_at INTEGER NOT NULL,
+                duration_s DOUBLE,
+                PRIMARY KEY (wiki_name, dump_date, stat_name)
+            )",
+            (),
+        )
+        .expect("Failed creating table");
+        Self { conn }
+    }
+
+    /// Inserts or replaces the value
+    pub fn put(&self, stat: &StoredStat) {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO StatValue
+                (wiki_name, dump_date, stat_name, value, computed_at, duration_s)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                (
+                    &stat.wiki_name,
+                    &stat.dump_date,
+                    &stat.stat_name,
+                    stat.value.to_string(),
+                    stat.computed_at,
+                    stat.duration_s,
+                ),
+            )
+            .expect("Failed storing stat");
+    }
+
+    /// Stores the values of the computed wikis of a record, with how long they took. The other
+    /// wikis of the record are left as they are, the global entry is not stored
+    pub fn put_record(
+        &self,
+        dump_date: &str,
+        stat_name: &str,
+        record: &Value,
+        durations: &[(String, Duration)],
+    ) {
+        let computed_at = chrono::Utc::now().timestamp();
+        let tx = self.conn.unchecked_transaction().unwrap();
+        for (wiki_name, duration) in durations {
+            if let Some(value) = record.get(wiki_name) {
+                self.put(&StoredStat {
+                    wiki_name: wiki_name.clone(),
+                    dump_date: dump_date.to_string(),
+                    stat_name: stat_name.to_string(),
+                    value: value.clone(),
+                    computed_at,
+                    duration_s: Some(duration.as_secs_f64()),
+                });
+            }
+        }
+        tx.commit().unwrap();
+    }
+
+    /// Deletes the values of all wikis of a stat, e.g. before storing it with other settings
+    pub fn delete_record(&self, dump_date: &str, stat_name: &str) {
+        self.conn
+            .execute(
+                "DELETE FROM StatValue WHERE dump_date = ?1 AND stat_name = ?2",
+                (dump_date, stat_name),
+            )
+            .expect("Failed deleting stat");
+    }
+
+    /// Stores a value of the whole dump, e.g. local_wiki_sizes
+    pub fn put_dump_stat(&self, dump_date: &str, stat_name: &str, value: Value) {
+        self.put(&StoredStat {
+            wiki_name: GLOBAL.to_string(),
+            dump_date: dump_date.to_string(),
+            stat_name: stat_name.to_string(),
+            value,
+            computed_at: chrono::Utc::now().timestamp(),
+            duration_s: None,
+        });
+    }
+
+    pub fn get(&self, wiki_name: &str, dump_date: &str, stat_name: &str) -> Option<StoredStat> {
+        self.conn
+            .query_row(
+                "SELECT wiki_name, dump_date, stat_name, value, computed_at, duration_s
+                FROM StatValue WHERE wiki_name = ?1 AND dump_date = ?2 AND stat_name = ?3",
+                (wiki_name, dump_date, stat_name),
+                StoredStat::from_row,
+            )
+            .optional()
+            .unwrap()
+    }
+
+    pub fn dump_dates(&self) -> Vec<String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT dump_date FROM StatValue ORDER BY dump_date")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    /// The values of a stat of a wiki over all dump dates, oldest first
+    pub fn history(&self, wiki_name: &str, stat_name: &str) -> Vec<StoredStat> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT wiki_name, dump_date, stat_name, value, computed_at, duration_s
+                FROM StatValue WHERE wiki_name = ?1 AND stat_name = ?2 ORDER BY dump_date",
+            )
+            .unwrap();
+        stmt.query_map((wiki_name, stat_name), StoredStat::from_row)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    /// Stores every value of the stats (e.g. of an existing stats json), replacing stored ones.
+    /// The global entries of the records are left out
+    pub fn import_stats(&self, stats: &Stats) {
+        let Value::Object(fields) = serde_json::to_value(stats).unwrap() else {
+            unreachable!("stats are serialized to an object")
+        };
+
+        let mut rows: Vec<(String, String, Value)> = vec![];
+        for (name, value) in fields {
+            if DERIVED_FIELDS.contains(&name.as_str()) || value.is_null() {
+                continue;
+            }
+            if DUMP_STATS.contains(&name.as_str()) {
+                rows.push((GLOBAL.to_string(), name, value));
+            } else if name == LINK_CHURN {
+                rows.push((
+                    GLOBAL.to_string(),
+                    LINK_CHURN_OLD_DUMP_DATE.to_string(),
+                    value["old_dump_date"].clone(),
+                ));
+                if let Some(Value::Object(diffs)) = value.get("diffs") {
+                    for (wiki_name, diff) in diffs {
+                        rows.push((wiki_name.clone(), name.clone(), diff.clone()));
+                    }
+                }
+            } else if let Value::Object(record) = value {
+                for (wiki_name, value) in record {
+                    if wiki_name != GLOBAL {
+                        rows.push((wiki_name, name.clone(), value));
+                    }
+                }
+            }
+        }
+
+        let tx = self.conn.unchecked_transaction().unwrap();
+        for (wiki_name, stat_name, value) in rows {
+            self.put(&StoredStat {
+                wiki_name,
+                dump_date: stats.dump_date.clone(),
+                stat_name,
+                value,
+                computed_at: stats.created_at,
+                duration_s: None,
+            });
+        }
+        tx.commit().unwrap();
+    }
+
+    /// The stats of a dump date as in the stats json, None if nothing is stored for it.
+    /// created_at is the time of the latest value, seconds_taken the sum of the durations.
+    /// The global entries of the records are merged from the wikis by the registered stats
+    pub fn load_stats(&self, dump_date: &str) -> Option<Stats> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT wiki_name, dump_date, stat_name, value, computed_at, duration_s
+                FROM StatValue WHERE dump_date = ?1",
+            )
+            .unwrap();
+        let stored: Vec<StoredStat> = stmt
+            .query_map([dump_date], StoredStat::from_row)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        if stored.is_empty() {
+            return None;
+        }
+
+        let mut fields: Map<String, Value> = Map::new();
+        let mut wikis: BTreeSet<String> = BTreeSet::new();
+        let mut churn_diffs: Map<String, Value> = Map::new();
+        let mut churn_old_dump_date = None;
+        let mut created_at = 0;
+        let mut seconds_taken = 0.0;
+
+        for stat in stored {
+            created_at = created_at.max(stat.computed_at);
+            seconds_taken += stat.duration_s.unwrap_or(0.0);
+            if stat.wiki_name != GLOBAL {
+                wikis.insert(stat.wiki_name.clone());
+            }
+
+            match stat.stat_name.as_str() {
+                name if DUMP_STATS.contains(&name) => {
+                    fields.insert(stat.stat_name, stat.value);
+                }
+                LINK_CHURN_OLD_DUMP_DATE => churn_old_dump_date = Some(stat.value),
+                LINK_CHURN => {
+                    churn_diffs.insert(stat.wiki_name, stat.value);
+                }
+                // global rows of stores written before the globals were merged on load
+                _ if stat.wiki_name == GLOBAL => {}
+                _ => {
+                    if let Value::Object(record) =
+                        fields.entry(stat.stat_name).or_insert_with(|| json!({}))
+                    {
+                        record.insert(stat.wiki_name, stat.value);
+                    }
+                }
+            }
+        }
+
+        for stat in all_stats() {
+            if let Some(record) = fields.get_mut(stat.name()) {
+                stat.merge_global_record(record);
+            }
+        }
+
+        if let Some(old_dump_date) = churn_old_dump_date {
+            fields.insert(
+                LINK_CHURN.to_string(),
+                json!({"old_dump_date": old_dump_date, "diffs": churn_diffs}),
+            );
+        }
+
+        let record =
+            |name: &str| serde_json::from_value::<StatRecord<u64>>(fields.get(name)?.clone()).ok();
+        let (max_num_pages, min_num_pages) = max_min_u64(record("num_pages")).unwrap_or_default();
+        let (max_num_links, min_num_links) = max_min_u64(record("num_links")).unwrap_or_default();
+
+        fields.extend([
+            ("max_num_pages".to_string(), json!(max_num_pages)),
+            ("min_num_pages".to_string(), json!(min_num_pages)),
+            ("max_num_links".to_string(), json!(max_num_links)),
+            ("min_num_links".to_string(), json!(min_num_links)),
+            ("created_at".to_string(), json!(created_at)),
+            ("dump_date".to_string(), json!(dump_date)),
+            ("wikis".to_string(), json!(wikis)),
+            (
+                "seconds_taken".to_string(),
+                json!(seconds_taken.round() as u64),
+            ),
+        ]);
+
+        Some(
+            serde_json::from_value(Value::Object(fields))
+                .expect("Failed deserializing stored stats"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::stats::Stats;
+    use crate::stats::store::StatsStore;
+
+    fn stats(dump_date: &str) -> Stats {
+        serde_json::from_value(json!({
+            "num_pages": {"dewiki": 100, "enwiki": 300, "global": 400},
+            "created_at": 10,
+            "dump_date": dump_date,
+            "wikis": ["dewiki", "enwiki"],
+            "seconds_taken": 1,
+            "local_wiki_sizes": {"sizes": [], "tables": ["page"]},
+            "stat_settings": {"most_linked": "top=10"},
+            "link_churn": {
+                "old_dump_date": "20240101",
+                "diffs": {"dewiki": {
+                    "added_pages": 1, "removed_pages": 0, "renamed_pages": 0,
+                    "added_links": 5, "removed_links": 2
+                }}
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_stats_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stats.sqlite");
+        let store = StatsStore::open(&path);
+        assert!(store.load_stats("20240201").is_none());
+
+        store.import_stats(&stats("20240201"));
+        store.import_stats(&stats("20240301"));
+        assert_eq!(store.dump_dates(), vec!["20240201", "20240301"]);
+
+        let loaded = store.load_stats("20240201").unwrap();
+        assert_eq!(loaded.wikis, vec!["dewiki", "enwiki"]);
+        assert_eq!(loaded.max_num_pages, ("enwiki".to_string(), 300));
+        assert_eq!(loaded.created_at, 10);
+        assert_eq!(
+            loaded.records["num_pages"],
+            json!({"dewiki": 100, "enwiki": 300, "global": 400})
+        );
+        assert!(loaded.local_wiki_sizes.is_some());
+        assert_eq!(loaded.stat_settings["most_linked"], "top=10");
+        let churn = loaded.link_churn.unwrap();
+        assert_eq!(churn.old_dump_date, "20240101");
+        assert_eq!(churn.diffs["dewiki"].added_links, 5);
+
+        // another writer adds a wiki, only its value is replaced
+        let other = StatsStore::open(&path);
+        other.put_record(
+            "20240301",
+            "num_pages",
+            &json!({"dewiki": 0, "frwiki": 200, "global": 200}),
+            &[("frwiki".to_string(), Duration::from_secs(3))],
+        );
+        let loaded = store.load_stats("20240301").unwrap();
+        assert_eq!(loaded.wikis, vec!["dewiki", "enwiki", "frwiki"]);
+        assert_eq!(
+            loaded.records["num_pages"],
+            json!({"dewiki": 100, "enwiki": 300, "frwiki": 200, "global": 600})
+        );
+        assert_eq!(loaded.seconds_taken, 3);
+        assert_eq!(
+            store
+                .get("frwiki", "20240301", "num_pages")
+                .unwrap()
+                .duration_s,
+            Some(3.0)
+        );
+
+        assert!(store.get("global", "20240301", "num_pages").is_none());
+
+        let history = store.history("enwiki", "num_pages");
+        let values: Vec<(&str, u64)> = history
+            .iter()
+            .map(|stat| (stat.dump_date.as_str(), stat.value.as_u64().unwrap()))
+            .collect();
+        assert_eq!(values, vec![("20240201", 300), ("20240301", 300)]);
+
+        store.delete_record("20240301", "num_pages");
+        assert!(store.get("frwiki", "20240301", "num_pages").is_none());
+        assert!(store.get("dewiki", "20240201", "num_pages").is_some());
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stats.sqlite");
+        let store_a = StatsStore::open(&path);
+        let store_b = StatsStore::open(&path);
+
+        // each writer only knows the wiki it computed
+        store_a.put_record(
+            "20240201",
+            "num_pages",
+            &json!({"dewiki": 100, "global": 100}),
+            &[("dewiki".to_string(), Duration::from_secs(1))],
+        );
+        store_b.put_record(
+            "20240201",
+            "num_pages",
+            &json!({"enwiki": 300, "global": 300}),
+            &[("enwiki".to_string(), Duration::from_secs(2))],
+        );
+
+        let loaded = store_a.load_stats("20240201").unwrap();
+        assert_eq!(
+            loaded.records["num_pages"],
+            json!({"dewiki": 100, "enwiki": 300, "global": 400})
+        );
+    }
+}
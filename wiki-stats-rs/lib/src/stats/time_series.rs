@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::stats::{Stats, stats::WikiName};
+
+/// Days of an average month, to scale the change between two dumps to a monthly one
+const DAYS_PER_MONTH: f64 = 365.25 / 12.0;
+
+/// metric -> wiki -> (dump date, value)
+type DumpValues<'a> = BTreeMap<String, BTreeMap<WikiName, Vec<(&'a str, f64)>>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct TimeSeriesPoint {
+    pub dump_date: String,
+    pub value: f64,
+    /// Change since the previous dump date
+    pub delta: Option<f64>,
+    /// delta relative to the previous value, None if that was 0
+    pub growth_rate: Option<f64>,
+    /// delta scaled to a month of 30.4 days, by the days between both dump dates
+    pub monthly_delta: Option<f64>,
+}
+
+/// The numeric stats of all stats files, per metric per wiki, ordered by dump date
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, JsonSchema)]
+pub struct TimeSeries {
+    pub dump_dates: Vec<String>,
+    pub metrics: BTreeMap<String, BTreeMap<WikiName, Vec<TimeSeriesPoint>>>,
+}
+
+impl TimeSeries {
+    /// Collects every record with numeric values (e.g. num_pages, including the global entry).
+    /// If several stats share a dump date, the most recently created one is used
+    pub fn from_stats(stats: &[Stats]) -> Self {
+        let mut by_date: BTreeMap<&str, &Stats> = BTreeMap::new();
+        for s in stats {
+            let entry = by_date.entry(s.dump_date.as_str()).or_insert(s);
+            if s.created_at > entry.created_at {
+                *entry = s;
+            }
+        }
+
+        let mut values: DumpValues = BTreeMap::new();
+        for (dump_date, s) in &by_date {
+            for (metric, record) in &s.records {
+                let Some(record) = record.as_object() else {
+                    continue;
+                };
+                for (wiki_name, value) in record {
+                    if let Some(value) = value.as_f64() {
+                        values
+                            .entry(metric.clone())
+                            .or_default()
+                            .entry(wiki_name.clone())
+                            .or_default()
+                            .push((dump_date, value));
+                    }
+                }
+            }
+        }
+
+        let metrics = values
+            .into_iter()
+            .map(|(metric, wikis)| {
+                let wikis = wikis
+                    .into_iter()
+                    .map(|(wiki_name, values)| (wiki_name, points(&values)))
+                    .collect();
+                (metric, wikis)
+            })
+            .collect();
+
+        TimeSeries {
+            dump_dates: by_date.keys().map(|date| date.to_string()).collect(),
+            metrics,
+        }
+    }
+
+    /// Reads all stats json files of the directory. Files that are no stats files are skipped
+    pub fn from_dir(stats_dir: impl AsRef<Path>) -> Self {
+        let mut stats = vec![];
+        for entry in fs::read_dir(&stats_dir)
+            .expect("Failed reading stats dir")
+            .flatten()
+        {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let parsed = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str::<Stats>(&json).map_err(|e| e.to_string()));
+            match parsed {
+                Ok(s) => stats.push(s),
+                Err(e) => warn!("Skipping {path:?}, not a stats file: {e}"),
+            }
+        }
+        info!(
+            "Loaded {} stats files from {:?}",
+            stats.len(),
+            stats_dir.as_ref()
+        );
+
+        Self::from_stats(&stats)
+    }
+}
+
+fn parse_dump_date(dump_date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(dump_date, "%Y%m%d").ok()
+}
+
+/// The points of (dump date, value) pairs sorted by dump date, with the changes to the previous one
+fn points(values: &[(&str, f64)]) -> Vec<TimeSeriesPoint> {
+    let mut points: Vec<TimeSeriesPoint> = Vec::with_capacity(values.len());
+    for (i, (dump_date, value)) in values.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|prev| values[prev]);
+        let delta = prev.map(|(_, prev_value)| value - prev_value);
+
+        let growth_rate = prev
+            .zip(delta)
+            .filter(|((_, prev_value), _)| *prev_value != 0.0)
+            .map(|((_, prev_value), delta)| delta / prev_value);
+
+        let days = prev.and_then(|(prev_date, _)| {
+            let days = (parse_dump_date(dump_date)? - parse_dump_date(prev_date)?).num_days();
+            (days > 0).then_some(days as f64)
+        });
+        let monthly_delta = delta
+            .zip(days)
+            .map(|(delta, days)| delta / days * DAYS_PER_MONTH);
+
+        points.push(TimeSeriesPoint {
+            dump_date: dump_date.to_string(),
+            value: *value,
+            delta,
+            growth_rate,
+            monthly_delta,
+        });
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::stats::Stats;
+    use crate::stats::time_series::TimeSeries;
+
+    fn stats(dump_date: &str, created_at: i64, num_pages: u64) -> Stats {
+        serde_json::from_value(json!({
+            "num_pages": {"dewiki": num_pages, "global": num_pages},
+            "longest_name": {"dewiki": {"page_title": "A", "page_id": 1, "wiki_name": "dewiki"}},
+            "created_at": created_at,
+            "dump_date": dump_date,
+            "wikis": ["dewiki"],
+            "seconds_taken": 1,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_time_series() {
+        let series = TimeSeries::from_stats(&[
+            stats("20240301", 0, 1000),
+            stats("20240201", 0, 800),
+            // rerun of the same dump date
+            stats("20240301", 1, 1100),
+            stats("20240401", 0, 1100),
+        ]);
+
+        assert_eq!(series.dump_dates, vec!["20240201", "20240301", "20240401"]);
+        assert_eq!(series.metrics.len(), 1);
+
+        let points = &series.metrics["num_pages"]["dewiki"];
+        let values: Vec<f64> = points.iter().map(|point| point.value).collect();
+        assert_eq!(values, vec![800.0, 1100.0, 1100.0]);
+
+        assert_eq!(points[0].delta, None);
+        assert_eq!(points[1].delta, Some(300.0));
+        assert_eq!(points[1].growth_rate, Some(300.0 / 800.0));
+        // 29 days in february 2024
+        let monthly_delta = points[1].monthly_delta.unwrap();
+        assert!((monthly_delta - 300.0 / 29.0 * 365.25 / 12.0).abs() < 1e-9);
+        assert_eq!(points[2].delta, Some(0.0));
+        assert_eq!(series.metrics["num_pages"]["global"].len(), 3);
+    }
+}
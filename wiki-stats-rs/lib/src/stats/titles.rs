@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use fxhash::FxHashMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::WikiIdent;
+
+/// Number of tokens and disambiguators kept
+const TOP_TOKENS: usize = 20;
+
+lazy_static! {
+    static ref YEAR: Regex = Regex::new(r"^\d{1,4}(_(BC|BCE|AD|CE)|年)?$").unwrap();
+    /// CJK dates (1月1日) and day first dates with a dot (1._Januar)
+    static ref DATE: Regex = Regex::new(r"^(\d{1,2}月\d{1,2}日|\d{1,2}\._\p{L}+)$").unwrap();
+    /// Trailing disambiguator, e.g. film of Alien_(film)
+    static ref DISAMBIGUATOR: Regex = Regex::new(r"_\(([^()]+)\)$").unwrap();
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct TokenCount {
+    pub token: String,
+    pub count: u64,
+}
+
+/// Statistics of the titles of all pages that are no redirects
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+pub struct TitleAnalysis {
+    pub num_titles: u64,
+    /// number of characters -> number of titles
+    pub length_histogram: BTreeMap<usize, u64>,
+    pub avg_length: f64,
+    /// script or character class (see [`char_class`]) -> number of characters
+    pub char_classes: BTreeMap<String, u64>,
+    /// Most common words of the titles, lower case
+    pub common_tokens: Vec<TokenCount>,
+    /// Most common parenthetical disambiguators at the end of titles
+    pub common_disambiguators: Vec<TokenCount>,
+    /// Titles that are a year, e.g. 1999, 44_BC or 2001年
+    pub num_years: u64,
+    /// Titles that are a day of the year, e.g. January_1, 1._Januar or 1月1日
+    pub num_dates: u64,
+    pub year_share: f64,
+    pub date_share: f64,
+}
+
+/// Script of letters, else digit, punctuation (including _) or other
+pub fn char_class(c: char) -> &'static str {
+    match c {
+        '0'..='9' => "digit",
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => "latin",
+        '\u{0370}'..='\u{03FF}' => "greek",
+        '\u{0400}'..='\u{052F}' => "cyrillic",
+        '\u{0590}'..='\u{05FF}' => "hebrew",
+        '\u{0600}'..='\u{06FF}' | '\u{0750}'..='\u{077F}' => "arabic",
+        '\u{0900}'..='\u{097F}' => "devanagari",
+        '\u{3040}'..='\u{309F}' => "hiragana",
+        '\u{30A0}'..='\u{30FF}' => "katakana",
+        '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' => "han",
+        '\u{1100}'..='\u{11FF}' | '\u{AC00}'..='\u{D7AF}' => "hangul",
+        c if c.is_whitespace() || c.is_ascii_punctuation() || c == '_' => "punctuation",
+        '\u{2000}'..='\u{206F}' | '\u{3000}'..='\u{303F}' | '\u{FF00}'..='\u{FF0F}' => {
+            "punctuation"
+        }
+        _ => "other",
+    }
+}
+
+fn is_date(title: &str) -> bool {
+    if DATE.is_match(title) {
+        return true;
+    }
+    // english month names, January_1 or 1_January
+    let title = title.replace('_', " ");
+    ["%B %d %Y", "%d %B %Y"]
+        .iter()
+        .any(|format| NaiveDate::parse_from_str(&format!("{title} 2000"), format).is_ok())
+}
+
+fn top_counts(counts: FxHashMap<String, u64>, top: usize) -> Vec<TokenCount> {
+    let mut counts: Vec<TokenCount> = counts
+        .into_iter()
+        .map(|(token, count)| TokenCount { token, count })
+        .collect();
+    counts.sort_unstable_by(|t1, t2| t2.count.cmp(&t1.count).then(t1.token.cmp(&t2.token)));
+    counts.truncate(top);
+    counts
+}
+
+impl TitleAnalysis {
+    /// Analyses the titles in a single pass
+    pub fn from_titles<S: AsRef<str>>(titles: impl IntoIterator<Item = S>) -> Self {
+        let mut analysis = TitleAnalysis::default();
+        let mut tokens: FxHashMap<String, u64> = FxHashMap::default();
+        let mut disambiguators: FxHashMap<String, u64> = FxHashMap::default();
+        let mut total_length = 0;
+
+        for title in titles {
+            let title = title.as_ref();
+            analysis.num_titles += 1;
+
+            let length = title.chars().count();
+            total_length += length;
+            *analysis.length_histogram.entry(length).or_default() += 1;
+
+            for c in title.chars() {
+                *analysis
+                    .char_classes
+                    .entry(char_class(c).to_string())
+                    .or_default() += 1;
+            }
+
+            for token in title
+                .split(|c: char| c == '_' || c.is_ascii_punctuation())
+                .filter(|token| !token.is_empty())
+            {
+                *tokens.entry(token.to_lowercase()).or_default() += 1;
+            }
+
+            if let Some(captures) = DISAMBIGUATOR.captures(title) {
+                *disambiguators.entry(captures[1].to_string()).or_default() += 1;
+            }
+
+            if YEAR.is_match(title) {
+                analysis.num_years += 1;
+            } else if is_date(title) {
+                analysis.num_dates += 1;
+            }
+        }
+
+        analysis.common_tokens = top_counts(tokens, TOP_TOKENS);
+        analysis.common_disambiguators = top_counts(disambiguators, TOP_TOKENS);
+        analysis.update_averages(total_length as f64);
+        analysis
+    }
+
+    fn update_averages(&mut self, total_length: f64) {
+        let num_titles = self.num_titles.max(1) as f64;
+        self.avg_length = total_length / num_titles;
+        self.year_share = self.num_years as f64 / num_titles;
+        self.date_share = self.num_dates as f64 / num_titles;
+    }
+
+    /// Combined analysis of several wikis. The common tokens and disambiguators are merged from
+    /// the top lists only, so counts of tokens missing in some lists are too low
+    pub fn merge<'a>(analyses: impl IntoIterator<Item = &'a TitleAnalysis>) -> Self {
+        let mut merged = TitleAnalysis::default();
+        let mut tokens: FxHashMap<String, u64> = FxHashMap::default();
+        let mut disambiguators: FxHashMap<String, u64> = FxHashMap::default();
+        let mut total_length = 0.0;
+
+        for analysis in analyses {
+            merged.num_titles += analysis.num_titles;
+            total_length += analysis.avg_length * analysis.num_titles as f64;
+            for (length, count) in &analysis.length_histogram {
+                *merged.length_histogram.entry(*length).or_default() += count;
+            }
+            for (class, count) in &analysis.char_classes {
+                *merged.char_classes.entry(class.clone()).or_default() += count;
+            }
+            for token in &analysis.common_tokens {
+                *tokens.entry(token.token.clone()).or_default() += token.count;
+            }
+            for token in &analysis.common_disambiguators {
+                *disambiguators.entry(token.token.clone()).or_default() += token.count;
+            }
+            merged.num_years += analysis.num_years;
+            merged.num_dates += analysis.num_dates;
+        }
+
+        merged.common_tokens = top_counts(tokens, TOP_TOKENS);
+        merged.common_disambiguators = top_counts(disambiguators, TOP_TOKENS);
+        merged.update_averages(total_length);
+        merged
+    }
+}
+
+pub fn title_analysis(wiki_ident: WikiIdent) -> TitleAnalysis {
+    let conn = Connection::open(&wiki_ident.db_path).unwrap();
+    let mut stmt = conn
+        .prepare("SELECT page_title FROM WikiPage WHERE is_redirect = 0")
+        .unwrap();
+    let titles = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .unwrap()
+        .map(Result::unwrap);
+    TitleAnalysis::from_titles(titles)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::titles::{TitleAnalysis, TokenCount};
+
+    #[test]
+    fn test_title_analysis() {
+        let analysis = TitleAnalysis::from_titles([
+            "Alien_(film)",
+            "Heat_(film)",
+            "Alien_(band)",
+            "1999",
+            "44_BC",
+            "2001年",
+            "January_1",
+            "1._Januar",
+            "1月1日",
+            "Київ",
+            "東京",
+            "Route_66",
+        ]);
+
+        assert_eq!(analysis.num_titles, 12);
+        assert_eq!(analysis.num_years, 3);
+        assert_eq!(analysis.num_dates, 3);
+        assert_eq!(analysis.year_share, 0.25);
+        assert_eq!(analysis.length_histogram[&4], 3);
+        assert_eq!(analysis.char_classes["cyrillic"], 4);
+        assert_eq!(analysis.char_classes["han"], 5);
+        assert_eq!(
+            analysis.common_disambiguators,
+            vec![
+                TokenCount {
+                    token: "film".to_string(),
+                    count: 2
+                },
+                TokenCount {
+                    token: "band".to_string(),
+                    count: 1
+                }
+            ]
+        );
+        assert_eq!(analysis.common_tokens[0].token, "1");
+        assert_eq!(analysis.common_tokens[1].token, "alien");
+
+        let merged = TitleAnalysis::merge([&analysis, &analysis]);
+        assert_eq!(merged.num_titles, 24);
+        assert!((merged.avg_length - analysis.avg_length).abs() < 1e-9);
+        assert_eq!(merged.common_disambiguators[0].count, 4);
+        assert_eq!(merged.date_share, 0.25);
+    }
+}
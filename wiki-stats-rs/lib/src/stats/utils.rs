@@ -1,9 +1,7 @@
 use std::{
     cmp::{Ordering, max_by, min_by},
-    future::Future,
     ops::AddAssign,
     path::{Path, PathBuf},
-    thread,
 };
 
 use fxhash::{FxHashMap, FxHashSet};
@@ -19,39 +17,6 @@ use crate::{
 
 pub static GLOBAL: &str = "global";
 
-pub async fn make_stat_record_async<T, Fut, F>(
-    wikis: Vec<WikiIdent>,
-    func: F,
-    global_func: fn(&mut StatRecord<T>) -> (),
-    existing_stat_record: Option<StatRecord<T>>,
-) -> StatRecord<T>
-where
-    T: Debug + Send + 'static,
-    Fut: Future<Output = T> + Send + 'static,
-    F: Fn(WikiIdent) -> Fut + Send + Sync + 'static + Clone,
-{
-    let mut tasks = vec![];
-    let mut record = existing_stat_record.unwrap_or_else(FxHashMap::default);
-    let completed_wikis: FxHashSet<String> = record.keys().cloned().collect::<FxHashSet<String>>();
-
-    for wiki in wikis {
-        if !completed_wikis.contains(&wiki.wiki_name) {
-            let func = func.clone();
-            tasks.push(tokio::spawn(async move {
-                (func(wiki.clone()).await, wiki.wiki_name)
-            }));
-        }
-    }
-
-    for task in tasks {
-        let (res, wname) = task.await.unwrap();
-        record.insert(wname, res);
-    }
-    global_func(&mut record);
-
-    record
-}
-
 pub async fn make_stat_record_seq<T, F>(
     wikis: Vec<WikiIdent>,
     func: F,
@@ -76,36 +41,16 @@ where
     record
 }
 
-pub async fn make_stat_record<T: Debug + Send + 'static>(
-    wikis: Vec<WikiIdent>,
-    func: fn(WikiIdent) -> T,
-    global_func: fn(&mut StatRecord<T>) -> (),
-    existing_stat_record: Option<StatRecord<T>>,
-) -> StatRecord<T> {
-    let mut tids = vec![];
-    let mut record = existing_stat_record.unwrap_or_else(FxHashMap::default);
-    let completed_wikis: FxHashSet<String> = record.keys().cloned().collect::<FxHashSet<String>>();
-
-    for wiki in wikis {
-        if !completed_wikis.contains(&wiki.wiki_name) {
-            tids.push(thread::spawn(move || {
-                (func(wiki.clone()), wiki.wiki_name.clone())
-            }));
-        }
+/// Sets the global entry to the sum over all wikis
+pub fn sum_global<T: AddAssign + Default + Copy>(record: &mut StatRecord<T>) {
+    record.remove(GLOBAL);
+    let mut sum = T::default();
+    for value in record.values() {
+        sum += *value;
     }
-
-    tids.into_iter().for_each(|th| {
-        let (res, wname) = th.join().expect("can't join thread");
-        record.insert(wname, res);
-    });
-
-    global_func(&mut record);
-
-    record
+    record.insert(GLOBAL.to_string(), sum);
 }
 
-pub fn global_ignore<T>(_: &mut StatRecord<T>) {}
-
 #[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct MaxMinAvg<T, C: PartialOrd> {
     pub min: (T, C),
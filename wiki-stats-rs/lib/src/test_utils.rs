@@ -0,0 +1,61 @@
+//! Graphs and databases shared by the unit tests
+
+use fxhash::FxHashMap;
+use parse_mediawiki_sql::field_types::PageId;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rusqlite::Connection;
+
+use crate::DBCache;
+use crate::sqlite::{page_links, title_id_conv};
+
+/// In memory database with a WikiLink table holding the (from, to) links
+pub(crate) fn graph_db(links: &[(u32, u32)]) -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    page_links::db_setup(&conn);
+    insert_links(&conn, links);
+    conn
+}
+
+pub(crate) fn insert_links(conn: &Connection, links: &[(u32, u32)]) {
+    for (from, to) in links {
+        conn.execute(
+            "INSERT INTO WikiLink (page_id, page_link) VALUES (?1, ?2)",
+            (from, to),
+        )
+        .unwrap();
+    }
+}
+
+/// Creates the WikiPage table and inserts the (page_id, page_title, is_redirect) pages
+pub(crate) fn insert_pages(conn: &Connection, pages: &[(u32, &str, bool)]) {
+    title_id_conv::db_setup(conn);
+    for (page_id, page_title, is_redirect) in pages {
+        conn.execute(
+            "INSERT INTO WikiPage (page_id, page_title, is_redirect) VALUES (?1, ?2, ?3)",
+            (page_id, page_title, is_redirect),
+        )
+        .unwrap();
+    }
+}
+
+/// Outgoing and incoming links of a random graph with pages 1..=num_pages.
+/// Every page gets an outgoing entry, so bfs never falls back to the (empty) db
+pub(crate) fn random_graph(num_pages: u32, num_links: u32, seed: u64) -> (DBCache, DBCache) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut outgoing: DBCache = FxHashMap::default();
+    let mut incoming: DBCache = FxHashMap::default();
+    for id in 1..=num_pages {
+        outgoing.insert(PageId(id), vec![]);
+    }
+
+    for _ in 0..num_links {
+        let from = PageId(rng.gen_range(1..=num_pages));
+        let to = PageId(rng.gen_range(1..=num_pages));
+        let links = outgoing.get_mut(&from).unwrap();
+        if from != to && !links.contains(&to) {
+            links.push(to);
+            incoming.entry(to).or_default().push(from);
+        }
+    }
+    (outgoing, incoming)
+}
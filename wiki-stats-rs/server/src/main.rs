@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
 use axum::extract::{Query, State};
 use axum::http::StatusCode;
@@ -10,9 +11,12 @@ use axum_streams::*;
 use clap::{ArgAction, Parser, crate_version};
 use dotenv::dotenv;
 use futures::{Stream, StreamExt, pin_mut};
+use fxhash::FxHashMap;
 use lazy_static::lazy_static;
 use log::info;
-use parse_mediawiki_sql::field_types::PageTitle;
+use parse_mediawiki_sql::field_types::{PageId, PageTitle};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -21,23 +25,66 @@ use simplelog::{
 };
 use std::process::exit;
 
-use wiki_stats::calc::bfs::{bfs_bidirectional, bfs_stream};
-use wiki_stats::sqlite::page_links::get_cache;
-use wiki_stats::sqlite::{db_wiki_path, get_all_database_files, join_db_wiki_path};
-use wiki_stats::{DBCache, sqlite};
+use wiki_stats::calc::bfs::{PathSource, SpBiStream, bfs_bidirectional, bfs_stream};
+use wiki_stats::calc::difficulty::{DifficultyOptions, game_difficulty, generate_game_pairs};
+use wiki_stats::calc::distance_profile::distance_profile;
+use wiki_stats::calc::landmarks::{LandmarkOracle, bfs_bidirectional_alt};
+use wiki_stats::calc::link_prediction::{LinkScore, suggest_links};
+use wiki_stats::calc::meeting_point::{MeetingObjective, meeting_points};
+use wiki_stats::calc::pagerank::{DEFAULT_DAMPING, pagerank};
+use wiki_stats::calc::reachability::reverse_reachability;
+use wiki_stats::calc::similarity::similar_pages;
+use wiki_stats::calc::traversal::{TraversalMode, shortest_path_mode};
+use wiki_stats::calc::weighted_paths::{
+    EdgeCost, InDegreeCost, PagePenalty, PageRankCost, PathCost, UnitCost, weighted_shortest_path,
+};
+use wiki_stats::sqlite;
+use wiki_stats::sqlite::landmarks::load_landmarks;
+use wiki_stats::sqlite::page_links::LinkCaches;
+use wiki_stats::sqlite::paths::{SpDirection, build_sp, precalced_path_ids};
+use wiki_stats::sqlite::{
+    db_wiki_path, get_all_database_files, join_db_sp_wiki_path, join_db_wiki_path,
+};
 
 // TODO: remove redirects?
 
 // unfortunately necessary, as *I* cant put the cache in the axum state.
 // It seems to create a reference in the server function
 lazy_static! {
-    static ref CACHES: HashMap<String, DBCache> = {
+    static ref CACHES: HashMap<String, LinkCaches> = {
+        let cli = Cli::parse();
+        let (db_dir, wikis) = validate_cli_args(cli.db_path, cli.wikis);
+        get_caches(db_dir, wikis, cli.num_load)
+    };
+    // only wikis with precomputed landmarks (cli graph compute-landmarks)
+    static ref ORACLES: HashMap<String, LandmarkOracle> = {
+        let cli = Cli::parse();
+        let (db_dir, wikis) = validate_cli_args(cli.db_path, cli.wikis);
+        get_oracles(db_dir, wikis)
+    };
+    // PageRank over the cached outgoing links, for the page_rank path cost.
+    // Computed on the first request of a wiki, so it only delays the wikis that use it.
+    // Empty with --num-load, the ranks of a partial graph would be meaningless
+    static ref PAGERANKS: HashMap<String, OnceLock<FxHashMap<PageId, f64>>> = {
+        if Cli::parse().num_load.is_some() {
+            return HashMap::new();
+        }
+        CACHES
+            .keys()
+            .map(|wiki_name| (wiki_name.clone(), OnceLock::new()))
+            .collect()
+    };
+    // sources of the precomputed shortest path trees (cli graph precalc-shortest-paths)
+    static ref SP_SOURCES: HashMap<String, HashSet<(PageId, SpDirection)>> = {
         let cli = Cli::parse();
-        let (_, wikis) = validate_cli_args(cli.db_path, cli.wikis);
-        get_caches(wikis, cli.num_load)
+        let (db_dir, wikis) = validate_cli_args(cli.db_path, cli.wikis);
+        get_sp_sources(db_dir, wikis)
     };
 }
 
+/// Pages the weighted path search settles before giving up
+const MAX_WEIGHTED_VISITED: usize = 2_000_000;
+
 #[derive(Debug)]
 struct StatusError(StatusCode, String);
 
@@ -49,6 +96,44 @@ impl IntoResponse for StatusError {
     }
 }
 
+/// Connection to the database of the wiki, if the server supports it
+fn open_wiki_conn(state: &AppState, wiki_name: &str) -> Result<Connection, StatusError> {
+    if !state.wikis.iter().any(|wiki| wiki == wiki_name) {
+        return Err(StatusError(
+            StatusCode::NOT_FOUND,
+            format!(
+                "Unsupported wiki {wiki_name}. Supported: wikis: {:?}",
+                state.wikis
+            ),
+        ));
+    }
+    Connection::open(join_db_wiki_path(&state.path, wiki_name)).map_err(|_| {
+        StatusError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed connecting to db".to_string(),
+        )
+    })
+}
+
+fn resolve_title(conn: &Connection, wiki_name: &str, title: &str) -> Result<PageId, StatusError> {
+    sqlite::title_id_conv::page_title_to_id(&PageTitle(title.to_string()), conn).ok_or(StatusError(
+        StatusCode::NOT_FOUND,
+        format!("{title} is not a valid page for the {wiki_name}"),
+    ))
+}
+
+/// Runs f on the blocking thread pool. Graph searches take too long for the async workers
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, StatusError> {
+    tokio::task::spawn_blocking(f).await.map_err(|error| {
+        StatusError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed computing the result: {error}"),
+        )
+    })
+}
+
 async fn get_shortest_path(
     State(state): State<AppState>,
     axum::extract::Path((wiki_name, start_title, end_title)): axum::extract::Path<(
@@ -95,7 +180,7 @@ async fn get_shortest_path(
 
     let cache = CACHES.get(&wiki_name).unwrap();
 
-    let stream = bfs_stream(start_link_id, end_link_id, None, cache, path).await;
+    let stream = bfs_stream(start_link_id, end_link_id, None, &cache.outgoing, path).await;
     return Ok(StreamBodyAs::json_nl(stream));
 }
 
@@ -104,12 +189,26 @@ struct SPOptions {
     stream: Option<bool>,
     start_title: String,
     end_title: String,
+    /// Follow incoming or all links instead of the outgoing ones.
+    /// Answered with the direction of every hop, without streaming
+    mode: Option<TraversalMode>,
+    /// Search the cheapest path with Dijkstra instead of the shortest one
+    cost: Option<PathCost>,
+    /// Added to the cost of links to redirects (weighted search only)
+    redirect_penalty: Option<f64>,
+    /// Added to the cost of links to list pages (weighted search only)
+    list_penalty: Option<f64>,
+    /// Added to the cost of links to disambiguation pages (weighted search only)
+    disambiguation_penalty: Option<f64>,
 }
 
+/// Shortest paths between two pages. Without mode and cost, the path is read from a precomputed
+/// shortest path tree if the start (or end) page has one, else all shortest paths are searched
+/// with the bidirectional bfs. The `source` field of the result tells which one was used
 async fn get_shortest_path_bidirectional(
     State(state): State<AppState>,
     axum::extract::Path(wiki_name): axum::extract::Path<String>,
-    params: Query<SPOptions>,
+    Query(params): Query<SPOptions>,
 ) -> Result<impl IntoResponse, StatusError> {
     let start_title = &params.start_title;
     let end_title = &params.end_title;
@@ -119,40 +218,71 @@ async fn get_shortest_path_bidirectional(
         format!("Bidirectional sp: [{wiki_name}] {start_title} -> {end_title}")
     );
 
-    let base_path = state.path;
-    let wikis = state.wikis;
-    if !wikis.contains(&wiki_name) {
+    let conn = open_wiki_conn(&state, &wiki_name)?;
+    let start_link_id = resolve_title(&conn, &wiki_name, start_title)?;
+    let end_link_id = resolve_title(&conn, &wiki_name, end_title)?;
+
+    let directed = params
+        .mode
+        .is_none_or(|mode| mode == TraversalMode::Directed);
+    if params.cost.is_some() && !directed {
         return Err(StatusError(
-            StatusCode::NOT_FOUND,
-            format!(
-                "Unsupported wiki {wiki_name}. Supported: wikis: {:?}",
-                wikis
-            ),
+            StatusCode::BAD_REQUEST,
+            "The weighted search only follows outgoing links".to_string(),
         ));
     }
-    let path = join_db_wiki_path(base_path, &wiki_name);
-    let conn = Connection::open(&path).map_err(|_| {
-        StatusError(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed connecting to db".to_string(),
-        )
-    })?;
 
-    let start_link = PageTitle(start_title.clone());
-    let start_link_id =
-        sqlite::title_id_conv::page_title_to_id(&start_link, &conn).ok_or(StatusError(
-            StatusCode::NOT_FOUND,
-            format!("{start_title} is not a valid page for the {wiki_name}"),
-        ))?;
+    if let Some(cost) = params.cost {
+        let stream = params.stream.unwrap_or(false);
+        let result = run_blocking(move || {
+            weighted_path(&wiki_name, start_link_id, end_link_id, &conn, cost, &params)
+        })
+        .await??;
+        return if !stream {
+            Ok(Json(result).into_response())
+        } else {
+            Ok(StreamBodyAs::json_nl(futures::stream::iter([result])).into_response())
+        };
+    }
 
-    let end_link = PageTitle(end_title.clone());
-    let end_link_id =
-        sqlite::title_id_conv::page_title_to_id(&end_link, &conn).ok_or(StatusError(
-            StatusCode::NOT_FOUND,
-            format!("{end_title} is not a valid page for the {wiki_name}"),
-        ))?;
+    if let Some(mode) = params.mode {
+        let caches = CACHES.get(&wiki_name).unwrap();
+        let result = run_blocking(move || {
+            let start_time = std::time::Instant::now();
+            let result = shortest_path_mode(start_link_id, end_link_id, mode, &conn, caches);
+            let to_title = |pid| {
+                sqlite::title_id_conv::page_id_to_title(pid, &conn)
+                    .unwrap()
+                    .0
+            };
+            json!({
+                "visited": result.as_ref().map_or(0, |result| result.visited),
+                "elapsed_ms": start_time.elapsed().as_millis(),
+                "paths": result.iter().map(|result| result.pages.iter().map(to_title).collect::<Vec<_>>()).collect::<Vec<_>>(),
+                "hops": result.map(|result| result.hops),
+            })
+        })
+        .await?;
+        return if !params.stream.unwrap_or(false) {
+            Ok(Json(result).into_response())
+        } else {
+            Ok(StreamBodyAs::json_nl(futures::stream::iter([result])).into_response())
+        };
+    }
+
+    if let Some(result) =
+        stored_shortest_path(&state.path, &wiki_name, start_link_id, end_link_id, &conn)
+    {
+        return if !params.stream.unwrap_or(false) {
+            Ok(Json(json!(result)).into_response())
+        } else {
+            Ok(StreamBodyAs::json_nl(futures::stream::iter([result])).into_response())
+        };
+    }
 
-    let stream = bfs_bidirectional(start_link_id, end_link_id, path).await;
+    let path = join_db_wiki_path(&state.path, &wiki_name);
+    let caches = CACHES.get(&wiki_name).unwrap().clone();
+    let stream = bfs_bidirectional(start_link_id, end_link_id, path, caches).await;
     if !params.stream.unwrap_or(false) {
         pin_mut!(stream);
         let mut last = stream.next().await;
@@ -172,6 +302,515 @@ async fn get_shortest_path_bidirectional(
     // return Ok(StreamBodyAs::json_nl(stream));
 }
 
+/// Cheapest path by the given link costs. Does not stream, the single result has the fields of
+/// the last bfs item (paths, visited, elapsed_ms) plus the cost of the path
+fn weighted_path(
+    wiki_name: &str,
+    start_link_id: PageId,
+    end_link_id: PageId,
+    conn: &Connection,
+    cost: PathCost,
+    params: &SPOptions,
+) -> Result<serde_json::Value, StatusError> {
+    let start_time = std::time::Instant::now();
+    let caches = CACHES.get(wiki_name).unwrap();
+
+    let edge_cost: Box<dyn EdgeCost> = match cost {
+        PathCost::Unit => Box::new(UnitCost),
+        PathCost::Obscure => Box::new(InDegreeCost::new(&caches.incoming, false)),
+        PathCost::Natural => Box::new(InDegreeCost::new(&caches.incoming, true)),
+        PathCost::PageRank => {
+            let ranks = PAGERANKS.get(wiki_name).ok_or(StatusError(
+                StatusCode::BAD_REQUEST,
+                "The page_rank cost needs all links cached (server started without --num-load)"
+                    .to_string(),
+            ))?;
+            let ranks = ranks.get_or_init(|| pagerank(&caches.outgoing, DEFAULT_DAMPING, 20));
+            Box::new(PageRankCost::new(ranks))
+        }
+    };
+    let mut edge_cost = PagePenalty::new(
+        edge_cost,
+        params.redirect_penalty.unwrap_or(0.0),
+        params.list_penalty.unwrap_or(0.0),
+    )
+    .with_disambiguation_penalty(params.disambiguation_penalty.unwrap_or(0.0));
+
+    let result = weighted_shortest_path(
+        start_link_id,
+        end_link_id,
+        conn,
+        caches,
+        &mut edge_cost,
+        Some(MAX_WEIGHTED_VISITED),
+    )
+    .ok_or(StatusError(
+        StatusCode::NOT_FOUND,
+        format!("No path found within {MAX_WEIGHTED_VISITED} visited pages"),
+    ))?;
+    info!(
+        "Weighted sp ({cost:?}): cost {} in {:?}",
+        result.cost,
+        start_time.elapsed()
+    );
+
+    let path: Vec<String> = result
+        .path
+        .iter()
+        .map(|pid| {
+            sqlite::title_id_conv::page_id_to_title(pid, conn)
+                .unwrap()
+                .0
+        })
+        .collect();
+    Ok(json!({
+        "visited": result.visited,
+        "elapsed_ms": start_time.elapsed().as_millis(),
+        "paths": [path],
+        "cost": result.cost,
+    }))
+}
+
+/// Looks up the path in the precomputed tree of the start page (or the end page).
+/// Returns None if neither has one, in the same format as the last [`bfs_bidirectional`] item otherwise.
+/// The tree holds a single shortest path per page, unlike the bfs which finds all of them, so the
+/// result has the source [`PathSource::StoredTree`]
+fn stored_shortest_path(
+    db_dir: &PathBuf,
+    wiki_name: &str,
+    start_link_id: PageId,
+    end_link_id: PageId,
+    conn: &Connection,
+) -> Option<SpBiStream> {
+    let start_time = std::time::Instant::now();
+    let sources = SP_SOURCES.get(wiki_name)?;
+
+    let (source_id, direction, page_id) =
+        if sources.contains(&(start_link_id, SpDirection::FromSource)) {
+            (start_link_id, SpDirection::FromSource, end_link_id)
+        } else if sources.contains(&(end_link_id, SpDirection::ToSource)) {
+            (end_link_id, SpDirection::ToSource, start_link_id)
+        } else {
+            return None;
+        };
+
+    let conn_sp = Connection::open(join_db_sp_wiki_path(db_dir, wiki_name)).ok()?;
+    let path = build_sp(&conn_sp, source_id, direction, page_id);
+    info!("Answered from stored shortest paths of {source_id:?} ({direction:?})");
+
+    Some(SpBiStream {
+        visited: 0,
+        elapsed_ms: start_time.elapsed().as_millis(),
+        paths: Some(
+            path.map(|path| {
+                path.iter()
+                    .map(|pid| {
+                        sqlite::title_id_conv::page_id_to_title(pid, conn)
+                            .unwrap()
+                            .0
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .into_iter()
+            .collect(),
+        ),
+        cache_hits: 0,
+        cache_misses: 0,
+        cache_hit_ratio: 0.0,
+        source: PathSource::StoredTree,
+    })
+}
+
+#[derive(Deserialize)]
+struct DistanceOptions {
+    start_title: String,
+    end_title: String,
+    /// Also search the shortest path
+    exact: Option<bool>,
+}
+
+async fn get_distance(
+    State(state): State<AppState>,
+    axum::extract::Path(wiki_name): axum::extract::Path<String>,
+    params: Query<DistanceOptions>,
+) -> Result<impl IntoResponse, StatusError> {
+    let start_title = &params.start_title;
+    let end_title = &params.end_title;
+
+    info!("Distance: [{wiki_name}] {start_title} -> {end_title}");
+
+    let conn = open_wiki_conn(&state, &wiki_name)?;
+    let oracle = ORACLES.get(&wiki_name).ok_or(StatusError(
+        StatusCode::NOT_FOUND,
+        format!("No landmarks computed for {wiki_name}"),
+    ))?;
+
+    let start_link_id = resolve_title(&conn, &wiki_name, start_title)?;
+    let end_link_id = resolve_title(&conn, &wiki_name, end_title)?;
+
+    if !params.exact.unwrap_or(false) {
+        let bounds = oracle.distance_bounds(start_link_id, end_link_id);
+        return Ok(Json(json!({ "bounds": bounds })));
+    }
+
+    let caches = CACHES.get(&wiki_name).unwrap();
+    let result = run_blocking(move || {
+        let result = bfs_bidirectional_alt(start_link_id, end_link_id, &conn, caches, oracle);
+        let path = result.path.map(|path| {
+            path.iter()
+                .map(|pid| {
+                    sqlite::title_id_conv::page_id_to_title(pid, &conn)
+                        .unwrap()
+                        .0
+                })
+                .collect::<Vec<String>>()
+        });
+
+        json!({
+            "bounds": result.bounds,
+            "path": path,
+            "visited": result.visited,
+            "pruned_links": result.pruned_links,
+            "elapsed_ms": result.elapsed_ms,
+        })
+    })
+    .await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct SuggestLinksOptions {
+    title: String,
+    /// Suggest pages the page should link to instead of pages that should link to it
+    outgoing: Option<bool>,
+    score: Option<LinkScore>,
+    limit: Option<usize>,
+}
+
+async fn get_link_suggestions(
+    State(state): State<AppState>,
+    axum::extract::Path(wiki_name): axum::extract::Path<String>,
+    params: Query<SuggestLinksOptions>,
+) -> Result<impl IntoResponse, StatusError> {
+    let title = &params.title;
+    info!("Suggest links: [{wiki_name}] {title}");
+
+    let conn = open_wiki_conn(&state, &wiki_name)?;
+    let page_id = resolve_title(&conn, &wiki_name, title)?;
+
+    let caches = CACHES.get(&wiki_name).unwrap();
+    let incoming = !params.outgoing.unwrap_or(false);
+    let score = params.score.unwrap_or(LinkScore::AdamicAdar);
+    let limit = params.limit.unwrap_or(20).min(1000);
+    let suggestions = run_blocking(move || {
+        let suggestions = suggest_links(page_id, &conn, caches, incoming, score, limit);
+
+        suggestions
+            .iter()
+            .map(|suggestion| {
+                json!({
+                    "title": sqlite::title_id_conv::page_id_to_title(&suggestion.page_id, &conn).unwrap().0,
+                    "common_neighbours": suggestion.common_neighbours,
+                    "adamic_adar": suggestion.adamic_adar,
+                    "jaccard": suggestion.jaccard,
+                })
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    Ok(Json(json!({ "suggestions": suggestions })))
+}
+
+#[derive(Deserialize)]
+struct SimilarOptions {
+    title: String,
+    limit: Option<usize>,
+}
+
+async fn get_similar_pages(
+    State(state): State<AppState>,
+    axum::extract::Path(wiki_name): axum::extract::Path<String>,
+    params: Query<SimilarOptions>,
+) -> Result<impl IntoResponse, StatusError> {
+    let title = &params.title;
+    info!("Similar pages: [{wiki_name}] {title}");
+
+    let conn = open_wiki_conn(&state, &wiki_name)?;
+    if !sqlite::table_exists(&conn, "PageSignature") {
+        return Err(StatusError(
+            StatusCode::NOT_FOUND,
+            format!("No page signatures computed for {wiki_name}"),
+        ));
+    }
+
+    let page_id = resolve_title(&conn, &wiki_name, title)?;
+
+    let caches = CACHES.get(&wiki_name).unwrap();
+    let limit = params.limit.unwrap_or(20).min(1000);
+    let similar = run_blocking(move || {
+        let similar = similar_pages(page_id, &conn, caches, limit);
+
+        similar
+            .iter()
+            .map(|page| {
+                json!({
+                    "title": sqlite::title_id_conv::page_id_to_title(&page.page_id, &conn).unwrap().0,
+                    "weighted_cosine": page.weighted_cosine,
+                    "estimated_jaccard": page.estimated_jaccard,
+                })
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    Ok(Json(json!({ "similar": similar })))
+}
+
+#[derive(Deserialize)]
+struct GameDifficultyOptions {
+    start_title: String,
+    end_title: String,
+}
+
+async fn get_game_difficulty(
+    State(state): State<AppState>,
+    axum::extract::Path(wiki_name): axum::extract::Path<String>,
+    params: Query<GameDifficultyOptions>,
+) -> Result<impl IntoResponse, StatusError> {
+    let start_title = &params.start_title;
+    let end_title = &params.end_title;
+    info!("Game difficulty: [{wiki_name}] {start_title} -> {end_title}");
+
+    let conn = open_wiki_conn(&state, &wiki_name)?;
+    let start_link_id = resolve_title(&conn, &wiki_name, start_title)?;
+    let end_link_id = resolve_title(&conn, &wiki_name, end_title)?;
+
+    let caches = CACHES.get(&wiki_name).unwrap();
+    let difficulty = run_blocking(move || {
+        game_difficulty(
+            start_link_id,
+            end_link_id,
+            &conn,
+            caches,
+            DifficultyOptions::default(),
+            &mut StdRng::from_entropy(),
+        )
+    })
+    .await?;
+
+    Ok(Json(json!({ "difficulty": difficulty })))
+}
+
+#[derive(Deserialize)]
+struct GenerateGamesOptions {
+    min_score: Option<f64>,
+    max_score: Option<f64>,
+    num_pairs: Option<usize>,
+    #[serde(default)]
+    exclude_disambiguation: bool,
+}
+
+async fn get_generated_games(
+    State(state): State<AppState>,
+    axum::extract::Path(wiki_name): axum::extract::Path<String>,
+    params: Query<GenerateGamesOptions>,
+) -> Result<impl IntoResponse, StatusError> {
+    info!("Generate games: [{wiki_name}]");
+
+    let conn = open_wiki_conn(&state, &wiki_name)?;
+    let path = join_db_wiki_path(&state.path, &wiki_name);
+
+    let num_pairs = params.num_pairs.unwrap_or(5).min(50);
+    let scores = params.min_score.unwrap_or(0.3)..=params.max_score.unwrap_or(0.7);
+    let options = DifficultyOptions {
+        exclude_disambiguation: params.exclude_disambiguation,
+        ..DifficultyOptions::default()
+    };
+    let caches = CACHES.get(&wiki_name).unwrap();
+    let pairs = run_blocking(move || {
+        let pairs = generate_game_pairs(
+            &path,
+            caches,
+            scores,
+            num_pairs,
+            num_pairs * 20,
+            options,
+            &mut StdRng::from_entropy(),
+        );
+
+        pairs
+            .iter()
+            .map(|pair| {
+                json!({
+                    "start_title": sqlite::title_id_conv::page_id_to_title(&pair.start, &conn).unwrap().0,
+                    "end_title": sqlite::title_id_conv::page_id_to_title(&pair.target, &conn).unwrap().0,
+                    "difficulty": pair.difficulty,
+                })
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    Ok(Json(json!({ "games": pairs })))
+}
+
+#[derive(Deserialize)]
+struct ReachabilityOptions {
+    title: String,
+    max_depth: Option<u32>,
+    /// Titles listed per depth, none by default
+    num_titles: Option<usize>,
+}
+
+async fn get_reachability(
+    State(state): State<AppState>,
+    axum::extract::Path(wiki_name): axum::extract::Path<String>,
+    params: Query<ReachabilityOptions>,
+) -> Result<impl IntoResponse, StatusError> {
+    let title = &params.title;
+    info!("Reachability: [{wiki_name}] {title}");
+
+    let conn = open_wiki_conn(&state, &wiki_name)?;
+    let page_id = resolve_title(&conn, &wiki_name, title)?;
+
+    let max_depth = params.max_depth.unwrap_or(3).min(6);
+    let num_titles = params.num_titles.unwrap_or(0).min(1000);
+    let caches = CACHES.get(&wiki_name).unwrap();
+    let result = run_blocking(move || {
+        let reachability = reverse_reachability(page_id, max_depth, &conn, caches);
+
+        let titles_per_depth: Vec<Vec<String>> = reachability
+            .pages_per_depth
+            .iter()
+            .map(|pages| {
+                pages
+                    .iter()
+                    .take(num_titles)
+                    .map(|pid| {
+                        sqlite::title_id_conv::page_id_to_title(pid, &conn)
+                            .unwrap()
+                            .0
+                    })
+                    .collect()
+            })
+            .collect();
+
+        json!({
+            "max_depth": max_depth,
+            "num_reaching": reachability.num_reaching(),
+            "num_per_depth": reachability.num_per_depth(),
+            "titles_per_depth": titles_per_depth,
+        })
+    })
+    .await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct ProfileOptions {
+    title: String,
+}
+
+async fn get_distance_profile(
+    State(state): State<AppState>,
+    axum::extract::Path(wiki_name): axum::extract::Path<String>,
+    params: Query<ProfileOptions>,
+) -> Result<impl IntoResponse, StatusError> {
+    let title = &params.title;
+    info!("Distance profile: [{wiki_name}] {title}");
+
+    let conn = open_wiki_conn(&state, &wiki_name)?;
+    let page_id = resolve_title(&conn, &wiki_name, title)?;
+
+    let caches = CACHES.get(&wiki_name).unwrap();
+    let result = run_blocking(move || {
+        let profile_json = |direction| {
+            let profile = distance_profile(page_id, direction, &conn, caches);
+            json!({
+                "num_reachable": profile.num_reachable,
+                "deepest_title": sqlite::title_id_conv::page_id_to_title(&profile.deepest_id, &conn).unwrap().0,
+                "max_depth": profile.max_depth,
+                "depth_histogram": profile.depth_histogram,
+            })
+        };
+
+        json!({
+            "outgoing": profile_json(SpDirection::FromSource),
+            "incoming": profile_json(SpDirection::ToSource),
+        })
+    })
+    .await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct MeetingPointOptions {
+    /// Titles of the input pages, separated by |
+    titles: String,
+    mode: Option<TraversalMode>,
+    objective: Option<MeetingObjective>,
+    max_depth: Option<u32>,
+    limit: Option<usize>,
+}
+
+async fn get_meeting_points(
+    State(state): State<AppState>,
+    axum::extract::Path(wiki_name): axum::extract::Path<String>,
+    params: Query<MeetingPointOptions>,
+) -> Result<impl IntoResponse, StatusError> {
+    let titles: Vec<&str> = params.titles.split('|').collect();
+    info!("Meeting points: [{wiki_name}] {titles:?}");
+
+    if titles.len() < 2 || titles.len() > 10 {
+        return Err(StatusError(
+            StatusCode::BAD_REQUEST,
+            "Between 2 and 10 titles are supported".to_string(),
+        ));
+    }
+    let conn = open_wiki_conn(&state, &wiki_name)?;
+
+    let mut inputs = vec![];
+    for title in &titles {
+        inputs.push(resolve_title(&conn, &wiki_name, title)?);
+    }
+
+    let mode = params.mode.unwrap_or(TraversalMode::Undirected);
+    let objective = params.objective.unwrap_or(MeetingObjective::Max);
+    let max_depth = params.max_depth.unwrap_or(3).min(5);
+    let limit = params.limit.unwrap_or(10).min(100);
+    let caches = CACHES.get(&wiki_name).unwrap();
+    let points = run_blocking(move || {
+        let points = meeting_points(&inputs, mode, objective, max_depth, limit, &conn, caches);
+
+        let to_title = |pid: &PageId| {
+            sqlite::title_id_conv::page_id_to_title(pid, &conn)
+                .unwrap()
+                .0
+        };
+        points
+            .iter()
+            .map(|point| {
+                json!({
+                    "title": to_title(&point.page_id),
+                    "distances": point.distances,
+                    "paths": point
+                        .paths
+                        .iter()
+                        .map(|path| path.iter().map(to_title).collect::<Vec<_>>())
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    Ok(Json(json!({ "meeting_points": points })))
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct SpStream {
     some_test_field: String,
@@ -235,16 +874,67 @@ fn validate_cli_args(
     (db_dir, wikis_to_check)
 }
 
-fn get_caches(wikis: impl AsRef<[String]>, num_load: Option<usize>) -> HashMap<String, DBCache> {
-    let mut db_cache: HashMap<String, DBCache> = HashMap::new();
+fn get_caches(
+    db_dir: PathBuf,
+    wikis: impl AsRef<[String]>,
+    num_load: Option<usize>,
+) -> HashMap<String, LinkCaches> {
+    let mut db_cache: HashMap<String, LinkCaches> = HashMap::new();
 
     for wiki in wikis.as_ref().iter() {
-        let cache = get_cache(db_wiki_path(wiki), num_load, false);
-        db_cache.insert(wiki.to_string(), cache);
+        info!("Loading link caches for {wiki}");
+        let caches = LinkCaches::load(join_db_wiki_path(db_dir.clone(), wiki), num_load);
+        db_cache.insert(wiki.to_string(), caches);
     }
     db_cache
 }
 
+fn get_oracles(db_dir: PathBuf, wikis: impl AsRef<[String]>) -> HashMap<String, LandmarkOracle> {
+    let mut oracles: HashMap<String, LandmarkOracle> = HashMap::new();
+
+    for wiki in wikis.as_ref().iter() {
+        if let Some(oracle) = load_landmarks(join_db_wiki_path(db_dir.clone(), wiki)) {
+            info!("Loaded {} landmarks for {wiki}", oracle.num_landmarks());
+            oracles.insert(wiki.to_string(), oracle);
+        }
+    }
+    oracles
+}
+
+fn get_sp_sources(
+    db_dir: PathBuf,
+    wikis: impl AsRef<[String]>,
+) -> HashMap<String, HashSet<(PageId, SpDirection)>> {
+    let mut sp_sources: HashMap<String, HashSet<(PageId, SpDirection)>> = HashMap::new();
+
+    for wiki in wikis.as_ref().iter() {
+        let path = join_db_sp_wiki_path(db_dir.clone(), wiki);
+        if !path.exists() {
+            continue;
+        }
+
+        // only trees of the dump the wiki db was created from, older ones have stale paths
+        let dump_date = Connection::open(join_db_wiki_path(db_dir.clone(), wiki))
+            .ok()
+            .and_then(|conn| sqlite::wiki::dump_date(&conn));
+
+        let mut sources = HashSet::new();
+        for direction in [SpDirection::FromSource, SpDirection::ToSource] {
+            sources.extend(
+                precalced_path_ids(&path, direction, dump_date.as_deref())
+                    .into_iter()
+                    .map(|pid| (pid, direction)),
+            );
+        }
+        info!(
+            "Loaded {} stored shortest path trees for {wiki} (dump {dump_date:?})",
+            sources.len()
+        );
+        sp_sources.insert(wiki.to_string(), sources);
+    }
+    sp_sources
+}
+
 #[derive(Clone)]
 struct AppState {
     wikis: Vec<String>,
@@ -291,6 +981,10 @@ async fn main() {
     .unwrap();
 
     let (db_path, wikis) = validate_cli_args(cli.db_path, cli.wikis);
+    // load the caches before accepting requests instead of on the first one
+    lazy_static::initialize(&CACHES);
+    lazy_static::initialize(&ORACLES);
+    lazy_static::initialize(&SP_SOURCES);
 
     let state = AppState {
         wikis: wikis.clone(),
@@ -306,6 +1000,14 @@ async fn main() {
             get(|| async { "Hello, World! The shortest path endpoint is at /path/<wiki_name>" }),
         )
         .route("/path/:wiki", get(get_shortest_path_bidirectional))
+        .route("/distance/:wiki", get(get_distance))
+        .route("/suggest-links/:wiki", get(get_link_suggestions))
+        .route("/similar/:wiki", get(get_similar_pages))
+        .route("/reachability/:wiki", get(get_reachability))
+        .route("/meeting-points/:wiki", get(get_meeting_points))
+        .route("/profile/:wiki", get(get_distance_profile))
+        .route("/game/difficulty/:wiki", get(get_game_difficulty))
+        .route("/game/generate/:wiki", get(get_generated_games))
         // .route("/test", get(test_json_nl_stream))
         .with_state(state);
 
@@ -339,7 +1041,7 @@ struct Cli {
     #[arg(short, long, default_value_t = 1870)]
     port: u16,
 
-    /// Cache links of num_loads pages.
+    /// Cache links of num_loads pages. Disables the page_rank path cost.
     #[arg(long)]
     num_load: Option<usize>,
 
@@ -359,4 +1061,3 @@ struct Cli {
     #[arg(long, value_name = "PATH", default_value = "wiki-stats-sp-server.log")]
     logfile: PathBuf,
 }
-//TODO: implement cache for bidirectional